@@ -0,0 +1,236 @@
+use super::decode_block_file::load_metadata;
+use crate::decoding::builtin_types;
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{self, OutputFormat};
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use frame_metadata::RuntimeMetadata;
+use scale_info::{PortableRegistry, TypeDef};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Metadata files (as given to `decode-block-file`), in the order that the runtimes they
+    /// came from were upgraded. Each is compared with the one after it.
+    #[arg(required = true, num_args = 2..)]
+    metadata: Vec<PathBuf>,
+
+    /// The format to write any renumbered calls in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+/// The pallet and call name for each (pallet index, call index) pair in some metadata.
+type CallIndices = BTreeMap<(u8, u8), (String, String)>;
+
+/// A call index which refers to a different call in one metadata than in the previous one, as
+/// written with `--format json`.
+#[derive(Serialize, Debug, PartialEq)]
+struct RenumberedCall {
+    pallet_index: u8,
+    call_index: u8,
+    /// The call (as `Pallet.call`) that the index referred to before the upgrade.
+    before: String,
+    /// The call (as `Pallet.call`) that the index refers to after the upgrade, if any.
+    after: Option<String>,
+}
+
+/// The renumbered calls found between two consecutive metadata files.
+#[derive(Serialize)]
+struct UpgradeOutput {
+    from: PathBuf,
+    to: PathBuf,
+    renumbered: Vec<RenumberedCall>,
+}
+
+pub async fn run(opts: Opts) -> anyhow::Result<()> {
+    let mut all_indices = Vec::with_capacity(opts.metadata.len());
+    for path in &opts.metadata {
+        let metadata = load_metadata(path)?;
+        let indices = call_indices(&metadata)
+            .with_context(|| format!("Could not find the calls in {}", path.display()))
+            .context(ErrorKind::Config)?;
+        all_indices.push(indices);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let mut total = 0;
+    for (paths, indices) in opts.metadata.windows(2).zip(all_indices.windows(2)) {
+        let output = UpgradeOutput {
+            from: paths[0].clone(),
+            to: paths[1].clone(),
+            renumbered: renumbered_calls(&indices[0], &indices[1]),
+        };
+        total += output.renumbered.len();
+
+        if opts.format.is_json() {
+            output::write_json_line(&mut stdout, &output)?;
+            continue;
+        }
+        if output.renumbered.is_empty() {
+            continue;
+        }
+        writeln!(
+            stdout,
+            "{} -> {}:",
+            output.from.display(),
+            output.to.display()
+        )?;
+        for call in &output.renumbered {
+            let after = call.after.as_deref().unwrap_or("nothing");
+            writeln!(
+                stdout,
+                "  WARNING: call index ({}, {}) was {} but is now {after}",
+                call.pallet_index, call.call_index, call.before,
+            )?;
+        }
+    }
+
+    if opts.format == OutputFormat::Text {
+        writeln!(
+            stdout,
+            "{total} renumbered calls found across {} upgrades",
+            opts.metadata.len() - 1
+        )?;
+    }
+    Ok(())
+}
+
+/// Find the pallet and call name for every call in some metadata.
+fn call_indices(metadata: &RuntimeMetadata) -> anyhow::Result<CallIndices> {
+    match metadata {
+        RuntimeMetadata::V14(m) => Ok(portable_call_indices(
+            &m.types,
+            m.pallets
+                .iter()
+                .map(|p| (p.index, &p.name, p.calls.as_ref().map(|c| c.ty.id))),
+        )),
+        RuntimeMetadata::V15(m) => Ok(portable_call_indices(
+            &m.types,
+            m.pallets
+                .iter()
+                .map(|p| (p.index, &p.name, p.calls.as_ref().map(|c| c.ty.id))),
+        )),
+        RuntimeMetadata::V16(m) => Ok(portable_call_indices(
+            &m.types,
+            m.pallets
+                .iter()
+                .map(|p| (p.index, &p.name, p.calls.as_ref().map(|c| c.ty.id))),
+        )),
+        RuntimeMetadata::V8(_)
+        | RuntimeMetadata::V9(_)
+        | RuntimeMetadata::V10(_)
+        | RuntimeMetadata::V11(_)
+        | RuntimeMetadata::V12(_)
+        | RuntimeMetadata::V13(_) => {
+            // The calls in older metadata are given the same way as the `builtin::Call` type
+            // that we generate from it, so use that.
+            let types = builtin_types::builtin_types_from_metadata(metadata)?;
+            let pallets = builtin_types::enum_variants(&types, "builtin::Call")?
+                .ok_or_else(|| anyhow!("Could not find the builtin::Call type"))?;
+
+            let mut indices = CallIndices::new();
+            for pallet in pallets {
+                for (_, pallet_enum) in &pallet.fields {
+                    let calls = builtin_types::enum_variants(&types, pallet_enum)?;
+                    for call in calls.into_iter().flatten() {
+                        indices
+                            .insert((pallet.index, call.index), (pallet.name.clone(), call.name));
+                    }
+                }
+            }
+            Ok(indices)
+        }
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    }
+}
+
+fn portable_call_indices<'a>(
+    types: &PortableRegistry,
+    pallets: impl Iterator<Item = (u8, &'a String, Option<u32>)>,
+) -> CallIndices {
+    let mut indices = CallIndices::new();
+    for (pallet_index, pallet_name, calls_id) in pallets {
+        let Some(ty) = calls_id.and_then(|id| types.resolve(id)) else {
+            continue;
+        };
+        let TypeDef::Variant(calls) = &ty.type_def else {
+            continue;
+        };
+        for call in &calls.variants {
+            indices.insert(
+                (pallet_index, call.index),
+                (pallet_name.clone(), call.name.clone()),
+            );
+        }
+    }
+    indices
+}
+
+/// Find each call index in `before` which refers to some other call (or no call) in `after`.
+/// New calls at indexes that weren't used before can't be confused with older ones, and
+/// pallets are sometimes renamed without their calls changing, so neither is reported.
+fn renumbered_calls(before: &CallIndices, after: &CallIndices) -> Vec<RenumberedCall> {
+    let name = |(pallet, call): &(String, String)| format!("{pallet}.{call}");
+    before
+        .iter()
+        .filter(|(index, (_, call))| after.get(index).map(|(_, c)| c) != Some(call))
+        .map(|(&(pallet_index, call_index), call)| RenumberedCall {
+            pallet_index,
+            call_index,
+            before: name(call),
+            after: after.get(&(pallet_index, call_index)).map(name),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn indices(calls: &[(u8, u8, &str, &str)]) -> CallIndices {
+        calls
+            .iter()
+            .map(|&(p, c, pallet, call)| ((p, c), (pallet.to_owned(), call.to_owned())))
+            .collect()
+    }
+
+    #[test]
+    fn finds_renumbered_calls() {
+        let before = indices(&[
+            (4, 0, "Balances", "transfer"),
+            (4, 1, "Balances", "set_balance"),
+            (5, 0, "Staking", "bond"),
+            (7, 0, "ElectionsPhragmen", "vote"),
+        ]);
+        let after = indices(&[
+            (4, 0, "Balances", "transfer"),
+            (4, 1, "Balances", "force_transfer"),
+            (4, 2, "Balances", "set_balance"),
+            (6, 0, "Staking", "bond"),
+            // Renamed pallets are fine as long as their calls stay put.
+            (7, 0, "PhragmenElection", "vote"),
+        ]);
+        assert_eq!(
+            renumbered_calls(&before, &after),
+            vec![
+                RenumberedCall {
+                    pallet_index: 4,
+                    call_index: 1,
+                    before: "Balances.set_balance".to_owned(),
+                    after: Some("Balances.force_transfer".to_owned()),
+                },
+                RenumberedCall {
+                    pallet_index: 5,
+                    call_index: 0,
+                    before: "Staking.bond".to_owned(),
+                    after: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use super::decode_blocks::{decode_extrinsics, write_block, Output, WriteOpts};
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::{decode_metadata, DecodingContextCache};
+use crate::decoding::historic_types::{load_historic_types, warn_if_no_spec_types};
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::OutputFormat;
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use frame_metadata::RuntimeMetadata;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use subxt::backend::legacy::rpc_methods::{BlockDetails, Bytes};
+use subxt::config::Header;
+use subxt::utils::H256;
+use subxt::PolkadotConfig;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// The metadata to decode the blocks with. This is the output of `fetch-metadata --binary`,
+    /// or the hex encoded metadata returned from `state_getMetadata`.
+    #[arg(short, long)]
+    metadata: PathBuf,
+
+    /// A file containing the blocks to decode. This is either the output of
+    /// `decode-blocks --format json --print-bytes`, or `chain_getBlock` responses, given as a
+    /// JSON array or one after the other.
+    #[arg(short, long)]
+    blocks: PathBuf,
+
+    /// The spec version to pick types from the types file for. This must be given if the blocks
+    /// are `chain_getBlock` responses, which don't contain it.
+    #[arg(long)]
+    spec_version: Option<u32>,
+
+    /// Only log errors; don't log extrinsics that decode successfully.
+    #[arg(short, long)]
+    errors_only: bool,
+
+    /// Print the hex encoded extrinsic bytes too. In JSON output these are attached
+    /// to each item, so that it can be checked or decoded again later without refetching it.
+    #[arg(long, visible_alias = "include-raw")]
+    print_bytes: bool,
+
+    /// The format to write decoded blocks in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let historic_types = Arc::new(load_historic_types(&opts.types)?);
+    let contexts = DecodingContextCache::new(options.clone());
+    // Everything is decoded with the pinned metadata instead, if there is some.
+    let metadata = match &options.pinned_metadata {
+        Some(metadata) => metadata.clone(),
+        None => Arc::new(load_metadata(&opts.metadata)?),
+    };
+    let blocks = load_blocks(&opts.blocks)?;
+
+    let write_opts = WriteOpts {
+        format: opts.format,
+        errors_only: opts.errors_only,
+        print_bytes: opts.print_bytes,
+    };
+
+    let mut saw_decode_errors = false;
+    for block in blocks {
+        let spec_version = block
+            .spec_version
+            .or(opts.spec_version)
+            .ok_or_else(|| {
+                anyhow!(
+                    "The spec version of block {} is unknown; pass --spec-version",
+                    block.number
+                )
+            })
+            .context(ErrorKind::Config)?;
+
+        let context = contexts.prepare(spec_version, metadata.clone(), &historic_types)?;
+        warn_if_no_spec_types(block.number, spec_version, &options, opts.format);
+        let extrinsics = decode_extrinsics(
+            block.extrinsics,
+            &context.metadata,
+            &context.types,
+            &options,
+        );
+
+        let output = Output {
+            spec_version,
+            block_number: block.number,
+            block_hash: block.hash,
+            parent_hash: None,
+            extrinsics,
+            endpoint: None,
+            explainer: None,
+            docs_from: None,
+            outcomes: None,
+        };
+        saw_decode_errors |= write_block(std::io::stdout().lock(), &output, write_opts, &options)?;
+    }
+
+    if saw_decode_errors {
+        return Err(
+            anyhow!("Some extrinsics could not be decoded").context(ErrorKind::DecodeErrors)
+        );
+    }
+    Ok(())
+}
+
+/// Load metadata from a file containing it either as bytes or as hex, with or
+/// without the "meta" magic number prefix that `state_getMetadata` returns.
+pub fn load_metadata(path: &Path) -> anyhow::Result<RuntimeMetadata> {
+    let bytes = std::fs::read(path)
+        .with_context(|| "Could not load metadata")
+        .context(ErrorKind::Config)?;
+
+    let bytes = match std::str::from_utf8(&bytes) {
+        Ok(s) if s.trim().starts_with("0x") => hex::decode(&s.trim()[2..])
+            .with_context(|| "Could not decode hex metadata")
+            .context(ErrorKind::Config)?,
+        _ => bytes,
+    };
+
+    decode_metadata(&bytes).context(ErrorKind::Config)
+}
+
+/// A block that we've loaded from a file.
+struct FileBlock {
+    number: u64,
+    hash: H256,
+    spec_version: Option<u32>,
+    extrinsics: Vec<Bytes>,
+}
+
+/// The parts of `decode-blocks --format json --print-bytes` output that we need.
+#[derive(serde::Deserialize)]
+struct PrintedBlock {
+    block_number: u64,
+    block_hash: H256,
+    spec_version: u32,
+    extrinsics: Vec<PrintedExtrinsic>,
+}
+
+#[derive(serde::Deserialize)]
+struct PrintedExtrinsic {
+    bytes: Option<Bytes>,
+}
+
+fn load_blocks(path: &Path) -> anyhow::Result<Vec<FileBlock>> {
+    let blocks_str = std::fs::read_to_string(path)
+        .with_context(|| "Could not load blocks")
+        .context(ErrorKind::Config)?;
+
+    let mut blocks = Vec::new();
+    for value in serde_json::Deserializer::from_str(&blocks_str).into_iter::<serde_json::Value>() {
+        let value = value
+            .with_context(|| "Could not parse blocks file as JSON")
+            .context(ErrorKind::Config)?;
+        match value {
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    blocks.push(parse_block(value).context(ErrorKind::Config)?);
+                }
+            }
+            value => blocks.push(parse_block(value).context(ErrorKind::Config)?),
+        }
+    }
+    Ok(blocks)
+}
+
+fn parse_block(mut value: serde_json::Value) -> anyhow::Result<FileBlock> {
+    // Unwrap full JSON-RPC responses.
+    if let Some(result) = value.get_mut("result") {
+        value = result.take();
+    }
+
+    if value.get("block").is_some() {
+        let details: BlockDetails<PolkadotConfig> = serde_json::from_value(value)
+            .with_context(|| "Could not parse chain_getBlock response")?;
+        let header = details.block.header;
+        return Ok(FileBlock {
+            number: header.number().into(),
+            hash: header.hash(),
+            spec_version: None,
+            extrinsics: details.block.extrinsics,
+        });
+    }
+
+    let printed: PrintedBlock = serde_json::from_value(value).with_context(|| {
+        "Could not parse block; expected chain_getBlock or decode-blocks output"
+    })?;
+    let extrinsics = printed
+        .extrinsics
+        .into_iter()
+        .map(|ext| ext.bytes)
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| {
+            anyhow!(
+                "Block {} is missing extrinsic bytes; was it output using --print-bytes?",
+                printed.block_number
+            )
+        })?;
+    Ok(FileBlock {
+        number: printed.block_number,
+        hash: printed.block_hash,
+        spec_version: Some(printed.spec_version),
+        extrinsics,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_both_block_formats() {
+        let printed = serde_json::json!({
+            "block_number": 10,
+            "block_hash": format!("0x{}", "11".repeat(32)),
+            "spec_version": 0,
+            "extrinsics": [{ "index": 0, "bytes": "0x0102", "decoded": {} }]
+        });
+        let block = parse_block(printed).unwrap();
+        assert_eq!(block.number, 10);
+        assert_eq!(block.spec_version, Some(0));
+        assert_eq!(block.extrinsics[0].0, vec![1, 2]);
+
+        let rpc_response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "block": {
+                    "header": {
+                        "parentHash": format!("0x{}", "00".repeat(32)),
+                        "number": "0x14",
+                        "stateRoot": format!("0x{}", "00".repeat(32)),
+                        "extrinsicsRoot": format!("0x{}", "00".repeat(32)),
+                        "digest": { "logs": [] }
+                    },
+                    "extrinsics": ["0x0304"]
+                },
+                "justifications": null
+            }
+        });
+        let block = parse_block(rpc_response).unwrap();
+        assert_eq!(block.number, 20);
+        assert_eq!(block.spec_version, None);
+        assert_eq!(block.extrinsics[0].0, vec![3, 4]);
+    }
+}
@@ -0,0 +1,364 @@
+use super::decode_blocks::{fetch_block_info, PrefetchedBlock};
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::{DecodingContext, DecodingContextCache};
+use crate::decoding::events::{decode_events, events_storage_key, Event};
+use crate::decoding::historic_types::{load_historic_types, warn_if_no_spec_types};
+use crate::utils;
+use crate::utils::error_category;
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{
+    self, BlockEventsOutput, ErrorOutput, EventOutput, OutputFormat, RunnerErrorOutput,
+};
+use crate::utils::profile::{self, Stage};
+use crate::utils::rpc::RpcOpts;
+use crate::utils::run_config::RunConfig;
+use crate::utils::runner::{DecodePool, RoundRobin, Runner, RunnerItem};
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use scale_info_legacy::ChainTypeRegistry;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use subxt::backend::{legacy::LegacyRpcMethods, rpc::RpcClient};
+use subxt::utils::H256;
+use subxt::PolkadotConfig;
+use tokio::sync::Mutex;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// URL of the node(s) to connect to.
+    /// Defaults to using Polkadot RPC URLs if not given.
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// How many blocks to fetch events from in parallel. Each of these uses its own connection.
+    #[arg(long, alias = "connections")]
+    fetch_concurrency: Option<usize>,
+
+    /// How many threads to decode fetched events on. Defaults to the number of CPUs.
+    #[arg(long)]
+    decode_threads: Option<usize>,
+
+    /// Only log errors; don't log events that decode successfully.
+    #[arg(short, long)]
+    errors_only: bool,
+
+    /// Keep outputting blocks once we hit an error.
+    #[arg(long)]
+    continue_on_error: bool,
+
+    /// Block number to start from.
+    #[arg(short, long)]
+    starting_block: Option<u64>,
+
+    /// Block number to stop after. Defaults to continuing until the latest block.
+    #[arg(long)]
+    ending_block: Option<u64>,
+
+    /// The format to write decoded events in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let errors_only = opts.errors_only;
+    let continue_on_error = opts.continue_on_error;
+    let format = opts.format;
+    let ending_block = opts.ending_block;
+    let start_block_num = opts.starting_block.unwrap_or(0);
+    let fetch_concurrency = opts.fetch_concurrency.unwrap_or(1);
+    let decode_pool = DecodePool::new(opts.decode_threads);
+    // Use our default or built-in URLs if not provided.
+    let urls = RoundRobin::new(
+        utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc).await?,
+    );
+
+    // Our base type mappings that we'll use to decode pre-V14 events.
+    let historic_types = Arc::new(load_historic_types(&opts.types)?);
+    // The decoding contexts prepared so far, shared by every task.
+    let contexts = Arc::new(DecodingContextCache::new(options.clone()));
+
+    let mut config = RunConfig::new("decode-events", urls.items(), &opts.types)?;
+    config.filter("starting-block", Some(start_block_num));
+    config.filter("ending-block", ending_block);
+    config.flag("errors-only", errors_only);
+    eprintln!("{config}");
+
+    let rpc_opts = opts.rpc;
+    let saw_decode_errors = Arc::new(AtomicBool::new(false));
+    let task_saw_decode_errors = saw_decode_errors.clone();
+
+    let runner = Runner::new(
+        // Initial state; each task fetches the next URl to connect to.
+        urls,
+        // Turn each URL into some state that we'll reuse to fetch a bunch of blocks. This reruns on error.
+        move |_n, urls| {
+            let url = urls.get().to_owned();
+            let rpc_opts = rpc_opts.clone();
+            async move {
+                let state = RunnerState::connect(url, &rpc_opts).await?;
+                Ok(Some(Arc::new(Mutex::new(state))))
+            }
+        },
+        // Fetch the events of a block and decode them. This runs in parallel for number of initial state items.
+        move |block_number, state| {
+            let historic_types = historic_types.clone();
+            let contexts = contexts.clone();
+            let decode_pool = decode_pool.clone();
+            let state = state.clone();
+            async move {
+                if ending_block.is_some_and(|end| block_number > end) {
+                    return Ok(None);
+                }
+                let mut state = state.lock().await;
+                fetch_events(
+                    &mut state,
+                    block_number,
+                    &contexts,
+                    &historic_types,
+                    &decode_pool,
+                    format,
+                )
+                .await
+            }
+        },
+        // Log the output. This runs sequentially, in order of task numbers.
+        move |item: RunnerItem<Output>| {
+            let output = match item {
+                RunnerItem::Output(output) => output,
+                RunnerItem::Error(e) => {
+                    let err = RunnerErrorOutput::new(&e);
+                    return output::write_runner_error(format, std::io::stdout().lock(), &err);
+                }
+            };
+            let is_error = write_events(
+                std::io::stdout().lock(),
+                &output,
+                format,
+                errors_only,
+                &options,
+            )?;
+            if is_error {
+                task_saw_decode_errors.store(true, Ordering::Relaxed);
+                if !continue_on_error {
+                    return Err(
+                        anyhow!("Stopping: error decoding events").context(ErrorKind::DecodeErrors)
+                    );
+                }
+            }
+            Ok(())
+        },
+    );
+
+    runner.run(fetch_concurrency, start_block_num).await?;
+
+    // If we continued on error, we still want to report that errors happened.
+    if saw_decode_errors.load(Ordering::Relaxed) {
+        return Err(anyhow!("Some events could not be decoded").context(ErrorKind::DecodeErrors));
+    }
+    Ok(())
+}
+
+/// Write out the decoded events of a block, returning whether they failed to decode.
+fn write_events<W: std::io::Write>(
+    mut stdout: W,
+    output: &Output,
+    format: OutputFormat,
+    errors_only: bool,
+    decode_opts: &DecodeOptions,
+) -> anyhow::Result<bool> {
+    let is_error = output.events.is_err();
+    if let Err(e) = &output.events {
+        error_category::count(e);
+    }
+    if errors_only && !is_error {
+        return Ok(false);
+    }
+
+    if format.is_json() {
+        let (events, error) = match &output.events {
+            Ok(events) => (
+                events
+                    .iter()
+                    .cloned()
+                    .enumerate()
+                    .map(|(index, event)| EventOutput::new(index, event))
+                    .collect(),
+                None,
+            ),
+            Err(e) => (vec![], Some(ErrorOutput::new(e))),
+        };
+        let block = BlockEventsOutput {
+            block_number: output.block_number,
+            block_hash: subxt::utils::to_hex(output.block_hash),
+            spec_version: output.spec_version,
+            endpoint: output.endpoint.clone(),
+            events,
+            error,
+        };
+        output::write_output_line(format, &mut stdout, &block)?;
+        return Ok(is_error);
+    }
+
+    writeln!(stdout, "==============================================")?;
+    writeln!(
+        stdout,
+        "Block {} ({})",
+        output.block_number,
+        subxt::utils::to_hex(output.block_hash)
+    )?;
+    writeln!(stdout, "Spec version {}", output.spec_version)?;
+    match &output.events {
+        Ok(events) => {
+            for (index, event) in events.iter().enumerate() {
+                writeln!(
+                    stdout,
+                    "  Event {index} ({}): {}.{}",
+                    event.phase_name(),
+                    event.pallet,
+                    event.name
+                )?;
+                for (name, value) in event.named_fields() {
+                    write!(stdout, "    {name}: ")?;
+                    utils::write_value(
+                        utils::IndentedWriter::<4, _>(&mut stdout),
+                        value,
+                        decode_opts,
+                    )?;
+                    writeln!(stdout)?;
+                }
+            }
+        }
+        Err(e) => {
+            writeln!(
+                stdout,
+                "Error decoding events (fetched from {}): {e:?}",
+                output.endpoint
+            )?;
+        }
+    }
+    Ok(is_error)
+}
+
+/// A connection to fetch events with, and the context to decode them with at the spec version
+/// that we last fetched events at.
+pub(super) struct RunnerState {
+    url: String,
+    rpc_client: RpcClient,
+    rpcs: LegacyRpcMethods<PolkadotConfig>,
+    current_context: Option<Arc<DecodingContext>>,
+}
+
+impl RunnerState {
+    pub(super) async fn connect(url: String, rpc_opts: &RpcOpts) -> anyhow::Result<Self> {
+        let rpc_client = rpc_opts.connect(&url).await?;
+        Ok(RunnerState {
+            url,
+            rpc_client: rpc_client.clone(),
+            rpcs: LegacyRpcMethods::<PolkadotConfig>::new(rpc_client),
+            current_context: None,
+        })
+    }
+}
+
+/// Fetch the events emitted in some block and decode them. Nothing is returned if the block
+/// doesn't exist yet. Failing to decode the events isn't an error here; it's given in the output.
+pub(super) async fn fetch_events(
+    state: &mut RunnerState,
+    block_number: u64,
+    contexts: &DecodingContextCache,
+    historic_types: &Arc<ChainTypeRegistry>,
+    decode_pool: &DecodePool,
+    format: OutputFormat,
+) -> anyhow::Result<Option<Output>> {
+    let url = state.url.clone();
+    // Say which node any errors came from, since some nodes return bad data for particular
+    // ranges of blocks.
+    let output: anyhow::Result<_> = async {
+        let Some(PrefetchedBlock {
+            runtime_update_block_hash,
+            block_hash,
+            spec_version,
+        }) = fetch_block_info(&state.rpcs, block_number).await?
+        else {
+            return Ok(None);
+        };
+
+        if state
+            .current_context
+            .as_ref()
+            .is_none_or(|context| context.spec_version != spec_version)
+        {
+            let rpc_client = &state.rpc_client;
+            let context = contexts
+                .get_or_prepare(spec_version, historic_types, || async {
+                    if let Some(metadata) = &contexts.options().pinned_metadata {
+                        return Ok(metadata.clone());
+                    }
+                    profile::timed(
+                        spec_version,
+                        Stage::MetadataDownload,
+                        super::fetch_metadata::state_get_metadata(
+                            rpc_client,
+                            Some(runtime_update_block_hash),
+                        ),
+                    )
+                    .await
+                    .map(Arc::new)
+                })
+                .await?;
+            state.current_context = Some(context);
+        }
+        let context = state.current_context.clone().unwrap();
+        warn_if_no_spec_types(block_number, spec_version, &context.options, format);
+
+        // The events emitted in a block are in storage at that block, until the next block
+        // clears them.
+        let storage_key = events_storage_key(&context.metadata, &context.types)?;
+        let bytes = state
+            .rpcs
+            .state_get_storage(&storage_key, Some(block_hash))
+            .await
+            .with_context(|| "Could not fetch System.Events")?;
+
+        // Fetching is done; decode the events on the decode pool. Blocks that emitted no events
+        // (such as the genesis block) may have no entry at all.
+        let events = match bytes {
+            Some(bytes) => {
+                decode_pool
+                    .run(move || {
+                        decode_events(&bytes, &context.metadata, &context.types, &context.options)
+                    })
+                    .await?
+            }
+            None => Ok(vec![]),
+        };
+
+        Ok(Some(Output {
+            block_number,
+            block_hash,
+            spec_version,
+            endpoint: state.url.clone(),
+            events,
+        }))
+    }
+    .await;
+    output.with_context(|| format!("Could not fetch events for block {block_number} from {url}"))
+}
+
+pub(super) struct Output {
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub spec_version: u32,
+    /// The URL of the node that the events were fetched from.
+    pub endpoint: String,
+    pub events: anyhow::Result<Vec<Event>>,
+}
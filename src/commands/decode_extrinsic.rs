@@ -0,0 +1,139 @@
+use super::decode_block_file::load_metadata;
+use super::decode_blocks::{decode_extrinsics, extrinsic_output, write_extrinsic, Output};
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::prepare_decoding_context;
+use crate::decoding::historic_types::{load_historic_types, warn_if_no_spec_types};
+use crate::utils::error_category;
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{self, OutputFormat};
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use subxt::backend::legacy::rpc_methods::Bytes;
+use subxt::utils::H256;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// The metadata to decode the extrinsic with. This is the output of
+    /// `fetch-metadata --binary`, or the hex encoded metadata returned from `state_getMetadata`.
+    #[arg(short, long)]
+    metadata: PathBuf,
+
+    /// The spec version to pick types from the types file for.
+    #[arg(long)]
+    spec_version: u32,
+
+    /// The hex encoded extrinsic to decode (eg as written by `decode-blocks --print-bytes`).
+    /// This is read from stdin if not given.
+    #[arg(long)]
+    bytes: Option<String>,
+
+    /// The block that the extrinsic is from. If given, the blocks that a mortal extrinsic was
+    /// valid in are written out too.
+    #[arg(long)]
+    block_number: Option<u64>,
+
+    /// The format to write the decoded extrinsic in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let historic_types = load_historic_types(&opts.types)?;
+    // Everything is decoded with the pinned metadata instead, if there is some.
+    let metadata = match &options.pinned_metadata {
+        Some(metadata) => metadata.clone(),
+        None => Arc::new(load_metadata(&opts.metadata)?),
+    };
+    let bytes = match opts.bytes {
+        Some(bytes) => bytes,
+        None => {
+            let mut bytes = String::new();
+            std::io::stdin()
+                .read_to_string(&mut bytes)
+                .with_context(|| "Could not read the extrinsic from stdin")?;
+            bytes
+        }
+    };
+    let bytes = parse_hex(&bytes)
+        .with_context(|| "Could not decode the extrinsic bytes")
+        .context(ErrorKind::Config)?;
+
+    let context = prepare_decoding_context(
+        opts.spec_version,
+        metadata,
+        &historic_types,
+        options.clone(),
+    )?;
+    let block_number = opts.block_number.unwrap_or(0);
+    warn_if_no_spec_types(block_number, opts.spec_version, &options, opts.format);
+    let output = Output {
+        spec_version: opts.spec_version,
+        block_number,
+        block_hash: H256::zero(),
+        parent_hash: None,
+        extrinsics: decode_extrinsics(
+            vec![Bytes(bytes)],
+            &context.metadata,
+            &context.types,
+            &options,
+        ),
+        endpoint: None,
+        explainer: None,
+        docs_from: None,
+        outcomes: None,
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    if opts.format.is_json() {
+        let mut ext = extrinsic_output(&output, 0, false);
+        if opts.block_number.is_none() {
+            if let Some(decoded) = &mut ext.decoded {
+                decoded.mortality = None;
+            }
+        }
+        output::write_output_line(opts.format, &mut stdout, &ext)?;
+    } else if let Err(e) = &output.extrinsics[0].1 {
+        writeln!(stdout, "Error decoding extrinsic: {e:?}")?;
+    } else {
+        write_extrinsic(
+            &mut stdout,
+            &output,
+            0,
+            opts.block_number.is_some(),
+            &options,
+        )?;
+    }
+
+    if let Err(e) = &output.extrinsics[0].1 {
+        error_category::count(e);
+        return Err(anyhow!("The extrinsic could not be decoded").context(ErrorKind::DecodeErrors));
+    }
+    Ok(())
+}
+
+/// Parse some hex, with or without a leading `0x` and surrounding whitespace.
+pub(super) fn parse_hex(s: &str) -> anyhow::Result<Vec<u8>> {
+    let s = s.trim();
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    Ok(hex::decode(s)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_hex_with_or_without_prefix() {
+        assert_eq!(parse_hex("0x0102\n").unwrap(), vec![1, 2]);
+        assert_eq!(parse_hex(" 0a0b").unwrap(), vec![10, 11]);
+        assert!(parse_hex("0xzz").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use super::decode_block_file::load_metadata;
+use super::decode_extrinsic::parse_hex;
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::prepare_decoding_context;
+use crate::decoding::historic_types::{load_historic_types, warn_if_no_spec_types};
+use crate::decoding::storage_decoder::{self, decode_storage_keys, decode_storage_value};
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{self, ErrorOutput, OutputFormat, StorageKeyValOutput};
+use crate::utils::{error_category, write_value, IndentedWriter};
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// The metadata to decode the storage item with. This is the output of
+    /// `fetch-metadata --binary`, or the hex encoded metadata returned from `state_getMetadata`.
+    #[arg(short, long)]
+    metadata: PathBuf,
+
+    /// The spec version to pick types from the types file for.
+    #[arg(long)]
+    spec_version: u32,
+
+    /// The pallet that the storage entry is in.
+    #[arg(long)]
+    pallet: String,
+
+    /// The name of the storage entry.
+    #[arg(long)]
+    entry: String,
+
+    /// The hex encoded storage key, including the hashed pallet and entry names at the start.
+    /// Defaults to just those, which is the whole key for plain storage entries.
+    #[arg(long)]
+    key: Option<String>,
+
+    /// The hex encoded storage value. Only the key is decoded if this isn't given.
+    #[arg(long)]
+    value: Option<String>,
+
+    /// The format to write the decoded key and value in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let historic_types = load_historic_types(&opts.types)?;
+    // Everything is decoded with the pinned metadata instead, if there is some.
+    let metadata = match &options.pinned_metadata {
+        Some(metadata) => metadata.clone(),
+        None => Arc::new(load_metadata(&opts.metadata)?),
+    };
+    let (pallet, entry) = (&opts.pallet, &opts.entry);
+
+    let key_bytes = match &opts.key {
+        Some(key) => parse_hex(key)
+            .with_context(|| "Could not decode the key")
+            .context(ErrorKind::Config)?,
+        None => {
+            let mut key = sp_crypto_hashing::twox_128(pallet.as_bytes()).to_vec();
+            key.extend(sp_crypto_hashing::twox_128(entry.as_bytes()));
+            key
+        }
+    };
+    let value_bytes = opts
+        .value
+        .as_deref()
+        .map(parse_hex)
+        .transpose()
+        .with_context(|| "Could not decode the value")
+        .context(ErrorKind::Config)?;
+
+    let context = prepare_decoding_context(
+        opts.spec_version,
+        metadata,
+        &historic_types,
+        options.clone(),
+    )?;
+    warn_if_no_spec_types(0, opts.spec_version, &options, opts.format);
+    let key = decode_storage_keys(
+        pallet,
+        entry,
+        &key_bytes,
+        &context.metadata,
+        &context.types,
+        &options,
+    )
+    .with_context(|| format!("Failed to decode storage key in {pallet}.{entry}"));
+    let value = value_bytes.map(|value_bytes| {
+        decode_storage_value(
+            pallet,
+            entry,
+            &value_bytes,
+            &context.metadata,
+            &context.types,
+            &options,
+        )
+        .with_context(|| format!("Failed to decode storage value in {pallet}.{entry}"))
+    });
+
+    let mut stdout = std::io::stdout().lock();
+    if opts.format.is_json() {
+        let keyval = StorageKeyValOutput {
+            key_bytes: None,
+            value_bytes: None,
+            key_digest: None,
+            value_digest: None,
+            key: key
+                .as_ref()
+                .ok()
+                .map(|key| key.iter().map(Into::into).collect()),
+            key_error: key.as_ref().err().map(ErrorOutput::new),
+            value: value
+                .as_ref()
+                .and_then(|value| value.as_ref().ok())
+                .cloned(),
+            value_error: value
+                .as_ref()
+                .and_then(|value| value.as_ref().err())
+                .map(ErrorOutput::new),
+            skipped: None,
+            resolved_calls: Vec::new(),
+        };
+        output::write_output_line(opts.format, &mut stdout, &keyval)?;
+    } else {
+        writeln!(stdout, "{pallet}.{entry}")?;
+        match &key {
+            Ok(key) => {
+                write!(stdout, "  Key: ")?;
+                storage_decoder::write_storage_keys(
+                    IndentedWriter::<4, _>(&mut stdout),
+                    key,
+                    &options,
+                )?;
+                writeln!(stdout)?;
+            }
+            Err(e) => writeln!(stdout, "  Key Error: {e:?}")?,
+        }
+        match &value {
+            Some(Ok(value)) => {
+                write!(stdout, "  Value: ")?;
+                write_value(IndentedWriter::<4, _>(&mut stdout), value, &options)?;
+                writeln!(stdout)?;
+            }
+            Some(Err(e)) => writeln!(stdout, "  Value Error: {e:?}")?,
+            None => {}
+        }
+    }
+
+    let errors: Vec<_> = [
+        key.as_ref().err(),
+        value.as_ref().and_then(|v| v.as_ref().err()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    errors.iter().for_each(|e| error_category::count(e));
+    if !errors.is_empty() {
+        return Err(
+            anyhow!("The storage item could not be decoded").context(ErrorKind::DecodeErrors)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use super::decode_block_file::load_metadata;
+use super::decode_blocks::decode_extrinsics;
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::types_for_spec_version;
+use crate::decoding::historic_types::load_historic_types;
+use crate::decoding::storage_decoder;
+use crate::utils::corpus::Corpus;
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{self, DecodedExtrinsicOutput, OutputFormat, StorageKeyPartOutput};
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Parser};
+use frame_metadata::RuntimeMetadata;
+use serde::Serialize;
+use serde_json::json;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+#[command(group(ArgGroup::new("side_b").required(true).multiple(true).args(["types_b", "metadata_b", "spec_version_b"])))]
+pub struct Opts {
+    /// The historic type definitions to compare against.
+    #[arg(long)]
+    types_a: PathBuf,
+
+    /// The historic type definitions to compare, eg a refactored version of `--types-a`.
+    /// Defaults to `--types-a`.
+    #[arg(long)]
+    types_b: Option<PathBuf>,
+
+    /// Decode side B with the metadata in this file (the output of `fetch-metadata --binary`,
+    /// or hex encoded metadata) rather than the recorded metadata, eg that of the equivalent
+    /// runtime on a sister chain. For pairing blocks from one chain with another's metadata,
+    /// to find where they diverge when porting types between them.
+    #[arg(long)]
+    metadata_b: Option<PathBuf>,
+
+    /// Decode side B with the types for this spec version, rather than those for the spec
+    /// version of each block, eg that of the equivalent runtime on a sister chain.
+    #[arg(long)]
+    spec_version_b: Option<u32>,
+
+    /// The blocks and storage entries to decode, as recorded with `--record` by
+    /// `decode-blocks` or `decode-storage-items`.
+    #[arg(long)]
+    from_file: PathBuf,
+
+    /// The format to write any differences in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+/// Some item which decoded differently on each side, as written with `--format json`.
+#[derive(Serialize, Debug, PartialEq)]
+struct Difference {
+    /// The item that was decoded, eg `block 1234 extrinsic 2`.
+    item: String,
+    /// Where in the decoded output (as written with `--format json`) the first difference is.
+    path: String,
+    /// The output at that path when decoded with side A, or null if there was none.
+    a: serde_json::Value,
+    /// The output at that path when decoded with side B, or null if there was none.
+    b: serde_json::Value,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let types_a = load_historic_types(&opts.types_a)?;
+    let types_b = load_historic_types(opts.types_b.as_ref().unwrap_or(&opts.types_a))?;
+    let forced_metadata_b = opts.metadata_b.as_deref().map(load_metadata).transpose()?;
+    let corpus = Corpus::load(&opts.from_file)?;
+
+    let mut stdout = std::io::stdout().lock();
+    let mut compared = 0;
+    let mut differences = 0;
+    let mut report = |difference: Option<Difference>| -> anyhow::Result<()> {
+        compared += 1;
+        let Some(difference) = difference else {
+            return Ok(());
+        };
+        differences += 1;
+        if opts.format.is_json() {
+            output::write_json_line(&mut stdout, &difference)?;
+        } else {
+            writeln!(stdout, "{}:", difference.item)?;
+            writeln!(stdout, "  at {}", difference.path)?;
+            writeln!(stdout, "  A: {}", difference.a)?;
+            writeln!(stdout, "  B: {}", difference.b)?;
+        }
+        Ok(())
+    };
+
+    for block in &corpus.blocks {
+        let metadata: &RuntimeMetadata = corpus.metadata(block.spec_version)?;
+        let metadata_b = forced_metadata_b.as_ref().unwrap_or(metadata);
+        let spec_version_b = opts.spec_version_b.unwrap_or(block.spec_version);
+        let types_a = types_for_spec_version(block.spec_version, metadata, &types_a)?;
+        let types_b = types_for_spec_version(spec_version_b, metadata_b, &types_b)?;
+        let decoded_a = decode_extrinsics(block.extrinsics.clone(), metadata, &types_a, &options);
+        let decoded_b = decode_extrinsics(block.extrinsics.clone(), metadata_b, &types_b, &options);
+
+        for (index, ((_, a), (_, b))) in decoded_a.into_iter().zip(decoded_b).enumerate() {
+            let [a, b] = [a, b].map(|ext| match ext {
+                Ok(ext) => serde_json::to_value(DecodedExtrinsicOutput::from(ext)),
+                Err(e) => Ok(error_json(&e)),
+            });
+            let item = format!("Block {} extrinsic {index}", block.block_number);
+            report(first_difference(item, &a?, &b?))?;
+        }
+    }
+
+    for record in &corpus.storage {
+        let metadata: &RuntimeMetadata = corpus.metadata(record.spec_version)?;
+        let metadata_b = forced_metadata_b.as_ref().unwrap_or(metadata);
+        let spec_version_b = opts.spec_version_b.unwrap_or(record.spec_version);
+        let types_a = types_for_spec_version(record.spec_version, metadata, &types_a)?;
+        let types_b = types_for_spec_version(spec_version_b, metadata_b, &types_b)?;
+        let (pallet, entry) = (&record.pallet, &record.entry);
+
+        for (key, value) in &record.keyvals {
+            let [a, b] = [(metadata, &types_a), (metadata_b, &types_b)].map(|(metadata, types)| {
+                let key = match storage_decoder::decode_storage_keys(
+                    pallet, entry, &key.0, metadata, types, &options,
+                ) {
+                    Ok(key) => {
+                        let parts: Vec<StorageKeyPartOutput> = key.iter().map(Into::into).collect();
+                        serde_json::to_value(parts)?
+                    }
+                    Err(e) => error_json(&e),
+                };
+                let value = match storage_decoder::decode_storage_value(
+                    pallet, entry, &value.0, metadata, types, &options,
+                ) {
+                    Ok(value) => serde_json::to_value(value)?,
+                    Err(e) => error_json(&e),
+                };
+                anyhow::Ok(json!({ "key": key, "value": value }))
+            });
+            let item = format!(
+                "Storage {pallet}.{entry} at block {} key {}",
+                record.block_number,
+                subxt::utils::to_hex(&key.0)
+            );
+            report(first_difference(item, &a?, &b?))?;
+        }
+    }
+
+    if opts.format == OutputFormat::Text {
+        writeln!(
+            stdout,
+            "Compared {compared} items: {differences} decoded differently"
+        )?;
+    }
+    if differences > 0 {
+        return Err(anyhow!(
+            "{differences} items decoded differently on each side"
+        ))
+        .context(ErrorKind::DecodeErrors);
+    }
+    Ok(())
+}
+
+fn error_json(err: &anyhow::Error) -> serde_json::Value {
+    json!({ "error": format!("{err:#}") })
+}
+
+/// Find the first place where two decoded outputs differ, if they do.
+fn first_difference(
+    item: String,
+    a: &serde_json::Value,
+    b: &serde_json::Value,
+) -> Option<Difference> {
+    let (path, a, b) = first_difference_at(String::new(), a, b)?;
+    Some(Difference {
+        item,
+        path: if path.is_empty() {
+            ".".to_owned()
+        } else {
+            path
+        },
+        a: a.clone(),
+        b: b.clone(),
+    })
+}
+
+fn first_difference_at<'a>(
+    path: String,
+    a: &'a serde_json::Value,
+    b: &'a serde_json::Value,
+) -> Option<(String, &'a serde_json::Value, &'a serde_json::Value)> {
+    use serde_json::Value;
+    const NULL: &Value = &Value::Null;
+
+    match (a, b) {
+        // If the fields differ (eg one failed to decode and has an `error` instead), then the
+        // whole objects are more useful to see than the first missing field.
+        (Value::Object(a_fields), Value::Object(b_fields))
+            if a_fields.keys().eq(b_fields.keys()) =>
+        {
+            a_fields
+                .iter()
+                .zip(b_fields.values())
+                .find_map(|((key, a), b)| first_difference_at(format!("{path}.{key}"), a, b))
+        }
+        (Value::Array(a_items), Value::Array(b_items)) => (0..a_items.len().max(b_items.len()))
+            .find_map(|idx| {
+                first_difference_at(
+                    format!("{path}[{idx}]"),
+                    a_items.get(idx).unwrap_or(NULL),
+                    b_items.get(idx).unwrap_or(NULL),
+                )
+            }),
+        (a, b) if a == b => None,
+        (a, b) => Some((path, a, b)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_first_difference() {
+        let a =
+            json!({ "args": [{ "name": "dest", "value": 1 }, { "name": "value", "value": 2 }] });
+        let b =
+            json!({ "args": [{ "name": "dest", "value": 1 }, { "name": "value", "value": 3 }] });
+        assert_eq!(
+            first_difference("item".to_owned(), &a, &b),
+            Some(Difference {
+                item: "item".to_owned(),
+                path: ".args[1].value".to_owned(),
+                a: json!(2),
+                b: json!(3),
+            })
+        );
+
+        let b = json!({ "args": [{ "name": "dest", "value": 1 }] });
+        let difference = first_difference("item".to_owned(), &a, &b).unwrap();
+        assert_eq!(
+            (difference.path, difference.b),
+            (".args[1]".to_owned(), json!(null))
+        );
+
+        let b = json!({ "error": "oops" });
+        let difference = first_difference("item".to_owned(), &a, &b).unwrap();
+        assert_eq!((difference.path, difference.b), (".".to_owned(), b));
+
+        assert_eq!(first_difference("item".to_owned(), &a, &a), None);
+    }
+
+    #[test]
+    fn needs_something_to_compare() {
+        let parse = |args: &[&str]| {
+            let base = [
+                "diff-types",
+                "--types-a",
+                "a.yaml",
+                "--from-file",
+                "corpus.jsonl",
+            ];
+            Opts::try_parse_from(base.iter().chain(args))
+        };
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--spec-version-b", "9430"]).is_ok());
+        assert!(parse(&["--types-b", "b.yaml", "--metadata-b", "kusama.scale"]).is_ok());
+    }
+}
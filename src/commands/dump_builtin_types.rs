@@ -0,0 +1,119 @@
+use crate::decoding::builtin_types::{self, VariantInfo};
+use crate::utils;
+use crate::utils::block_ref::BlockRef;
+use crate::utils::rpc::RpcOpts;
+use crate::utils::runner::RoundRobin;
+use anyhow::Context;
+use clap::Parser;
+use serde_yaml::{Mapping, Value};
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::PolkadotConfig;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// URL of the node to connect to.
+    /// Defaults to using Polkadot RPC URLs if not given.
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// The block to fetch metadata from: `latest`, `finalized`, a block number or a block hash.
+    #[arg(short = 'b', long, visible_alias = "block", alias = "block-hash")]
+    at: BlockRef,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
+}
+
+pub async fn run(opts: Opts) -> anyhow::Result<()> {
+    // Use our the given URl, or polkadot RPC node urls if not given.
+    let urls = RoundRobin::new(
+        utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc).await?,
+    );
+
+    let url = urls.get();
+    let rpc_client = opts.rpc.connect(url).await?;
+    let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client.clone());
+    let (_, block_hash) = opts.at.resolve(&rpcs).await?;
+    let metadata = super::fetch_metadata::state_get_metadata(&rpc_client, Some(block_hash))
+        .await
+        .with_context(|| "Could not fetch metadata")?;
+
+    let types = builtin_types::builtin_types_from_metadata(&metadata)?;
+
+    // Output each top level enum followed by the per-module enums that it points to,
+    // in the same shape as the types file so that they can be compared easily.
+    let mut out = Mapping::new();
+    for name in builtin_types::BUILTIN_ENUMS {
+        let Some(variants) = builtin_types::enum_variants(&types, name)? else {
+            continue;
+        };
+
+        let module_enums: Vec<String> = variants
+            .iter()
+            .flat_map(|v| v.fields.iter().map(|(_, ty)| ty.clone()))
+            .collect();
+        out.insert(name.into(), enum_to_yaml(&variants));
+
+        for module_enum in module_enums {
+            if let Some(variants) = builtin_types::enum_variants(&types, &module_enum)? {
+                out.insert(module_enum.into(), enum_to_yaml(&variants));
+            }
+        }
+    }
+
+    if out.is_empty() {
+        eprintln!(
+            "No builtin types are generated for V{} metadata",
+            metadata.version()
+        );
+        return Ok(());
+    }
+
+    let mut global = Mapping::new();
+    global.insert("types".into(), Value::Mapping(out));
+    let mut root = Mapping::new();
+    root.insert("global".into(), Value::Mapping(global));
+
+    serde_yaml::to_writer(std::io::stdout(), &root)?;
+    Ok(())
+}
+
+/// Describe an enum using the explicit form accepted in the types file, so that
+/// variant indexes are visible.
+fn enum_to_yaml(variants: &[VariantInfo]) -> Value {
+    let variants = variants
+        .iter()
+        .map(|variant| {
+            let is_struct = variant.fields.iter().any(|(name, _)| name.is_some());
+            let fields = if is_struct {
+                let fields = variant
+                    .fields
+                    .iter()
+                    .map(|(name, ty)| {
+                        let name = name.clone().unwrap_or_default();
+                        (Value::from(name), Value::from(ty.clone()))
+                    })
+                    .collect();
+                Value::Mapping(fields)
+            } else {
+                let fields = variant
+                    .fields
+                    .iter()
+                    .map(|(_, ty)| Value::from(ty.clone()))
+                    .collect();
+                Value::Sequence(fields)
+            };
+
+            let mut v = Mapping::new();
+            v.insert("name".into(), variant.name.clone().into());
+            v.insert("index".into(), variant.index.into());
+            v.insert("fields".into(), fields);
+            Value::Mapping(v)
+        })
+        .collect();
+
+    let mut e = Mapping::new();
+    e.insert("_enum".into(), Value::Sequence(variants));
+    Value::Mapping(e)
+}
@@ -0,0 +1,331 @@
+use super::decode_events::{fetch_events, Output, RunnerState};
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::DecodingContextCache;
+use crate::decoding::events::Event;
+use crate::decoding::historic_types::load_historic_types;
+use crate::utils;
+use crate::utils::error_category;
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{
+    self, EventCountOutput, EventStatsOutput, OutputFormat, PhaseCountOutput, RunnerErrorOutput,
+};
+use crate::utils::rpc::RpcOpts;
+use crate::utils::run_config::RunConfig;
+use crate::utils::runner::{DecodePool, RoundRobin, Runner, RunnerItem};
+use anyhow::anyhow;
+use clap::Parser;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// URL of the node(s) to connect to.
+    /// Defaults to using Polkadot RPC URLs if not given.
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// How many blocks to fetch events from in parallel. Each of these uses its own connection.
+    #[arg(long, alias = "connections")]
+    fetch_concurrency: Option<usize>,
+
+    /// How many threads to decode fetched events on. Defaults to the number of CPUs.
+    #[arg(long)]
+    decode_threads: Option<usize>,
+
+    /// Block number to start from.
+    #[arg(short, long)]
+    starting_block: Option<u64>,
+
+    /// Block number to stop after. Defaults to continuing until the latest block.
+    #[arg(long)]
+    ending_block: Option<u64>,
+
+    /// The format to write the counts in. JSON output is a single line.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let format = opts.format;
+    let ending_block = opts.ending_block;
+    let start_block_num = opts.starting_block.unwrap_or(0);
+    let fetch_concurrency = opts.fetch_concurrency.unwrap_or(1);
+    let decode_pool = DecodePool::new(opts.decode_threads);
+    // Use our default or built-in URLs if not provided.
+    let urls = RoundRobin::new(
+        utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc).await?,
+    );
+
+    // Our base type mappings that we'll use to decode pre-V14 events.
+    let historic_types = Arc::new(load_historic_types(&opts.types)?);
+    // The decoding contexts prepared so far, shared by every task.
+    let contexts = Arc::new(DecodingContextCache::new(options));
+
+    let mut config = RunConfig::new("events-stats", urls.items(), &opts.types)?;
+    config.filter("starting-block", Some(start_block_num));
+    config.filter("ending-block", ending_block);
+    eprintln!("{config}");
+
+    let rpc_opts = opts.rpc;
+    let stats = Arc::new(std::sync::Mutex::new(EventStats::default()));
+    let task_stats = stats.clone();
+
+    let runner = Runner::new(
+        // Initial state; each task fetches the next URl to connect to.
+        urls,
+        // Turn each URL into some state that we'll reuse to fetch a bunch of blocks. This reruns on error.
+        move |_n, urls| {
+            let url = urls.get().to_owned();
+            let rpc_opts = rpc_opts.clone();
+            async move {
+                let state = RunnerState::connect(url, &rpc_opts).await?;
+                Ok(Some(Arc::new(Mutex::new(state))))
+            }
+        },
+        // Fetch the events of a block and decode them. This runs in parallel for number of initial state items.
+        move |block_number, state| {
+            let historic_types = historic_types.clone();
+            let contexts = contexts.clone();
+            let decode_pool = decode_pool.clone();
+            let state = state.clone();
+            async move {
+                if ending_block.is_some_and(|end| block_number > end) {
+                    return Ok(None);
+                }
+                let mut state = state.lock().await;
+                fetch_events(
+                    &mut state,
+                    block_number,
+                    &contexts,
+                    &historic_types,
+                    &decode_pool,
+                    format,
+                )
+                .await
+            }
+        },
+        // Count the events. This runs sequentially, in order of task numbers.
+        move |item: RunnerItem<Output>| {
+            let mut stats = task_stats.lock().unwrap();
+            let output = match item {
+                RunnerItem::Output(output) => output,
+                // The counts are written out once at the end, so errors fetching blocks go to
+                // stderr like errors decoding them.
+                RunnerItem::Error(e) => {
+                    let err = RunnerErrorOutput::new(&e);
+                    output::write_runner_error(OutputFormat::Text, std::io::stderr(), &err)?;
+                    if e.gave_up {
+                        stats.add_failed(e.task_number);
+                    }
+                    return Ok(());
+                }
+            };
+            match &output.events {
+                Ok(events) => stats.add(output.block_number, events),
+                Err(e) => {
+                    error_category::count(e);
+                    eprintln!(
+                        "Error decoding events in block {} (fetched from {}): {e:?}",
+                        output.block_number, output.endpoint
+                    );
+                    stats.add_failed(output.block_number);
+                }
+            }
+            Ok(())
+        },
+    );
+
+    // Write out whatever we counted, even if we stopped early.
+    let res = runner.run(fetch_concurrency, start_block_num).await;
+    let stats = std::mem::take(&mut *stats.lock().unwrap());
+    let stats = stats.into_output(start_block_num);
+    let mut stdout = std::io::stdout().lock();
+    if format.is_json() {
+        output::write_output_line(format, &mut stdout, &stats)?;
+    } else {
+        write_stats(&mut stdout, &stats)?;
+    }
+    res?;
+
+    if !stats.failed_blocks.is_empty() {
+        return Err(anyhow!("The events in some blocks could not be decoded")
+            .context(ErrorKind::DecodeErrors));
+    }
+    Ok(())
+}
+
+/// The events counted so far.
+#[derive(Default)]
+struct EventStats {
+    blocks: u64,
+    last_block: Option<u64>,
+    failed_blocks: Vec<u64>,
+    events: BTreeMap<(String, String), EventCountOutput>,
+    phases: BTreeMap<String, u64>,
+}
+
+impl EventStats {
+    fn add(&mut self, block_number: u64, events: &[Event]) {
+        self.blocks += 1;
+        self.last_block = Some(block_number);
+        for event in events {
+            let count = self
+                .events
+                .entry((event.pallet.clone(), event.name.clone()))
+                .or_insert_with(|| EventCountOutput {
+                    pallet: event.pallet.clone(),
+                    event: event.name.clone(),
+                    count: 0,
+                    first_block: block_number,
+                    last_block: block_number,
+                });
+            count.count += 1;
+            count.last_block = block_number;
+            *self
+                .phases
+                .entry(event.phase_kind().to_owned())
+                .or_default() += 1;
+        }
+    }
+
+    fn add_failed(&mut self, block_number: u64) {
+        self.blocks += 1;
+        self.last_block = Some(block_number);
+        self.failed_blocks.push(block_number);
+    }
+
+    fn into_output(self, starting_block: u64) -> EventStatsOutput {
+        let mut events: Vec<_> = self.events.into_values().collect();
+        // The rarest events are the ones worth finding examples of, so they come first.
+        events.sort_by_key(|event| event.count);
+        EventStatsOutput {
+            starting_block,
+            ending_block: self.last_block,
+            blocks: self.blocks,
+            failed_blocks: self.failed_blocks,
+            events,
+            phases: self
+                .phases
+                .into_iter()
+                .map(|(phase, count)| PhaseCountOutput { phase, count })
+                .collect(),
+        }
+    }
+}
+
+fn write_stats<W: std::io::Write>(mut w: W, stats: &EventStatsOutput) -> anyhow::Result<()> {
+    match stats.ending_block {
+        Some(end) => writeln!(
+            w,
+            "Scanned blocks {} to {end} ({} blocks)",
+            stats.starting_block, stats.blocks
+        )?,
+        None => writeln!(w, "Scanned no blocks from block {}", stats.starting_block)?,
+    }
+    if let Some(first) = stats.failed_blocks.first() {
+        writeln!(
+            w,
+            "The events in {} blocks failed to decode and aren't counted, starting at block {first}",
+            stats.failed_blocks.len()
+        )?;
+    }
+
+    let names: Vec<_> = stats
+        .events
+        .iter()
+        .map(|e| format!("{}.{}", e.pallet, e.event))
+        .collect();
+    let name_width = names.iter().map(String::len).max().unwrap_or(0).max(5);
+    writeln!(w)?;
+    writeln!(
+        w,
+        "{:name_width$}  {:>8}  {:>11}  {:>11}",
+        "Event", "Count", "First block", "Last block"
+    )?;
+    for (name, e) in names.iter().zip(&stats.events) {
+        writeln!(
+            w,
+            "{name:name_width$}  {:>8}  {:>11}  {:>11}",
+            e.count, e.first_block, e.last_block
+        )?;
+    }
+
+    let phase_width = stats
+        .phases
+        .iter()
+        .map(|p| p.phase.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    writeln!(w)?;
+    writeln!(w, "{:phase_width$}  {:>8}", "Phase", "Count")?;
+    for p in &stats.phases {
+        writeln!(w, "{:phase_width$}  {:>8}", p.phase, p.count)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_value::{Composite, Value};
+
+    fn event(phase: &str, pallet: &str, name: &str) -> Event {
+        Event {
+            phase: Value::unnamed_variant(phase, []).map_context(|_| String::new()),
+            pallet: pallet.to_owned(),
+            name: name.to_owned(),
+            fields: Composite::Unnamed(vec![]),
+            topics: Value::unnamed_composite([]).map_context(|_| String::new()),
+        }
+    }
+
+    #[test]
+    fn counts_events_rarest_first() {
+        let mut stats = EventStats::default();
+        let success = || event("ApplyExtrinsic", "System", "ExtrinsicSuccess");
+        stats.add(10, &[success(), success()]);
+        stats.add_failed(11);
+        stats.add(
+            12,
+            &[success(), event("Finalization", "Democracy", "Tabled")],
+        );
+
+        let output = stats.into_output(10);
+        assert_eq!(output.blocks, 3);
+        assert_eq!(output.ending_block, Some(12));
+        assert_eq!(output.failed_blocks, vec![11]);
+        let counts: Vec<_> = output
+            .events
+            .iter()
+            .map(|e| (e.event.as_str(), e.count, e.first_block, e.last_block))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("Tabled", 1, 12, 12), ("ExtrinsicSuccess", 3, 10, 12)]
+        );
+        assert_eq!(
+            output.phases,
+            vec![
+                PhaseCountOutput {
+                    phase: "ApplyExtrinsic".to_owned(),
+                    count: 3
+                },
+                PhaseCountOutput {
+                    phase: "Finalization".to_owned(),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
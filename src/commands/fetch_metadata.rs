@@ -1,18 +1,17 @@
 use std::io::Write;
 
+use crate::decoding::decoding_context::decode_metadata;
 use crate::utils;
+use crate::utils::block_ref::BlockRef;
+use crate::utils::rpc::RpcOpts;
 use crate::utils::runner::RoundRobin;
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use clap::Parser;
 use parity_scale_codec::Encode;
 use subxt::backend::{
-    legacy::{
-        rpc_methods::{Bytes, NumberOrHex},
-        LegacyRpcMethods,
-    },
+    legacy::{rpc_methods::Bytes, LegacyRpcMethods},
     rpc::{rpc_params, RpcClient},
 };
-use subxt::ext::codec::Decode;
 use subxt::{Config, PolkadotConfig};
 
 #[derive(Parser)]
@@ -23,31 +22,30 @@ pub struct Opts {
     #[arg(short, long)]
     url: Option<String>,
 
-    /// Block number to fetch metadata from.
-    #[arg(short, long)]
-    block: u64,
+    /// The block to fetch metadata from: `latest`, `finalized`, a block number or a block hash.
+    #[arg(short = 'b', long, visible_alias = "block", alias = "block-hash")]
+    at: BlockRef,
 
     /// As binary?
     #[arg(long)]
     binary: bool,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
 }
 
 pub async fn run(opts: Opts) -> anyhow::Result<()> {
-    let start_block_num = opts.block;
     let as_binary = opts.binary;
 
     // Use our the given URl, or polkadot RPC node urls if not given.
-    let urls = RoundRobin::new(utils::url_or_polkadot_rpc_nodes(opts.url.as_deref()));
+    let urls = RoundRobin::new(
+        utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc).await?,
+    );
 
-    let block_number = start_block_num;
     let url = urls.get();
-    let rpc_client = RpcClient::from_insecure_url(url).await?;
+    let rpc_client = opts.rpc.connect(url).await?;
     let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client.clone());
-    let block_hash = rpcs
-        .chain_get_block_hash(Some(NumberOrHex::Number(block_number as u64)))
-        .await
-        .with_context(|| "Could not fetch block hash")?
-        .ok_or_else(|| anyhow!("Couldn't find block {block_number}"))?;
+    let (_, block_hash) = opts.at.resolve(&rpcs).await?;
     let metadata = state_get_metadata(&rpc_client, Some(block_hash))
         .await
         .with_context(|| "Could not fetch metadata")?;
@@ -69,7 +67,5 @@ pub(super) async fn state_get_metadata(
         .request("state_getMetadata", rpc_params![at])
         .await
         .with_context(|| "Could not fetch metadata")?;
-    let metadata = frame_metadata::RuntimeMetadataPrefixed::decode(&mut &bytes[..])
-        .with_context(|| "Could not decode metadata")?;
-    Ok(metadata.1)
+    decode_metadata(&bytes)
 }
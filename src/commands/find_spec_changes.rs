@@ -1,5 +1,8 @@
+use crate::decoding::runtime_code;
 use crate::utils;
 use crate::utils::binary_chopper::{BinaryChopper, Next};
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::rpc::RpcOpts;
 use anyhow::{anyhow, Context};
 use clap::Parser;
 use subxt::backend::{
@@ -23,11 +26,23 @@ pub struct Opts {
     /// Block number to end on.
     #[arg(short, long)]
     ending_block: Option<u32>,
+
+    /// At each spec version change, also fetch the runtime code (from `:code`) and check that
+    /// the spec version embedded in it is the one reported. The hash and size of the code are
+    /// added to the output too.
+    #[arg(long)]
+    verify_code: bool,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
 }
 
 pub async fn run(opts: Opts) -> anyhow::Result<()> {
-    let url = utils::url_or_polkadot_rpc_nodes(opts.url.as_deref()).remove(0);
-    let rpc_client = RpcClient::from_insecure_url(&url).await?;
+    let url = utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc)
+        .await?
+        .remove(0);
+    let rpc_opts = &opts.rpc;
+    let rpc_client = rpc_opts.connect(&url).await?;
 
     let starting_block_number = opts.starting_block.unwrap_or(0);
     let latest_block_number = match opts.ending_block {
@@ -41,8 +56,9 @@ pub async fn run(opts: Opts) -> anyhow::Result<()> {
         }
     };
 
-    let mut low_version = get_spec_version(&rpc_client, &url, starting_block_number).await;
-    let high_version = get_spec_version(&rpc_client, &url, latest_block_number).await;
+    let mut low_version =
+        get_spec_version(&rpc_client, rpc_opts, &url, starting_block_number).await;
+    let high_version = get_spec_version(&rpc_client, rpc_opts, &url, latest_block_number).await;
 
     let mut start = starting_block_number;
     let end = latest_block_number;
@@ -54,7 +70,7 @@ pub async fn run(opts: Opts) -> anyhow::Result<()> {
         // While this is true, the BinaryChopper is proposing new blocks and we are
         // providing the spec versions at them to guide it.
         while let Next::NeedsState(n) = chopper.next_value() {
-            let spec_version = get_spec_version(&rpc_client, &url, n).await;
+            let spec_version = get_spec_version(&rpc_client, rpc_opts, &url, n).await;
             chopper.set_state_for_next_value(spec_version);
         }
 
@@ -74,42 +90,125 @@ pub async fn run(opts: Opts) -> anyhow::Result<()> {
         }
     }
 
-    print_spec_version_updates(&changes)?;
-    Ok(())
-}
-
-fn print_spec_version_updates(updates: &[(u32, u32)]) -> Result<(), serde_json::Error> {
-    let updates: Vec<_> = updates
-        .iter()
-        .map(|&(block, spec_version)| SpecVersionUpdate {
+    let mut updates = Vec::with_capacity(changes.len());
+    let mut mismatches = 0;
+    for (block, spec_version) in changes {
+        let code = if opts.verify_code {
+            let code = get_code_info(&rpc_client, rpc_opts, &url, block).await;
+            match code.embedded_spec_version {
+                Some(embedded) if embedded == spec_version => eprintln!(
+                    "Runtime code at block {block}: {} ({} bytes), spec version {embedded}",
+                    code.hash, code.size
+                ),
+                Some(embedded) => {
+                    mismatches += 1;
+                    eprintln!(
+                        "WARNING: runtime code at block {block} ({}) has spec version {embedded}, but {spec_version} was reported",
+                        code.hash
+                    );
+                }
+                None => {
+                    mismatches += 1;
+                    eprintln!(
+                        "WARNING: could not find the spec version in the runtime code at block {block} ({}): {}",
+                        code.hash,
+                        code.error.as_deref().unwrap_or("unknown error")
+                    );
+                }
+            }
+            Some(code)
+        } else {
+            None
+        };
+        updates.push(SpecVersionUpdate {
             block,
             spec_version,
-        })
-        .collect();
+            code,
+        });
+    }
 
+    print_spec_version_updates(&updates)?;
+    if mismatches > 0 {
+        return Err(anyhow!(
+            "The runtime code at {mismatches} spec version changes did not match the reported spec version"
+        ))
+        .context(ErrorKind::DecodeErrors);
+    }
+    Ok(())
+}
+
+fn print_spec_version_updates(updates: &[SpecVersionUpdate]) -> Result<(), serde_json::Error> {
     let stdout = std::io::stdout().lock();
     serde_json::to_writer_pretty(stdout, &updates)
 }
 
-async fn get_spec_version(rpc_client: &RpcClient, url: &str, block_number: u32) -> u32 {
-    retry(rpc_client.clone(), url, |rpcs: RpcClient| async move {
-        let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpcs);
-        let block_hash = rpcs
-            .chain_get_block_hash(Some(NumberOrHex::Number(block_number as u64)))
-            .await
-            .with_context(|| format!("Could not fetch block hash for block {block_number}"))?
-            .ok_or_else(|| anyhow!("Couldn't find block {block_number}"))?;
-        let version = rpcs
-            .state_get_runtime_version(Some(block_hash))
-            .await
-            .with_context(|| "Could not fetch runtime version")?;
-        Ok(version.spec_version)
-    })
+/// Fetch the runtime code at some block and find the spec version embedded in it.
+async fn get_code_info(
+    rpc_client: &RpcClient,
+    rpc_opts: &RpcOpts,
+    url: &str,
+    block_number: u32,
+) -> CodeInfo {
+    let code = retry(
+        rpc_client.clone(),
+        rpc_opts,
+        url,
+        |rpcs: RpcClient| async move {
+            let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpcs);
+            let block_hash = rpcs
+                .chain_get_block_hash(Some(NumberOrHex::Number(block_number as u64)))
+                .await
+                .with_context(|| format!("Could not fetch block hash for block {block_number}"))?
+                .ok_or_else(|| anyhow!("Couldn't find block {block_number}"))?;
+            rpcs.state_get_storage(b":code", Some(block_hash))
+                .await
+                .with_context(|| "Could not fetch runtime code")?
+                .ok_or_else(|| anyhow!("No runtime code at block {block_number}"))
+        },
+    )
+    .await;
+
+    let (embedded_spec_version, error) = match runtime_code::embedded_version(&code) {
+        Ok(version) => (Some(version.spec_version), None),
+        Err(e) => (None, Some(format!("{e:#}"))),
+    };
+    CodeInfo {
+        hash: subxt::utils::to_hex(sp_crypto_hashing::blake2_256(&code)),
+        size: code.len(),
+        embedded_spec_version,
+        error,
+    }
+}
+
+async fn get_spec_version(
+    rpc_client: &RpcClient,
+    rpc_opts: &RpcOpts,
+    url: &str,
+    block_number: u32,
+) -> u32 {
+    retry(
+        rpc_client.clone(),
+        rpc_opts,
+        url,
+        |rpcs: RpcClient| async move {
+            let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpcs);
+            let block_hash = rpcs
+                .chain_get_block_hash(Some(NumberOrHex::Number(block_number as u64)))
+                .await
+                .with_context(|| format!("Could not fetch block hash for block {block_number}"))?
+                .ok_or_else(|| anyhow!("Couldn't find block {block_number}"))?;
+            let version = rpcs
+                .state_get_runtime_version(Some(block_hash))
+                .await
+                .with_context(|| "Could not fetch runtime version")?;
+            Ok(version.spec_version)
+        },
+    )
     .await
 }
 
 // A dumb retry function that retries forever.
-async fn retry<T, Func, Fut>(rpc_client: RpcClient, url: &str, f: Func) -> T
+async fn retry<T, Func, Fut>(rpc_client: RpcClient, rpc_opts: &RpcOpts, url: &str, f: Func) -> T
 where
     Func: Fn(RpcClient) -> Fut,
     Fut: std::future::Future<Output = anyhow::Result<T>>,
@@ -121,7 +220,7 @@ where
         let client = match &rpc_client {
             Some(rpc_client) => rpc_client,
             None => {
-                match RpcClient::from_insecure_url(url).await {
+                match rpc_opts.connect(url).await {
                     Ok(client) => rpc_client = Some(client),
                     Err(e) => eprintln!("{e:?}"),
                 };
@@ -144,4 +243,21 @@ where
 pub struct SpecVersionUpdate {
     pub block: u32,
     pub spec_version: u32,
+    /// The runtime code at the block, if `--verify-code` was given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<CodeInfo>,
+}
+
+/// The runtime code in use from some spec version change.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CodeInfo {
+    /// Hex encoded blake2_256 hash of the code, as stored (ie compressed if it is).
+    pub hash: String,
+    /// The size of the code in bytes, as stored.
+    pub size: usize,
+    /// The spec version embedded in the code, if it could be found.
+    pub embedded_spec_version: Option<u32>,
+    /// Why the embedded spec version couldn't be found, if it couldn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
@@ -0,0 +1,659 @@
+use super::decode_storage_items::{fetch_storage_page, STORAGE_PAGE_SIZE};
+use super::fetch_metadata::state_get_metadata;
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::{DecodingContext, DecodingContextCache};
+use crate::decoding::historic_types::load_historic_types;
+use crate::decoding::storage_decoder;
+use crate::utils::output::{self, ErrorOutput, JsonValue, OutputFormat, StorageKeyValOutput};
+use crate::utils::{
+    self, block_ref::BlockRef, exit_code::ErrorKind, rpc::RpcOpts, runner::RoundRobin,
+    template::Template, IndentedWriter,
+};
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use scale_info_legacy::ChainTypeRegistry;
+use scale_value::{Composite, Value, ValueDef, Variant};
+use serde::Serialize;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use subxt::backend::{legacy::LegacyRpcMethods, rpc::RpcClient, StorageResponse};
+use subxt::utils::H256;
+use subxt::PolkadotConfig;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    #[command(subcommand)]
+    command: GetCommand,
+}
+
+#[derive(Subcommand)]
+enum GetCommand {
+    /// Fetch and decode a single storage value, eg `get storage System.Account 5Grw.. --at 1000000`.
+    Storage(StorageOpts),
+    /// Fetch and decode the staking entries indexed by era (eg Staking.ErasStakers and
+    /// Staking.ErasRewardPoints) at some block, grouped by era, eg `get staking-eras --at 1000000`.
+    StakingEras(StakingErasOpts),
+}
+
+#[derive(Parser)]
+struct StorageOpts {
+    /// The storage entry to fetch, eg System.Account.
+    entry: StorageEntryName,
+
+    /// A value for each of the storage entry's keys. Account IDs can be given as SS58
+    /// addresses, bytes as hex, and anything else in the format that values are printed in,
+    /// eg `123`, `"text"` or `(1, 2)`.
+    keys: Vec<String>,
+
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// URL of the node to connect to.
+    /// Defaults to using Polkadot RPC URLs if not given.
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// The block to fetch the value at: `latest`, `finalized`, a block number or a block hash.
+    #[arg(long, alias = "at-hash")]
+    at: BlockRef,
+
+    /// A template to print instead of the whole value, where `{path}` is replaced by the
+    /// part of the output at that path, eg `{value.data.free}`. The paths `value`, `exists`,
+    /// `block_number`, `block_hash` and `spec_version` are available.
+    #[arg(short, long)]
+    output: Option<Template>,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
+}
+
+#[derive(Parser)]
+struct StakingErasOpts {
+    /// The eras to fetch entries for. Defaults to the active era at the block.
+    #[arg(long = "era")]
+    eras: Vec<u32>,
+
+    /// Also print the values of Staking.ActiveEra and Staking.CurrentEra at the block, to see
+    /// how the eras being printed relate to them.
+    #[arg(long)]
+    join_active_era: bool,
+
+    /// The max number of items to fetch from each entry for each era.
+    /// Defaults to fetching all of them.
+    #[arg(long, default_value = "0")]
+    max_items: usize,
+
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// URL of the node to connect to.
+    /// Defaults to using Polkadot RPC URLs if not given.
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// The block to fetch the entries at: `latest`, `finalized`, a block number or a block hash.
+    #[arg(long, alias = "at-hash")]
+    at: BlockRef,
+
+    /// The format to write the entries in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
+}
+
+/// The Staking entries whose first key is an era index, in the order that they're printed.
+/// Only those which exist in the metadata at the block are fetched.
+const ERA_INDEXED_ENTRIES: &[&str] = &[
+    "ErasStartSessionIndex",
+    "ErasTotalStake",
+    "ErasValidatorReward",
+    "ErasRewardPoints",
+    "ErasValidatorPrefs",
+    "ErasStakers",
+    "ErasStakersClipped",
+    "ErasStakersOverview",
+    "ErasStakersPaged",
+    "ClaimedRewards",
+    "UnappliedSlashes",
+    "ValidatorSlashInEra",
+    "NominatorSlashInEra",
+];
+
+/// The era-indexed staking entries for one era, as written with `--format json`.
+#[derive(Serialize)]
+struct StakingEraOutput {
+    block_number: u64,
+    /// Hex encoded block hash.
+    block_hash: String,
+    spec_version: u32,
+    era: u32,
+    /// The value of Staking.ActiveEra at the block, if `--join-active-era` was given and the
+    /// entry exists.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "output::serialize_option_value"
+    )]
+    active_era: Option<JsonValue>,
+    /// The value of Staking.CurrentEra at the block, if `--join-active-era` was given.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "output::serialize_option_value"
+    )]
+    current_era: Option<JsonValue>,
+    entries: Vec<EraEntryOutput>,
+}
+
+#[derive(Serialize)]
+struct EraEntryOutput {
+    /// The storage entry, eg `Staking.ErasStakers`.
+    entry: String,
+    keyvals: Vec<StorageKeyValOutput>,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    match opts.command {
+        GetCommand::Storage(opts) => get_storage(opts, options).await,
+        GetCommand::StakingEras(opts) => get_staking_eras(opts, options).await,
+    }
+}
+
+async fn get_storage(opts: StorageOpts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let StorageEntryName { pallet, entry } = &opts.entry;
+    let keys = opts
+        .keys
+        .iter()
+        .map(|key| parse_key(key))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .context(ErrorKind::Config)?;
+    let historic_types = Arc::new(load_historic_types(&opts.types)?);
+
+    // Use our the given URl, or polkadot RPC node urls if not given.
+    let urls = RoundRobin::new(
+        utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc).await?,
+    );
+
+    let url = urls.get();
+    let rpc_client = opts.rpc.connect(url).await?;
+    let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client.clone());
+    let (block_number, block_hash) = opts.at.resolve(&rpcs).await?;
+    let StorageValueAt {
+        spec_version,
+        exists,
+        value,
+    } = fetch_storage_value(
+        &rpc_client,
+        &DecodingContextCache::new(options.clone()),
+        &historic_types,
+        pallet,
+        entry,
+        &keys,
+        block_hash,
+    )
+    .await?;
+
+    let mut stdout = std::io::stdout().lock();
+    match &opts.output {
+        Some(template) => {
+            let root = Value {
+                value: ValueDef::Composite(Composite::named([
+                    ("value", value),
+                    ("exists", Value::bool(exists).map_context(|_| String::new())),
+                    (
+                        "block_number",
+                        Value::u128(block_number as u128).map_context(|_| String::new()),
+                    ),
+                    (
+                        "block_hash",
+                        Value::string(subxt::utils::to_hex(block_hash))
+                            .map_context(|_| String::new()),
+                    ),
+                    (
+                        "spec_version",
+                        Value::u128(spec_version as u128).map_context(|_| String::new()),
+                    ),
+                ])),
+                context: String::new(),
+            };
+            let out = template
+                .render(&root, &options)
+                .context(ErrorKind::Config)?;
+            writeln!(stdout, "{out}")?;
+        }
+        None => {
+            utils::write_value(&mut stdout, &value, &options)?;
+            writeln!(stdout)?;
+        }
+    }
+    Ok(())
+}
+
+async fn get_staking_eras(
+    opts: StakingErasOpts,
+    options: Arc<DecodeOptions>,
+) -> anyhow::Result<()> {
+    let historic_types = Arc::new(load_historic_types(&opts.types)?);
+    let urls = RoundRobin::new(
+        utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc).await?,
+    );
+
+    let url = urls.get();
+    let rpc_client = opts.rpc.connect(url).await?;
+    let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client.clone());
+    let (block_number, block_hash) = opts.at.resolve(&rpcs).await?;
+    let runtime = runtime_at(
+        &rpc_client,
+        &DecodingContextCache::new(options),
+        &historic_types,
+        block_hash,
+    )
+    .await?;
+
+    let has_entry = |name: &str| {
+        frame_decode::helpers::list_storage_entries_any(&runtime.metadata)
+            .any(|entry| entry.pallet() == "Staking" && entry.entry() == name)
+    };
+    let entries: Vec<&str> = ERA_INDEXED_ENTRIES
+        .iter()
+        .copied()
+        .filter(|entry| has_entry(entry))
+        .collect();
+    if entries.is_empty() {
+        return Err(anyhow!(
+            "There are no era-indexed Staking entries at block {block_number}"
+        ))
+        .context(ErrorKind::Config);
+    }
+
+    // Older runtimes have no Staking.ActiveEra; their Staking.CurrentEra is the active era.
+    let active_era = if has_entry("ActiveEra") {
+        let (_, value) = storage_value_at(
+            &rpc_client,
+            &runtime,
+            "Staking",
+            "ActiveEra",
+            &[],
+            block_hash,
+        )
+        .await?;
+        Some(value)
+    } else {
+        None
+    };
+    let (_, current_era) = storage_value_at(
+        &rpc_client,
+        &runtime,
+        "Staking",
+        "CurrentEra",
+        &[],
+        block_hash,
+    )
+    .await?;
+
+    let eras = if opts.eras.is_empty() {
+        let era = era_index(active_era.as_ref().unwrap_or(&current_era))
+            .ok_or_else(|| {
+                anyhow!("There is no active era at block {block_number}; pass --era to pick one")
+            })
+            .context(ErrorKind::Config)?;
+        vec![era]
+    } else {
+        opts.eras.clone()
+    };
+    let (active_era, current_era) = if opts.join_active_era {
+        (active_era, Some(current_era))
+    } else {
+        (None, None)
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    if opts.format == OutputFormat::Text {
+        writeln!(
+            stdout,
+            "Block {block_number} (spec version {})",
+            runtime.spec_version
+        )?;
+        for (name, value) in [("ActiveEra", &active_era), ("CurrentEra", &current_era)] {
+            if let Some(value) = value {
+                write!(stdout, "  Staking.{name}: ")?;
+                utils::write_value(&mut stdout, value, &runtime.options)?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+
+    let mut errors = 0;
+    for era in eras {
+        let mut output = StakingEraOutput {
+            block_number,
+            block_hash: subxt::utils::to_hex(block_hash),
+            spec_version: runtime.spec_version,
+            era,
+            active_era: active_era.clone(),
+            current_era: current_era.clone(),
+            entries: Vec::new(),
+        };
+
+        for &entry in &entries {
+            let era_key = [Value::u128(era as u128)];
+            let prefix = storage_decoder::encode_storage_key_prefix(
+                "Staking",
+                entry,
+                &era_key,
+                &runtime.metadata,
+                &runtime.types,
+            )?;
+            let values = fetch_keyvals(&rpcs, &prefix, opts.max_items, block_hash)
+                .await
+                .with_context(|| format!("Could not fetch Staking.{entry} for era {era}"))?;
+
+            let keyvals = values
+                .into_iter()
+                .map(|kv| decode_keyval(&runtime, entry, kv))
+                .collect::<Vec<_>>();
+            errors += keyvals
+                .iter()
+                .filter(|kv| kv.key_error.is_some() || kv.value_error.is_some())
+                .count();
+            output.entries.push(EraEntryOutput {
+                entry: format!("Staking.{entry}"),
+                keyvals,
+            });
+        }
+
+        if opts.format.is_json() {
+            output::write_output_line(opts.format, &mut stdout, &output)?;
+        } else {
+            write_staking_era(&mut stdout, &output, &runtime.options)?;
+        }
+    }
+
+    if errors > 0 {
+        return Err(anyhow!("{errors} staking items could not be decoded"))
+            .context(ErrorKind::DecodeErrors);
+    }
+    Ok(())
+}
+
+/// Fetch every key and value under some prefix, up to `max_items` of them if it isn't 0.
+async fn fetch_keyvals(
+    rpcs: &LegacyRpcMethods<PolkadotConfig>,
+    prefix: &[u8],
+    max_items: usize,
+    at: H256,
+) -> anyhow::Result<Vec<Result<StorageResponse, subxt::Error>>> {
+    let mut values = Vec::new();
+    let mut start_key: Option<Vec<u8>> = None;
+    loop {
+        let (keys, page_values) =
+            fetch_storage_page(rpcs, prefix, start_key.as_deref(), at, false).await?;
+        values.extend(page_values);
+        if max_items > 0 && values.len() >= max_items {
+            values.truncate(max_items);
+            return Ok(values);
+        }
+        if keys.len() < STORAGE_PAGE_SIZE as usize {
+            return Ok(values);
+        }
+        start_key = keys.into_iter().last();
+    }
+}
+
+fn decode_keyval(
+    runtime: &DecodingContext,
+    entry: &str,
+    kv: Result<StorageResponse, subxt::Error>,
+) -> StorageKeyValOutput {
+    let mut output = StorageKeyValOutput {
+        key_bytes: None,
+        value_bytes: None,
+        key_digest: None,
+        value_digest: None,
+        key: None,
+        key_error: None,
+        value: None,
+        value_error: None,
+        skipped: None,
+        resolved_calls: Vec::new(),
+    };
+    let kv = match kv {
+        Ok(kv) => kv,
+        Err(e) => {
+            output.value_error = Some(ErrorOutput::new(&e.into()));
+            return output;
+        }
+    };
+
+    match storage_decoder::decode_storage_keys(
+        "Staking",
+        entry,
+        &kv.key,
+        &runtime.metadata,
+        &runtime.types,
+        &runtime.options,
+    ) {
+        Ok(keys) => output.key = Some(keys.iter().map(Into::into).collect()),
+        Err(e) => output.key_error = Some(ErrorOutput::new(&e)),
+    }
+    match storage_decoder::decode_storage_value(
+        "Staking",
+        entry,
+        &kv.value,
+        &runtime.metadata,
+        &runtime.types,
+        &runtime.options,
+    ) {
+        Ok(value) => output.value = Some(value),
+        Err(e) => output.value_error = Some(ErrorOutput::new(&e)),
+    }
+    output
+}
+
+fn write_staking_era<W: std::io::Write>(
+    mut w: W,
+    output: &StakingEraOutput,
+    opts: &DecodeOptions,
+) -> anyhow::Result<()> {
+    writeln!(w, "\nEra {}:", output.era)?;
+    for entry in &output.entries {
+        writeln!(w, "  {} ({} items)", entry.entry, entry.keyvals.len())?;
+        for (idx, kv) in entry.keyvals.iter().enumerate() {
+            write!(w, "    [{idx}] ")?;
+            match (&kv.key, &kv.key_error) {
+                (Some(key), _) => {
+                    let parts: Vec<String> = key
+                        .iter()
+                        .map(|part| match &part.value {
+                            Some(value) => value.to_string(),
+                            None => part.hash.clone(),
+                        })
+                        .collect();
+                    write!(w, "{}", parts.join(" + "))?;
+                }
+                (None, Some(e)) => write!(w, "Key Error: {}", e.message)?,
+                (None, None) => {}
+            }
+            write!(w, "\n      - ")?;
+            match (&kv.value, &kv.value_error) {
+                (Some(value), _) => {
+                    utils::write_value(IndentedWriter::<8, _>(&mut w), value, opts)?
+                }
+                (None, Some(e)) => write!(w, "Value Error: {}", e.message)?,
+                (None, None) => {}
+            }
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+/// The era index in some value of Staking.ActiveEra (an `Option<ActiveEraInfo>`) or
+/// Staking.CurrentEra (an `EraIndex`, or an `Option<EraIndex>` in later runtimes).
+fn era_index<T>(value: &Value<T>) -> Option<u32> {
+    match &value.value {
+        ValueDef::Variant(Variant { name, values }) if name == "Some" => {
+            values.values().next().and_then(era_index)
+        }
+        ValueDef::Composite(Composite::Named(fields)) => fields
+            .iter()
+            .find(|(name, _)| name == "index")
+            .and_then(|(_, value)| era_index(value)),
+        _ => value.as_u128().and_then(|n| n.try_into().ok()),
+    }
+}
+
+/// A storage value that has been fetched and decoded at some block.
+pub(super) struct StorageValueAt {
+    pub spec_version: u32,
+    /// False if nothing was stored, and so this is the entry's default value.
+    pub exists: bool,
+    pub value: Value<String>,
+}
+
+/// Fetch and decode the value stored at some storage entry and keys at the given block.
+pub(super) async fn fetch_storage_value(
+    rpc_client: &RpcClient,
+    contexts: &DecodingContextCache,
+    historic_types: &Arc<ChainTypeRegistry>,
+    pallet: &str,
+    entry: &str,
+    keys: &[Value],
+    block_hash: H256,
+) -> anyhow::Result<StorageValueAt> {
+    let runtime = runtime_at(rpc_client, contexts, historic_types, block_hash).await?;
+    let (exists, value) =
+        storage_value_at(rpc_client, &runtime, pallet, entry, keys, block_hash).await?;
+    Ok(StorageValueAt {
+        spec_version: runtime.spec_version,
+        exists,
+        value,
+    })
+}
+
+async fn runtime_at(
+    rpc_client: &RpcClient,
+    contexts: &DecodingContextCache,
+    historic_types: &Arc<ChainTypeRegistry>,
+    block_hash: H256,
+) -> anyhow::Result<Arc<DecodingContext>> {
+    let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client.clone());
+    let spec_version = rpcs
+        .state_get_runtime_version(Some(block_hash))
+        .await
+        .with_context(|| format!("Could not fetch runtime version for block {block_hash}"))?
+        .spec_version;
+    contexts
+        .get_or_prepare(spec_version, historic_types, || async {
+            if let Some(metadata) = &contexts.options().pinned_metadata {
+                return Ok(metadata.clone());
+            }
+            state_get_metadata(rpc_client, Some(block_hash))
+                .await
+                .map(Arc::new)
+                .with_context(|| "Could not fetch metadata")
+        })
+        .await
+}
+
+/// Fetch and decode a storage value, returning whether it exists along with the value (which
+/// is the entry's default if it doesn't).
+async fn storage_value_at(
+    rpc_client: &RpcClient,
+    runtime: &DecodingContext,
+    pallet: &str,
+    entry: &str,
+    keys: &[Value],
+    block_hash: H256,
+) -> anyhow::Result<(bool, Value<String>)> {
+    let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client.clone());
+    let storage_key =
+        storage_decoder::encode_storage_key(pallet, entry, keys, &runtime.metadata, &runtime.types)
+            .context(ErrorKind::Config)?;
+
+    // Nothing being stored means that the entry has its default value.
+    let (exists, value_bytes) = match rpcs
+        .state_get_storage(&storage_key, Some(block_hash))
+        .await
+        .with_context(|| format!("Could not fetch {pallet}.{entry}"))?
+    {
+        Some(bytes) => (true, bytes),
+        None => (
+            false,
+            storage_decoder::storage_default_bytes(pallet, entry, &runtime.metadata)?,
+        ),
+    };
+
+    let value = storage_decoder::decode_storage_value(
+        pallet,
+        entry,
+        &value_bytes,
+        &runtime.metadata,
+        &runtime.types,
+        &runtime.options,
+    )
+    .with_context(|| format!("Failed to decode storage value in {pallet}.{entry}"))
+    .context(ErrorKind::DecodeErrors)?;
+
+    Ok((exists, value))
+}
+
+/// Parse a storage key given on the command line.
+pub(super) fn parse_key(key: &str) -> anyhow::Result<Value> {
+    use scale_value::stringify::custom_parsers::{parse_hex, parse_ss58};
+
+    let (value, rest) = scale_value::stringify::from_str_custom()
+        .add_custom_parser(parse_ss58)
+        .add_custom_parser(parse_hex)
+        .parse(key);
+    let value = value.map_err(|e| anyhow!("Could not parse key '{key}': {e}"))?;
+    if !rest.trim().is_empty() {
+        anyhow::bail!("Could not parse key '{key}': unexpected '{rest}'");
+    }
+    Ok(value)
+}
+
+#[derive(Clone)]
+pub(super) struct StorageEntryName {
+    pub pallet: String,
+    pub entry: String,
+}
+
+impl std::str::FromStr for StorageEntryName {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((pallet, entry)) = s.split_once('.') else {
+            anyhow::bail!("storage entry should take the form $pallet.$name");
+        };
+        Ok(StorageEntryName {
+            pallet: pallet.to_string(),
+            entry: entry.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_era_index() {
+        let active_era = Value::variant(
+            "Some",
+            Composite::unnamed([Value::named_composite([
+                ("index", Value::u128(1234)),
+                ("start", Value::variant("None", Composite::unnamed([]))),
+            ])]),
+        );
+        assert_eq!(era_index(&active_era), Some(1234));
+        assert_eq!(era_index(&Value::u128(56)), Some(56));
+        assert_eq!(
+            era_index(&Value::variant("None", Composite::unnamed([]))),
+            None
+        );
+    }
+}
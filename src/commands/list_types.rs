@@ -0,0 +1,256 @@
+use super::decode_block_file::load_metadata;
+use super::resolve_type::{referenced_types, resolve_shape, Shape};
+use crate::decoding::builtin_types::{self, extend_with_metadata_info};
+use crate::decoding::historic_types::load_historic_types;
+use crate::decoding::storage_decoder::storage_type_names;
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{self, OutputFormat};
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use frame_metadata::RuntimeMetadata;
+use scale_info_legacy::{LookupName, TypeRegistrySet};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// The pre-V14 metadata (as given to `decode-block-file`) whose calls, events and storage
+    /// entries we'll list the types of.
+    #[arg(short, long)]
+    metadata: PathBuf,
+
+    /// The spec version of the metadata, to pick types from the types file for.
+    #[arg(short, long)]
+    spec_version: u64,
+
+    /// Only list the types that can't be resolved.
+    #[arg(long)]
+    unresolvable_only: bool,
+
+    /// The format to write the list of types in.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+/// Every type referenced by some metadata, as written with `--format json`.
+#[derive(Serialize)]
+struct TypesOutput {
+    spec_version: u64,
+    types: Vec<TypeOutput>,
+}
+
+#[derive(Serialize)]
+struct TypeOutput {
+    name: String,
+    /// Whether the type can be found in every pallet that it's used in.
+    resolvable: bool,
+    /// The pallets that the type can't be found in, if any. Types scoped to a pallet are
+    /// preferred over global ones, so this can differ between pallets.
+    unresolvable_in: Vec<String>,
+    /// The calls, events, storage entries and other types which refer to this type.
+    used_by: Vec<String>,
+}
+
+/// Where some type name is used, and whether it can be found in each pallet that it's used in.
+#[derive(Default)]
+struct TypeUses {
+    resolvable_in: BTreeMap<String, bool>,
+    used_by: Vec<String>,
+}
+
+pub async fn run(opts: Opts) -> anyhow::Result<()> {
+    let historic_types = load_historic_types(&opts.types)?;
+    let metadata = load_metadata(&opts.metadata)?;
+    if metadata.version() >= 14 {
+        return Err(anyhow!(
+            "V14+ metadata describes its own types, so there are no type names to list"
+        ))
+        .context(ErrorKind::Config);
+    }
+
+    let mut types = historic_types.for_spec_version(opts.spec_version);
+    extend_with_metadata_info(&mut types, &metadata)?;
+
+    let uses = type_uses(&types, &metadata)?;
+    let mut output = TypesOutput {
+        spec_version: opts.spec_version,
+        types: uses
+            .into_iter()
+            .map(|(name, uses)| {
+                let unresolvable_in: Vec<String> = uses
+                    .resolvable_in
+                    .into_iter()
+                    .filter(|(_, resolvable)| !resolvable)
+                    .map(|(pallet, _)| pallet)
+                    .collect();
+                TypeOutput {
+                    name,
+                    resolvable: unresolvable_in.is_empty(),
+                    unresolvable_in,
+                    used_by: uses.used_by,
+                }
+            })
+            .collect(),
+    };
+    let unresolvable = output.types.iter().filter(|t| !t.resolvable).count();
+    if opts.unresolvable_only {
+        output.types.retain(|t| !t.resolvable);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    if opts.format.is_json() {
+        output::write_json_line(&mut stdout, &output)?;
+    } else {
+        writeln!(
+            stdout,
+            "Spec version {}: {} types referenced, {unresolvable} not resolvable",
+            output.spec_version,
+            output.types.len()
+        )?;
+        for ty in &output.types {
+            if ty.resolvable {
+                writeln!(stdout, "  {}", ty.name)?;
+            } else {
+                writeln!(
+                    stdout,
+                    "  {} (NOT RESOLVABLE in {}; used by {})",
+                    ty.name,
+                    ty.unresolvable_in.join(", "),
+                    ty.used_by.join(", ")
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find every type name referenced by the calls, events and storage entries in some metadata,
+/// and by the types that those refer to in turn, noting whether each can be resolved.
+fn type_uses(
+    types: &TypeRegistrySet,
+    metadata: &RuntimeMetadata,
+) -> anyhow::Result<BTreeMap<String, TypeUses>> {
+    // The types used directly by the metadata, and the pallet that they're used in.
+    let mut roots: Vec<(String, String, String)> = Vec::new();
+
+    let builtins = builtin_types::builtin_types_from_metadata(metadata)?;
+    for (enum_name, kind) in [("builtin::Call", "call"), ("builtin::Event", "event")] {
+        let modules = builtin_types::enum_variants(&builtins, enum_name)?.unwrap_or_default();
+        for module in modules {
+            for (_, module_enum) in &module.fields {
+                let items = builtin_types::enum_variants(&builtins, module_enum)?;
+                for item in items.into_iter().flatten() {
+                    for (_, ty) in item.fields {
+                        let used_by = format!("{kind} {}.{}", module.name, item.name);
+                        roots.push((module.name.clone(), ty, used_by));
+                    }
+                }
+            }
+        }
+    }
+
+    for entry in frame_decode::helpers::list_storage_entries_any(metadata) {
+        let (pallet, entry) = (entry.pallet(), entry.entry());
+        let (keys, value) = storage_type_names(pallet, entry, metadata)?;
+        for ty in keys.into_iter().chain([value]) {
+            roots.push((pallet.to_owned(), ty, format!("storage {pallet}.{entry}")));
+        }
+    }
+
+    walk_types(types, roots)
+}
+
+/// Walk each type that's reachable from the given (pallet, type name, used by) roots, once per
+/// pallet that it's reached from.
+fn walk_types(
+    types: &TypeRegistrySet,
+    roots: Vec<(String, String, String)>,
+) -> anyhow::Result<BTreeMap<String, TypeUses>> {
+    let mut uses: BTreeMap<String, TypeUses> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<_> = roots.into_iter().rev().collect();
+    while let Some((pallet, ty, used_by)) = stack.pop() {
+        let type_uses = uses.entry(ty.clone()).or_default();
+        if !type_uses.used_by.contains(&used_by) {
+            type_uses.used_by.push(used_by);
+        }
+        if !seen.insert((pallet.clone(), ty.clone())) {
+            continue;
+        }
+
+        let lookup_name = LookupName::parse(&ty)
+            .map_err(|e| anyhow!("Could not parse type name '{ty}': {e}"))?
+            .in_pallet(&pallet);
+        let shape = resolve_shape(types, lookup_name)?;
+        type_uses
+            .resolvable_in
+            .insert(pallet.clone(), shape != Shape::NotFound);
+
+        // The calls and events from the metadata are listed separately, so we don't need to
+        // follow them (eg from a `Vec<Call>` argument to `Utility.batch`).
+        if ty.starts_with("builtin::") {
+            continue;
+        }
+        for child in referenced_types(&shape).into_iter().rev() {
+            stack.push((pallet.clone(), child, format!("type {ty}")));
+        }
+    }
+
+    // Tuples and arrays are walked into, but aren't named types themselves.
+    uses.retain(|name, _| !name.starts_with(['(', '[']));
+    Ok(uses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_info_legacy::ChainTypeRegistry;
+
+    #[test]
+    fn finds_types_reachable_from_others() {
+        let types: ChainTypeRegistry = serde_yaml::from_str(
+            r#"
+            global:
+              types:
+                Foo:
+                  a: Vec<Bar>
+                  b: (u8, Baz)
+                Bar: u32
+              palletTypes:
+                Staking:
+                  Baz: u16
+            "#,
+        )
+        .unwrap();
+        let types = types.for_spec_version(0);
+
+        let root = |pallet: &str| {
+            (
+                pallet.to_owned(),
+                "Foo".to_owned(),
+                format!("call {pallet}.foo"),
+            )
+        };
+        let uses = walk_types(&types, vec![root("Balances"), root("Staking")]).unwrap();
+
+        let names: Vec<_> = uses.keys().map(|name| name.as_str()).collect();
+        // Aliases (like `Bar: u32`) are followed rather than listed.
+        assert_eq!(names, ["Bar", "Baz", "Foo", "Vec<Bar>", "u8"]);
+        assert_eq!(
+            uses["Foo"].used_by,
+            ["call Balances.foo", "call Staking.foo"]
+        );
+        assert_eq!(uses["Baz"].used_by, ["type (u8, Baz)"]);
+        assert_eq!(
+            uses["Baz"].resolvable_in,
+            BTreeMap::from([("Balances".to_owned(), false), ("Staking".to_owned(), true)])
+        );
+    }
+}
@@ -1,4 +1,20 @@
+pub mod check_call_indices;
+pub mod decode_block_file;
 pub mod decode_blocks;
+pub mod decode_events;
+pub mod decode_extrinsic;
+pub mod decode_storage_item;
 pub mod decode_storage_items;
+pub mod diff_types;
+pub mod dump_builtin_types;
+pub mod events_stats;
 pub mod fetch_metadata;
 pub mod find_spec_changes;
+pub mod get;
+pub mod list_types;
+pub mod query;
+pub mod resolve_type;
+pub mod run_matrix;
+pub mod schema;
+pub mod self_test;
+pub mod serve_rpc;
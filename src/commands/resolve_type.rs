@@ -0,0 +1,255 @@
+use super::decode_block_file::load_metadata;
+use crate::decoding::builtin_types::extend_with_metadata_info;
+use crate::decoding::historic_types::load_historic_types_yaml;
+use crate::decoding::type_provenance::TypeExplainer;
+use crate::utils::exit_code::ErrorKind;
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use scale_info_legacy::{ChainTypeRegistry, LookupName, TypeRegistrySet};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// The name of the type to resolve, eg `Address` or `Vec<RewardDestination>`.
+    type_name: String,
+
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// The spec version to pick types from the types file for.
+    #[arg(short, long)]
+    spec_version: u64,
+
+    /// Look the type up as it would be from within this pallet, so that types scoped to it
+    /// are used.
+    #[arg(short, long)]
+    pallet: Option<String>,
+
+    /// Pre-V14 metadata (as given to `decode-block-file`) to derive the `builtin::` types from.
+    /// Without this, only the types file and basic Rust types are used.
+    #[arg(short, long)]
+    metadata: Option<PathBuf>,
+}
+
+/// The optional name and the type of each field in a struct or variant.
+type Fields = Vec<(Option<String>, String)>;
+
+/// The shape that some type resolves to, with the names of any types that it refers to.
+#[derive(Debug, PartialEq)]
+pub(super) enum Shape {
+    Composite {
+        path: String,
+        fields: Fields,
+    },
+    Variant {
+        path: String,
+        variants: Vec<(u8, String, Fields)>,
+    },
+    Sequence(String),
+    Array(String, usize),
+    Tuple(Vec<String>),
+    Primitive(String),
+    Compact(String),
+    BitSequence(String),
+    NotFound,
+}
+
+pub async fn run(opts: Opts) -> anyhow::Result<()> {
+    let types_file = load_historic_types_yaml(&opts.types)?;
+    let historic_types: ChainTypeRegistry = serde_yaml::from_value(types_file.clone())
+        .with_context(|| "Can't parse historic types from JSON")
+        .context(ErrorKind::Config)?;
+
+    let mut types = historic_types.for_spec_version(opts.spec_version);
+    let explainer = match &opts.metadata {
+        None => TypeExplainer::from_types_file(&types_file, opts.spec_version),
+        Some(path) => {
+            let metadata = load_metadata(path)?;
+            if metadata.version() >= 14 {
+                return Err(anyhow!(
+                    "V14+ metadata describes its own types, so there's nothing to resolve here"
+                ))
+                .context(ErrorKind::Config);
+            }
+            extend_with_metadata_info(&mut types, &metadata)?;
+            TypeExplainer::new(&types_file, opts.spec_version, &metadata)?
+        }
+    };
+
+    let mut lookup_name = LookupName::parse(&opts.type_name)
+        .map_err(|e| anyhow!("Could not parse type name '{}': {e}", opts.type_name))
+        .context(ErrorKind::Config)?;
+    if let Some(pallet) = &opts.pallet {
+        lookup_name = lookup_name.in_pallet(pallet);
+    }
+    let shape = resolve_shape(&types, lookup_name)?;
+
+    let pallet = opts.pallet.as_deref();
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", opts.type_name)?;
+    if let Some(source) = explainer.source(pallet, &opts.type_name) {
+        writeln!(stdout, "  Defined in: {source}")?;
+    }
+    write!(stdout, "  Resolves to: ")?;
+    write_shape(&mut stdout, &shape)?;
+
+    let referenced = referenced_types(&shape);
+    if !referenced.is_empty() {
+        writeln!(stdout, "  Referenced types:")?;
+        for type_name in referenced {
+            if let Some(source) = explainer.source(pallet, &type_name) {
+                writeln!(stdout, "    {type_name}: {source}")?;
+            }
+        }
+    }
+
+    if shape == Shape::NotFound {
+        return Err(anyhow!("Type '{}' could not be resolved", opts.type_name))
+            .context(ErrorKind::Config);
+    }
+    Ok(())
+}
+
+/// Resolve a type one level deep, following any aliases.
+pub(super) fn resolve_shape(
+    types: &TypeRegistrySet,
+    lookup_name: LookupName,
+) -> anyhow::Result<Shape> {
+    let fields_of = |fields: &mut dyn scale_type_resolver::FieldIter<'_, LookupName>| {
+        fields
+            .map(|f| (f.name.map(ToOwned::to_owned), f.id.to_string()))
+            .collect()
+    };
+    let visitor =
+        scale_type_resolver::visitor::new::<_, LookupName, _, _>((), |_, _| Shape::NotFound)
+            .visit_not_found(|_| Shape::NotFound)
+            .visit_composite(move |_, path, fields| Shape::Composite {
+                path: path.collect::<Vec<_>>().join("::"),
+                fields: fields_of(fields),
+            })
+            .visit_variant(move |_, path, variants| Shape::Variant {
+                path: path.collect::<Vec<_>>().join("::"),
+                variants: variants
+                    .map(|mut v| (v.index, v.name.to_owned(), fields_of(&mut v.fields)))
+                    .collect(),
+            })
+            .visit_sequence(|_, _, ty| Shape::Sequence(ty.to_string()))
+            .visit_array(|_, ty, len| Shape::Array(ty.to_string(), len))
+            .visit_tuple(|_, tys| Shape::Tuple(tys.map(|ty| ty.to_string()).collect()))
+            .visit_primitive(|_, p| Shape::Primitive(format!("{p:?}").to_lowercase()))
+            .visit_compact(|_, ty| Shape::Compact(ty.to_string()))
+            .visit_bit_sequence(|_, store, order| {
+                Shape::BitSequence(format!("{store:?}, {order:?}").to_lowercase())
+            });
+
+    let shape = types
+        .resolve_type(lookup_name, visitor)
+        .with_context(|| "Could not resolve type")?;
+    Ok(shape)
+}
+
+/// The names of the types that some shape refers to, in order and without duplicates.
+pub(super) fn referenced_types(shape: &Shape) -> Vec<String> {
+    let names: Vec<&String> = match shape {
+        Shape::Composite { fields, .. } => fields.iter().map(|(_, ty)| ty).collect(),
+        Shape::Variant { variants, .. } => variants
+            .iter()
+            .flat_map(|(_, _, fields)| fields.iter().map(|(_, ty)| ty))
+            .collect(),
+        Shape::Sequence(ty) | Shape::Array(ty, _) | Shape::Compact(ty) => vec![ty],
+        Shape::Tuple(tys) => tys.iter().collect(),
+        Shape::Primitive(_) | Shape::BitSequence(_) | Shape::NotFound => vec![],
+    };
+
+    let mut out: Vec<String> = Vec::new();
+    for name in names {
+        if !out.contains(name) {
+            out.push(name.clone());
+        }
+    }
+    out
+}
+
+fn write_shape<W: Write>(mut w: W, shape: &Shape) -> anyhow::Result<()> {
+    let write_fields = |w: &mut W, fields: &[(Option<String>, String)], indent: &str| {
+        for (name, ty) in fields {
+            match name {
+                Some(name) => writeln!(w, "{indent}{name}: {ty}")?,
+                None => writeln!(w, "{indent}{ty}")?,
+            }
+        }
+        anyhow::Ok(())
+    };
+    let path_suffix = |path: &str| match path {
+        "" => String::new(),
+        path => format!(" {path}"),
+    };
+
+    match shape {
+        Shape::Composite { path, fields } => {
+            writeln!(w, "struct{}", path_suffix(path))?;
+            write_fields(&mut w, fields, "    ")?;
+        }
+        Shape::Variant { path, variants } => {
+            writeln!(w, "enum{}", path_suffix(path))?;
+            for (index, name, fields) in variants {
+                writeln!(w, "    {index}: {name}")?;
+                write_fields(&mut w, fields, "      ")?;
+            }
+        }
+        Shape::Sequence(ty) => writeln!(w, "sequence of {ty}")?,
+        Shape::Array(ty, len) => writeln!(w, "array of {len} {ty}")?,
+        Shape::Tuple(tys) => writeln!(w, "tuple ({})", tys.join(", "))?,
+        Shape::Primitive(p) => writeln!(w, "{p}")?,
+        Shape::Compact(ty) => writeln!(w, "compact {ty}")?,
+        Shape::BitSequence(format) => writeln!(w, "bit sequence ({format})")?,
+        Shape::NotFound => writeln!(w, "not found")?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolves_through_aliases() {
+        let types: ChainTypeRegistry = serde_yaml::from_str(
+            r#"
+            global:
+              types:
+                Address: MultiAddress
+                MultiAddress:
+                  _enum:
+                    Id: AccountId
+                    Index: Compact<u32>
+                AccountId: '[u8; 32]'
+            "#,
+        )
+        .unwrap();
+        let types = types.for_spec_version(0);
+
+        let shape = resolve_shape(&types, LookupName::parse("Address").unwrap()).unwrap();
+        assert_eq!(
+            shape,
+            Shape::Variant {
+                path: "MultiAddress".to_owned(),
+                variants: vec![
+                    (0, "Id".to_owned(), vec![(None, "AccountId".to_owned())]),
+                    (
+                        1,
+                        "Index".to_owned(),
+                        vec![(None, "Compact<u32>".to_owned())]
+                    ),
+                ],
+            }
+        );
+        assert_eq!(referenced_types(&shape), vec!["AccountId", "Compact<u32>"]);
+
+        let shape = resolve_shape(&types, LookupName::parse("Unknown").unwrap()).unwrap();
+        assert_eq!(shape, Shape::NotFound);
+    }
+}
@@ -0,0 +1,382 @@
+use crate::utils::exit_code::ErrorKind;
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// The TOML file listing the chains to decode and what to decode on each.
+    config: PathBuf,
+
+    /// The most connections that all of the running jobs can use between them.
+    /// Overrides `max_connections` in the config file.
+    #[arg(long)]
+    max_connections: Option<usize>,
+
+    /// The directory to write the output of each job, and the combined report, to.
+    #[arg(long, default_value = "matrix-output")]
+    output_dir: PathBuf,
+}
+
+/// The format of the config file given to `run-matrix`.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct MatrixConfig {
+    /// The most connections that all of the running jobs can use between them.
+    #[serde(default = "default_max_connections")]
+    max_connections: usize,
+    chains: Vec<ChainConfig>,
+}
+
+fn default_max_connections() -> usize {
+    8
+}
+
+/// A chain to decode blocks and/or storage on. Paths are relative to the config file.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ChainConfig {
+    name: String,
+    /// Defaults to the Polkadot RPC URLs if empty.
+    #[serde(default)]
+    urls: Vec<String>,
+    types: PathBuf,
+    spec_versions: Option<PathBuf>,
+    /// The block numbers to decode blocks in, if any.
+    blocks: Option<RangeConfig>,
+    /// The `number`s to decode storage at, if any.
+    storage: Option<RangeConfig>,
+    /// Any other arguments to pass to each command, eg `["--insecure"]`.
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RangeConfig {
+    start: u64,
+    /// The last one to decode. Without this, blocks are decoded up to the latest block and
+    /// storage is sampled forever.
+    end: Option<u64>,
+    /// How many connections this job fetches with.
+    #[serde(default = "default_connections")]
+    connections: usize,
+}
+
+fn default_connections() -> usize {
+    1
+}
+
+/// One run of `decode-blocks` or `decode-storage-items` for some chain.
+#[derive(Debug, PartialEq)]
+struct Job {
+    chain: String,
+    kind: &'static str,
+    connections: usize,
+    args: Vec<OsString>,
+}
+
+/// The outcome of some job, as given in the combined report.
+#[derive(serde::Serialize)]
+struct JobReport {
+    chain: String,
+    kind: &'static str,
+    status: String,
+    exit_code: Option<i32>,
+    /// How many blocks or storage entries failed to decode.
+    errors: usize,
+    /// How many errors of each category (eg `corrupt` or `network_error`) were written out.
+    error_categories: BTreeMap<String, u64>,
+    elapsed_secs: f64,
+    output: PathBuf,
+    log: PathBuf,
+}
+
+pub async fn run(opts: Opts) -> anyhow::Result<()> {
+    let config = load_config(&opts.config).context(ErrorKind::Config)?;
+    let max_connections = opts
+        .max_connections
+        .unwrap_or(config.max_connections)
+        .max(1);
+    let base_dir = opts.config.parent().unwrap_or(Path::new("."));
+    let jobs = jobs(&config, base_dir);
+    if jobs.is_empty() {
+        return Err(anyhow!("No blocks or storage to decode are configured"))
+            .context(ErrorKind::Config);
+    }
+
+    std::fs::create_dir_all(&opts.output_dir)
+        .with_context(|| format!("Could not create {}", opts.output_dir.display()))?;
+    let exe = std::env::current_exe()?;
+
+    // Each job waits until enough of the shared connections are free before starting.
+    let connections = Arc::new(Semaphore::new(max_connections));
+    let mut tasks = Vec::new();
+    for job in jobs {
+        let connections = connections.clone();
+        let exe = exe.clone();
+        let output = opts
+            .output_dir
+            .join(format!("{}-{}.jsonl", job.chain, job.kind));
+        let log = opts
+            .output_dir
+            .join(format!("{}-{}.log", job.chain, job.kind));
+        tasks.push(tokio::spawn(async move {
+            let permits = job.connections.min(max_connections) as u32;
+            let _permits = connections.acquire_many(permits).await?;
+            eprintln!("Starting {} {}", job.chain, job.kind);
+            let report = run_job(&exe, job, output, log).await?;
+            eprintln!(
+                "Finished {} {}: {}",
+                report.chain, report.kind, report.status
+            );
+            anyhow::Ok(report)
+        }));
+    }
+
+    let mut reports = Vec::new();
+    for task in tasks {
+        reports.push(task.await??);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    write_report(&mut stdout, &reports)?;
+    let report_path = opts.output_dir.join("report.json");
+    std::fs::write(&report_path, serde_json::to_string_pretty(&reports)?)
+        .with_context(|| format!("Could not write {}", report_path.display()))?;
+    writeln!(stdout, "Report written to {}", report_path.display())?;
+
+    let failed: Vec<_> = reports.iter().filter(|r| r.exit_code != Some(0)).collect();
+    match failed.first() {
+        None => Ok(()),
+        Some(first) => {
+            let err = anyhow!("{} of {} jobs failed", failed.len(), reports.len());
+            match first.exit_code.and_then(ErrorKind::from_exit_code) {
+                Some(kind) => Err(err.context(kind)),
+                None => Err(err),
+            }
+        }
+    }
+}
+
+fn load_config(path: &Path) -> anyhow::Result<MatrixConfig> {
+    let config_str = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not load {}", path.display()))?;
+    let config: MatrixConfig = toml::from_str(&config_str)
+        .with_context(|| format!("Could not parse {}", path.display()))?;
+
+    // Chain names are used in file names, so keep them simple and unique.
+    let mut names = HashSet::new();
+    for chain in &config.chains {
+        let is_valid = !chain.name.is_empty()
+            && chain
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !is_valid {
+            bail!(
+                "Chain name '{}' should only contain letters, numbers, '-' and '_'",
+                chain.name
+            );
+        }
+        if !names.insert(&chain.name) {
+            bail!("Chain '{}' is configured more than once", chain.name);
+        }
+    }
+    Ok(config)
+}
+
+/// Turn the config into the list of commands to run. Each job writes only its errors, as JSON,
+/// and keeps going after them so that the whole range is covered.
+fn jobs(config: &MatrixConfig, base_dir: &Path) -> Vec<Job> {
+    let mut jobs = Vec::new();
+    for chain in &config.chains {
+        let mut common: Vec<OsString> = vec![
+            "--types".into(),
+            base_dir.join(&chain.types).into(),
+            "--errors-only".into(),
+            "--continue-on-error".into(),
+            "--format".into(),
+            "json".into(),
+        ];
+        if !chain.urls.is_empty() {
+            common.extend(["--url".into(), chain.urls.join(",").into()]);
+        }
+
+        let ranges = [
+            ("blocks", &chain.blocks, "decode-blocks", "block"),
+            ("storage", &chain.storage, "decode-storage-items", "number"),
+        ];
+        for (kind, range, command, unit) in ranges {
+            let Some(range) = range else {
+                continue;
+            };
+            let mut args: Vec<OsString> = vec!["--ci".into(), command.into()];
+            args.extend(common.iter().cloned());
+            args.extend([
+                "--fetch-concurrency".into(),
+                range.connections.to_string().into(),
+                format!("--starting-{unit}").into(),
+                range.start.to_string().into(),
+            ]);
+            if let Some(end) = range.end {
+                args.extend([format!("--ending-{unit}").into(), end.to_string().into()]);
+            }
+            if let (Some(spec_versions), "storage") = (&chain.spec_versions, kind) {
+                args.extend([
+                    "--spec-versions".into(),
+                    base_dir.join(spec_versions).into(),
+                ]);
+            }
+            args.extend(chain.args.iter().map(OsString::from));
+
+            jobs.push(Job {
+                chain: chain.name.clone(),
+                kind,
+                connections: range.connections,
+                args,
+            });
+        }
+    }
+    jobs
+}
+
+/// Run a job to completion, writing its output and log to the given files.
+async fn run_job(exe: &Path, job: Job, output: PathBuf, log: PathBuf) -> anyhow::Result<JobReport> {
+    let started = Instant::now();
+    let stdout = std::fs::File::create(&output)
+        .with_context(|| format!("Could not create {}", output.display()))?;
+    let res = tokio::process::Command::new(exe)
+        .args(&job.args)
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("Could not run {} {}", job.chain, job.kind))?;
+    std::fs::write(&log, &res.stderr)
+        .with_context(|| format!("Could not write {}", log.display()))?;
+
+    // With --ci, the last line written to stderr is a JSON summary of the run.
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    let summary: Option<serde_json::Value> = stderr
+        .lines()
+        .last()
+        .and_then(|line| serde_json::from_str(line).ok());
+    let status = summary
+        .as_ref()
+        .and_then(|s| s["status"].as_str())
+        .unwrap_or("error")
+        .to_owned();
+
+    let error_categories = summary
+        .as_ref()
+        .and_then(|s| serde_json::from_value(s["errors"].clone()).ok())
+        .unwrap_or_default();
+
+    // We only write errors, one per line.
+    let errors = std::fs::read_to_string(&output)
+        .map(|s| s.lines().count())
+        .unwrap_or_default();
+
+    Ok(JobReport {
+        chain: job.chain,
+        kind: job.kind,
+        status,
+        exit_code: res.status.code(),
+        errors,
+        error_categories,
+        elapsed_secs: started.elapsed().as_secs_f64(),
+        output,
+        log,
+    })
+}
+
+fn write_report<W: std::io::Write>(mut w: W, reports: &[JobReport]) -> anyhow::Result<()> {
+    let chain_width = reports
+        .iter()
+        .map(|r| r.chain.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    writeln!(
+        w,
+        "{:chain_width$}  {:7}  {:17}  {:>6}  {:>9}",
+        "Chain", "Job", "Status", "Errors", "Time"
+    )?;
+    for r in reports {
+        writeln!(
+            w,
+            "{:chain_width$}  {:7}  {:17}  {:>6}  {:>8.1}s",
+            r.chain, r.kind, r.status, r.errors, r.elapsed_secs
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builds_jobs_from_config() {
+        let config: MatrixConfig = toml::from_str(
+            r#"
+            [[chains]]
+            name = "kusama"
+            urls = ["wss://a.example.com", "wss://b.example.com"]
+            types = "types/kusama.yaml"
+            spec_versions = "kusama_spec_versions.json"
+            storage = { start = 0, end = 10, connections = 2 }
+            args = ["--insecure"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.max_connections, 8);
+
+        let jobs = jobs(&config, Path::new("configs"));
+        let args: Vec<_> = [
+            "--ci",
+            "decode-storage-items",
+            "--types",
+            "configs/types/kusama.yaml",
+            "--errors-only",
+            "--continue-on-error",
+            "--format",
+            "json",
+            "--url",
+            "wss://a.example.com,wss://b.example.com",
+            "--fetch-concurrency",
+            "2",
+            "--starting-number",
+            "0",
+            "--ending-number",
+            "10",
+            "--spec-versions",
+            "configs/kusama_spec_versions.json",
+            "--insecure",
+        ]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        assert_eq!(
+            jobs,
+            vec![Job {
+                chain: "kusama".to_owned(),
+                kind: "storage",
+                connections: 2,
+                args,
+            }]
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use crate::utils::output::{
+    BlockEventsOutput, BlockOutput, ErrorOutput, EventStatsOutput, IndexedCallOutput,
+    RunnerErrorOutput, StorageEntryOutput,
+};
+use clap::Parser;
+use std::io::Write;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {}
+
+pub async fn run(_opts: Opts) -> anyhow::Result<()> {
+    let schemas = serde_json::json!({
+        "block": schemars::schema_for!(BlockOutput),
+        "block_events": schemars::schema_for!(BlockEventsOutput),
+        "event_stats": schemars::schema_for!(EventStatsOutput),
+        "indexed_call": schemars::schema_for!(IndexedCallOutput),
+        "storage_entry": schemars::schema_for!(StorageEntryOutput),
+        "error": schemars::schema_for!(ErrorOutput),
+        "runner_error": schemars::schema_for!(RunnerErrorOutput),
+    });
+
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &schemas)?;
+    writeln!(stdout)?;
+    Ok(())
+}
@@ -0,0 +1,380 @@
+use super::decode_blocks::{block_output, decode_extrinsics, Output};
+use super::fetch_metadata::state_get_metadata;
+use super::get::{fetch_storage_value, parse_key, StorageEntryName, StorageValueAt};
+use crate::decoding::decode_options::DecodeOptions;
+use crate::decoding::decoding_context::{DecodingContext, DecodingContextCache};
+use crate::decoding::extrinsic_decoder::Extrinsic;
+use crate::decoding::historic_types::{load_historic_types, warn_if_no_spec_types};
+use crate::decoding::indices::{self, AccountIndices};
+use crate::utils::output::OutputFormat;
+use crate::utils::{
+    self, block_ref::BlockRef, rpc::RpcOpts, runner::DecodePool, runner::RoundRobin,
+};
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use scale_info_legacy::ChainTypeRegistry;
+use serde_json::{json, Value as JsonValue};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use subxt::backend::{legacy::LegacyRpcMethods, rpc::RpcClient};
+use subxt::utils::H256;
+use subxt::PolkadotConfig;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Opts {
+    /// Historic type definitions.
+    #[arg(short, long)]
+    types: PathBuf,
+
+    /// URL of the node to connect to.
+    /// Defaults to using Polkadot RPC URLs if not given.
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// The address to listen for JSON-RPC requests on.
+    #[arg(long, default_value = "127.0.0.1:9955")]
+    listen: SocketAddr,
+
+    /// How many threads to decode blocks on. Defaults to the number of CPUs.
+    #[arg(long)]
+    decode_threads: Option<usize>,
+
+    #[command(flatten)]
+    rpc: RpcOpts,
+}
+
+// Standard JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Our own error code for anything that goes wrong while handling a valid request.
+const SERVER_ERROR: i64 = -32000;
+
+struct ServerState {
+    rpc_client: RpcClient,
+    rpcs: LegacyRpcMethods<PolkadotConfig>,
+    historic_types: Arc<ChainTypeRegistry>,
+    contexts: DecodingContextCache,
+    decode_pool: DecodePool,
+}
+
+pub async fn run(opts: Opts, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
+    let historic_types = Arc::new(load_historic_types(&opts.types)?);
+
+    // Use our the given URl, or polkadot RPC node urls if not given.
+    let urls = RoundRobin::new(
+        utils::validated_url_or_polkadot_rpc_nodes(opts.url.as_deref(), &opts.rpc).await?,
+    );
+    let rpc_client = opts.rpc.connect(urls.get()).await?;
+
+    let state = Arc::new(ServerState {
+        rpcs: LegacyRpcMethods::new(rpc_client.clone()),
+        rpc_client,
+        historic_types,
+        contexts: DecodingContextCache::new(options),
+        decode_pool: DecodePool::new(opts.decode_threads),
+    });
+
+    let make_service = make_service_fn(move |_conn| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle_request(state.clone(), req))) }
+    });
+
+    let server = hyper::Server::try_bind(&opts.listen)
+        .with_context(|| format!("Could not listen on {}", opts.listen))?
+        .serve(make_service);
+    eprintln!("Listening for JSON-RPC requests on http://{}", opts.listen);
+    server.await?;
+    Ok(())
+}
+
+async fn handle_request(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if req.method() != Method::POST {
+        let res = Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(Body::from("JSON-RPC requests should be POSTed"))
+            .unwrap();
+        return Ok(res);
+    }
+
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            return Ok(json_response(error_response(
+                JsonValue::Null,
+                PARSE_ERROR,
+                e,
+            )))
+        }
+    };
+    let res = match serde_json::from_slice::<JsonValue>(&body) {
+        Ok(JsonValue::Array(calls)) if !calls.is_empty() => {
+            let mut responses = Vec::with_capacity(calls.len());
+            for call in calls {
+                responses.push(handle_call(&state, call).await);
+            }
+            JsonValue::Array(responses)
+        }
+        Ok(call) => handle_call(&state, call).await,
+        Err(e) => error_response(JsonValue::Null, PARSE_ERROR, e),
+    };
+    Ok(json_response(res))
+}
+
+fn json_response(body: JsonValue) -> Response<Body> {
+    Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error_response(id: JsonValue, code: i64, message: impl std::fmt::Display) -> JsonValue {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.to_string() },
+    })
+}
+
+/// Handle a single JSON-RPC call, returning the response to it.
+async fn handle_call(state: &ServerState, call: JsonValue) -> JsonValue {
+    let id = call.get("id").cloned().unwrap_or(JsonValue::Null);
+    let Some(method) = call.get("method").and_then(|m| m.as_str()) else {
+        return error_response(id, INVALID_REQUEST, "Request has no method");
+    };
+    let params = Params(call.get("params").cloned().unwrap_or(JsonValue::Null));
+
+    let res = match method {
+        "historic_decodeBlock" => decode_block(state, &params).await,
+        "historic_decodeStorage" => decode_storage(state, &params).await,
+        "historic_metadataAt" => metadata_at(state, &params).await,
+        _ => return error_response(id, METHOD_NOT_FOUND, format!("Unknown method {method}")),
+    };
+
+    match res {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(CallError::InvalidParams(e)) => error_response(id, INVALID_PARAMS, format!("{e:#}")),
+        Err(CallError::Failed(e)) => error_response(id, SERVER_ERROR, format!("{e:#}")),
+    }
+}
+
+enum CallError {
+    InvalidParams(anyhow::Error),
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for CallError {
+    fn from(e: anyhow::Error) -> Self {
+        CallError::Failed(e)
+    }
+}
+
+/// The params given to some call, which can be given by position or by name.
+struct Params(JsonValue);
+
+impl Params {
+    fn get(&self, idx: usize, name: &str) -> Option<&JsonValue> {
+        match &self.0 {
+            JsonValue::Array(params) => params.get(idx),
+            JsonValue::Object(params) => params.get(name),
+            _ => None,
+        }
+        .filter(|param| !param.is_null())
+    }
+
+    fn required(&self, idx: usize, name: &str) -> Result<&JsonValue, CallError> {
+        self.get(idx, name)
+            .ok_or_else(|| CallError::InvalidParams(anyhow!("Missing param '{name}'")))
+    }
+
+    /// A block number, a hex encoded block hash, or "latest" or "finalized".
+    async fn block_at(&self, state: &ServerState, idx: usize) -> Result<(u64, H256), CallError> {
+        let at = self.required(idx, "at")?;
+        let block_ref = match at {
+            JsonValue::Number(n) => n.as_u64().map(BlockRef::Number),
+            JsonValue::String(s) => s.parse().ok(),
+            _ => None,
+        };
+        let Some(block_ref) = block_ref else {
+            return Err(CallError::InvalidParams(anyhow!(
+                "'at' should be a block number or hash, or \"latest\" or \"finalized\""
+            )));
+        };
+        let block = block_ref.resolve(&state.rpcs).await?;
+        Ok(block)
+    }
+}
+
+/// `historic_decodeBlock(at)`: the decoded extrinsics in a block, in the same form as
+/// `decode-blocks --format json --print-bytes`.
+async fn decode_block(state: &ServerState, params: &Params) -> Result<JsonValue, CallError> {
+    let (block_number, block_hash) = params.block_at(state, 0).await?;
+
+    // Runtime updates take effect the block after they are applied.
+    let runtime_update_block_hash = match block_number {
+        0 => block_hash,
+        _ => {
+            state
+                .rpcs
+                .chain_get_header(Some(block_hash))
+                .await
+                .with_context(|| "Could not fetch block header")?
+                .ok_or_else(|| anyhow!("Block {block_number} not found"))?
+                .parent_hash
+        }
+    };
+    let (spec_version, runtime) = runtime_at(state, runtime_update_block_hash).await?;
+    warn_if_no_spec_types(
+        block_number,
+        spec_version,
+        &runtime.options,
+        OutputFormat::Text,
+    );
+
+    let block_body = state
+        .rpcs
+        .chain_get_block(Some(block_hash))
+        .await
+        .with_context(|| "Could not fetch block body")?
+        .ok_or_else(|| anyhow!("Block {block_number} not found"))?;
+
+    let extrinsics = block_body.block.extrinsics;
+    let decode_runtime = runtime.clone();
+    let mut extrinsics = state
+        .decode_pool
+        .run(move || {
+            decode_extrinsics(
+                extrinsics,
+                &decode_runtime.metadata,
+                &decode_runtime.types,
+                &decode_runtime.options,
+            )
+        })
+        .await?;
+
+    let decoded_extrinsics: Vec<&mut Extrinsic> = extrinsics
+        .iter_mut()
+        .filter_map(|(_, ext)| ext.as_mut().ok())
+        .collect();
+    indices::resolve_extrinsic_addresses(
+        &state.rpcs,
+        block_hash,
+        &runtime.metadata,
+        &runtime.types,
+        &runtime.options,
+        decoded_extrinsics,
+        &mut AccountIndices::new(),
+    )
+    .await
+    .with_context(|| "Could not resolve account indices")?;
+
+    let output = Output {
+        spec_version,
+        block_number,
+        block_hash,
+        parent_hash: None,
+        extrinsics,
+        endpoint: None,
+        explainer: None,
+        docs_from: None,
+        outcomes: None,
+    };
+    let block = block_output(&output, false, true);
+    Ok(serde_json::to_value(block).map_err(anyhow::Error::from)?)
+}
+
+/// `historic_decodeStorage(entry, keys, at)`: the decoded value at some storage entry, where
+/// `entry` is eg `System.Account` and `keys` is an array of strings as given to `get storage`.
+async fn decode_storage(state: &ServerState, params: &Params) -> Result<JsonValue, CallError> {
+    let entry_name = params
+        .required(0, "entry")?
+        .as_str()
+        .ok_or_else(|| CallError::InvalidParams(anyhow!("'entry' should be a string")))?;
+    let StorageEntryName { pallet, entry } =
+        entry_name.parse().map_err(CallError::InvalidParams)?;
+
+    let keys = match params.get(1, "keys") {
+        None => Vec::new(),
+        Some(JsonValue::Array(keys)) => keys
+            .iter()
+            .map(|key| match key {
+                JsonValue::String(key) => parse_key(key),
+                key => parse_key(&key.to_string()),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(CallError::InvalidParams)?,
+        Some(_) => {
+            return Err(CallError::InvalidParams(anyhow!(
+                "'keys' should be an array"
+            )))
+        }
+    };
+    let (block_number, block_hash) = params.block_at(state, 2).await?;
+
+    let StorageValueAt {
+        spec_version,
+        exists,
+        value,
+    } = fetch_storage_value(
+        &state.rpc_client,
+        &state.contexts,
+        &state.historic_types,
+        &pallet,
+        &entry,
+        &keys,
+        block_hash,
+    )
+    .await?;
+
+    Ok(json!({
+        "block_number": block_number,
+        "block_hash": subxt::utils::to_hex(block_hash),
+        "spec_version": spec_version,
+        "pallet": pallet,
+        "entry": entry,
+        "exists": exists,
+        "value": value,
+    }))
+}
+
+/// `historic_metadataAt(at)`: the metadata at some block, as JSON.
+async fn metadata_at(state: &ServerState, params: &Params) -> Result<JsonValue, CallError> {
+    let (_, block_hash) = params.block_at(state, 0).await?;
+    let (_, runtime) = runtime_at(state, block_hash).await?;
+    Ok(serde_json::to_value(&*runtime.metadata).map_err(anyhow::Error::from)?)
+}
+
+/// The spec version, metadata and types in use at some block.
+async fn runtime_at(
+    state: &ServerState,
+    block_hash: H256,
+) -> anyhow::Result<(u32, Arc<DecodingContext>)> {
+    let spec_version = state
+        .rpcs
+        .state_get_runtime_version(Some(block_hash))
+        .await
+        .with_context(|| format!("Could not fetch runtime version for block {block_hash}"))?
+        .spec_version;
+
+    let runtime = state
+        .contexts
+        .get_or_prepare(spec_version, &state.historic_types, || async {
+            if let Some(metadata) = &state.contexts.options().pinned_metadata {
+                return Ok(metadata.clone());
+            }
+            state_get_metadata(&state.rpc_client, Some(block_hash))
+                .await
+                .map(Arc::new)
+        })
+        .await?;
+    Ok((spec_version, runtime))
+}
@@ -0,0 +1,187 @@
+//! How account IDs are written out. By default, signer addresses are given in SS58 format and
+//! account IDs in call arguments and storage are left as bytes. Given an [`AddressFormat`], both
+//! are written in that format instead.
+
+use super::decode_options::DecodeOptions;
+use crate::utils::value_to_bytes;
+use scale_type_resolver::TypeResolver;
+use scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// The SS58 prefix used by default; that of generic Substrate chains.
+pub const DEFAULT_SS58_PREFIX: u16 = 42;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFormat {
+    /// SS58 addresses, like `5GrwvaEF...`.
+    Ss58,
+    /// Hex encoded account IDs, like `0xd43593c7...`.
+    Hex,
+    /// Both, like `5GrwvaEF... (0xd43593c7...)`.
+    Both,
+}
+
+/// Format some account ID (eg the address of a signed extrinsic) in the chosen format, or in
+/// SS58 format if none was chosen.
+pub fn format_account_id(account_id: &[u8; 32], opts: &DecodeOptions) -> String {
+    let ss58 = || to_ss58(account_id, opts.ss58_prefix);
+    match opts.address_format.unwrap_or(AddressFormat::Ss58) {
+        AddressFormat::Ss58 => ss58(),
+        AddressFormat::Hex => subxt::utils::to_hex(account_id),
+        AddressFormat::Both => format!("{} ({})", ss58(), subxt::utils::to_hex(account_id)),
+    }
+}
+
+/// Parse an account ID formatted by [`format_account_id`], or given in SS58 format with any
+/// prefix.
+pub fn parse_account_id(s: &str) -> Option<[u8; 32]> {
+    // In the "both" format, the SS58 address comes first.
+    let s = s.split_once(' ').map_or(s, |(first, _)| first);
+    if let Some(hex) = s.strip_prefix("0x") {
+        return hex::decode(hex).ok()?.try_into().ok();
+    }
+
+    let bytes = bs58::decode(s).into_vec().ok()?;
+    let prefix_len = match bytes.first()? {
+        0..=63 => 1,
+        64..=127 => 2,
+        _ => return None,
+    };
+    if bytes.len() != prefix_len + 32 + 2 {
+        return None;
+    }
+    let (data, checksum) = bytes.split_at(prefix_len + 32);
+    if ss58_checksum(data)[..2] != *checksum {
+        return None;
+    }
+    data[prefix_len..].try_into().ok()
+}
+
+/// The account ID in some value, whether it's still 32 bytes or has been formatted as a string
+/// by [`format_account_ids`].
+pub fn value_to_account_id<T>(value: &Value<T>) -> Option<[u8; 32]> {
+    match &value.value {
+        ValueDef::Primitive(Primitive::String(s)) => parse_account_id(s),
+        _ => value_to_bytes(value)?.try_into().ok(),
+    }
+}
+
+/// Replace any account IDs in some decoded value with strings in the chosen address format, if
+/// one was chosen. Account IDs are 32 byte values whose type is `AccountId32` in V14+ metadata,
+/// or has a name containing `AccountId` in the historic types.
+pub fn format_account_ids<Resolver>(
+    value: &mut Value<Resolver::TypeId>,
+    types: &Resolver,
+    opts: &DecodeOptions,
+) where
+    Resolver: TypeResolver,
+    Resolver::TypeId: Clone + std::fmt::Display,
+{
+    if opts.address_format.is_none() {
+        return;
+    }
+
+    if let Some(account_id) = value_to_bytes(value).and_then(|b| <[u8; 32]>::try_from(b).ok()) {
+        if is_account_type(&value.context, types) {
+            let formatted = format_account_id(&account_id, opts);
+            value.value = ValueDef::Primitive(Primitive::String(formatted));
+        }
+        return;
+    }
+    let composite = match &mut value.value {
+        ValueDef::Composite(composite) => composite,
+        ValueDef::Variant(variant) => &mut variant.values,
+        _ => return,
+    };
+    match composite {
+        Composite::Named(vals) => vals
+            .iter_mut()
+            .for_each(|(_, v)| format_account_ids(v, types, opts)),
+        Composite::Unnamed(vals) => vals
+            .iter_mut()
+            .for_each(|v| format_account_ids(v, types, opts)),
+    }
+}
+
+fn is_account_type<Resolver>(type_id: &Resolver::TypeId, types: &Resolver) -> bool
+where
+    Resolver: TypeResolver,
+    Resolver::TypeId: Clone + std::fmt::Display,
+{
+    if type_id.to_string().contains("AccountId") {
+        return true;
+    }
+    let visitor = scale_type_resolver::visitor::new::<_, Resolver::TypeId, _, _>((), |_, _| false)
+        .visit_composite(|_, path, _| path.last() == Some("AccountId32"));
+    types
+        .resolve_type(type_id.clone(), visitor)
+        .unwrap_or(false)
+}
+
+/// Encode an account ID as an SS58 address with the given prefix.
+fn to_ss58(account_id: &[u8; 32], prefix: u16) -> String {
+    let mut data = match prefix {
+        0..=63 => vec![prefix as u8],
+        // Larger prefixes are 14 bits, split over two bytes as the SS58 format describes.
+        _ => {
+            let first = ((prefix & 0b0000_0000_1111_1100) as u8) >> 2;
+            let second = ((prefix >> 8) as u8) | (((prefix & 0b0000_0000_0000_0011) as u8) << 6);
+            vec![first | 0b0100_0000, second]
+        }
+    };
+    data.extend(account_id);
+    let checksum = ss58_checksum(&data);
+    data.extend(&checksum[..2]);
+    bs58::encode(data).into_string()
+}
+
+fn ss58_checksum(data: &[u8]) -> [u8; 64] {
+    let mut preimage = b"SS58PRE".to_vec();
+    preimage.extend(data);
+    sp_crypto_hashing::blake2_512(&preimage)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use subxt::utils::AccountId32;
+
+    #[test]
+    fn ss58_matches_subxt() {
+        let account_id = [7; 32];
+        let ss58 = to_ss58(&account_id, DEFAULT_SS58_PREFIX);
+        assert_eq!(ss58, AccountId32(account_id).to_string());
+        assert_eq!(parse_account_id(&ss58), Some(account_id));
+    }
+
+    #[test]
+    fn ss58_uses_chain_prefix() {
+        let alice = hex::decode("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            to_ss58(&alice, 0),
+            "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5"
+        );
+        assert_eq!(
+            to_ss58(&alice, 2),
+            "HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F"
+        );
+    }
+
+    #[test]
+    fn parses_other_prefixes_and_hex() {
+        let account_id = [9; 32];
+        for prefix in [0, 2, 63, 64, 1284, 16383] {
+            assert_eq!(
+                parse_account_id(&to_ss58(&account_id, prefix)),
+                Some(account_id)
+            );
+        }
+        let hex = subxt::utils::to_hex(account_id);
+        assert_eq!(parse_account_id(&hex), Some(account_id));
+        let both = format!("{} ({hex})", to_ss58(&account_id, 0));
+        assert_eq!(parse_account_id(&both), Some(account_id));
+        assert_eq!(parse_account_id("ALICE"), None);
+    }
+}
@@ -0,0 +1,285 @@
+use super::decoded;
+use frame_metadata::RuntimeMetadata;
+use scale_info_legacy::type_shape::{Field, TypeShape, Variant, VariantDesc};
+use scale_info_legacy::{InsertName, LookupName, TypeRegistry, TypeRegistrySet};
+
+/// Extend the given historic types with the type information that we can derive from
+/// some pre-V14 metadata, so that things like `builtin::Call`, `builtin::Event` and
+/// `builtin::Error` can be referenced. These are prepended, so the types file can still override any of them.
+pub fn extend_with_metadata_info(
+    types: &mut TypeRegistrySet<'_>,
+    metadata: &RuntimeMetadata,
+) -> anyhow::Result<()> {
+    let builtin_types = builtin_types_from_metadata(metadata)?;
+    types.prepend(builtin_types);
+    Ok(())
+}
+
+/// Build the types that we derive from some metadata. Modern (V14+) metadata contains
+/// all of the type information that we need already, and so this is empty for it.
+pub fn builtin_types_from_metadata(metadata: &RuntimeMetadata) -> anyhow::Result<TypeRegistry> {
+    let mut types = frame_decode::helpers::type_registry_from_metadata_any(metadata)?;
+
+    let version = metadata.version();
+    if (8..14).contains(&version) {
+        types.extend(event_record_types(version));
+    }
+    if let Some(module_errors) = module_errors(metadata) {
+        types.extend(module_error_types(module_errors));
+    }
+
+    Ok(types)
+}
+
+/// The top level enums that we generate from pre-V14 metadata.
+pub const BUILTIN_ENUMS: [&str; 3] = ["builtin::Call", "builtin::Event", "builtin::Error"];
+
+/// A single variant of some enum in a [`TypeRegistry`].
+pub struct VariantInfo {
+    pub index: u8,
+    pub name: String,
+    /// The optional name and the type of each field in the variant.
+    pub fields: Vec<(Option<String>, String)>,
+}
+
+/// Return the variants of the given type, or `None` if it's not an enum that we know about.
+pub fn enum_variants(types: &TypeRegistry, name: &str) -> anyhow::Result<Option<Vec<VariantInfo>>> {
+    let visitor = scale_type_resolver::visitor::new::<_, LookupName, _, _>((), |_, _| None)
+        .visit_variant(|_, _, variants| {
+            let variants = variants
+                .map(|variant| VariantInfo {
+                    index: variant.index,
+                    name: variant.name.to_owned(),
+                    fields: variant
+                        .fields
+                        .map(|field| (field.name.map(ToOwned::to_owned), field.id.to_string()))
+                        .collect(),
+                })
+                .collect();
+            Some(variants)
+        });
+
+    let variants = types.resolve_type_str(name, visitor)?;
+    Ok(variants)
+}
+
+/// The `System.Events` storage entry is a `Vec<EventRecord<T::Event, T::Hash>>` in all
+/// pre-V14 metadatas that we support. Here we describe the shape of this `EventRecord`,
+/// and point `T::Event` at the `builtin::Event` type that we generate from the metadata.
+fn event_record_types(metadata_version: u32) -> TypeRegistry {
+    let mut types = TypeRegistry::empty();
+
+    // The Initialization phase was added alongside V11 metadata. Before then,
+    // only ApplyExtrinsic and Finalization existed.
+    let mut phase_variants = vec![
+        Variant {
+            index: 0,
+            name: "ApplyExtrinsic".to_owned(),
+            fields: VariantDesc::TupleOf(vec![lookup("u32")]),
+        },
+        Variant {
+            index: 1,
+            name: "Finalization".to_owned(),
+            fields: VariantDesc::TupleOf(vec![]),
+        },
+    ];
+    if metadata_version >= 11 {
+        phase_variants.push(Variant {
+            index: 2,
+            name: "Initialization".to_owned(),
+            fields: VariantDesc::TupleOf(vec![]),
+        });
+    }
+    types.insert(insert("Phase"), TypeShape::EnumOf(phase_variants));
+
+    types.insert(
+        insert("EventRecord<E, T>"),
+        TypeShape::StructOf(vec![
+            Field {
+                name: "phase".to_owned(),
+                value: lookup("Phase"),
+            },
+            Field {
+                name: "event".to_owned(),
+                value: lookup("E"),
+            },
+            Field {
+                name: "topics".to_owned(),
+                value: lookup("Vec<T>"),
+            },
+        ]),
+    );
+
+    types.insert(
+        insert("T::Event"),
+        TypeShape::AliasOf(lookup("builtin::Event")),
+    );
+
+    types
+}
+
+/// The errors that a single module can return.
+struct ModuleErrors {
+    /// The module index, as seen in `DispatchError::Module` errors.
+    index: u8,
+    name: String,
+    errors: Vec<String>,
+}
+
+/// Pull the module errors out of some pre-V14 metadata.
+fn module_errors(metadata: &RuntimeMetadata) -> Option<Vec<ModuleErrors>> {
+    // Before V12, modules did not have an explicit index, and were instead indexed
+    // by their position in the runtime (which is the order they appear in the metadata).
+    macro_rules! module_errors {
+        ($metadata:ident, |$pos:ident, $module:ident| $index:expr) => {
+            decoded(&$metadata.modules)
+                .iter()
+                .enumerate()
+                .map(|($pos, $module)| ModuleErrors {
+                    index: $index,
+                    name: decoded(&$module.name).clone(),
+                    errors: decoded(&$module.errors)
+                        .iter()
+                        .map(|e| decoded(&e.name).clone())
+                        .collect(),
+                })
+                .collect()
+        };
+    }
+
+    let errors = match metadata {
+        RuntimeMetadata::V8(m) => module_errors!(m, |pos, _module| pos as u8),
+        RuntimeMetadata::V9(m) => module_errors!(m, |pos, _module| pos as u8),
+        RuntimeMetadata::V10(m) => module_errors!(m, |pos, _module| pos as u8),
+        RuntimeMetadata::V11(m) => module_errors!(m, |pos, _module| pos as u8),
+        RuntimeMetadata::V12(m) => module_errors!(m, |_pos, module| module.index),
+        RuntimeMetadata::V13(m) => module_errors!(m, |_pos, module| module.index),
+        _ => return None,
+    };
+    Some(errors)
+}
+
+/// Describe the errors in each module as `builtin::module::error::$MODULE` enums, and combine
+/// these into a `builtin::Error` enum whose shape matches the `{ index, error }` bytes that
+/// a `DispatchError::Module` contains.
+fn module_error_types(module_errors: Vec<ModuleErrors>) -> TypeRegistry {
+    let mut types = TypeRegistry::empty();
+    let mut module_variants = vec![];
+
+    for module in module_errors {
+        if module.errors.is_empty() {
+            continue;
+        }
+
+        let error_variants = module
+            .errors
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| Variant {
+                index: idx as u8,
+                name,
+                fields: VariantDesc::TupleOf(vec![]),
+            })
+            .collect();
+
+        let error_enum_name = format!("builtin::module::error::{}", module.name);
+        types.insert(insert(&error_enum_name), TypeShape::EnumOf(error_variants));
+
+        module_variants.push(Variant {
+            index: module.index,
+            name: module.name,
+            fields: VariantDesc::TupleOf(vec![lookup(&error_enum_name)]),
+        });
+    }
+
+    types.insert(insert("builtin::Error"), TypeShape::EnumOf(module_variants));
+    types
+}
+
+fn insert(name: &str) -> InsertName {
+    InsertName::parse(name).expect("builtin type names should be valid")
+}
+
+fn lookup(name: &str) -> LookupName {
+    LookupName::parse(name).expect("builtin type names should be valid")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode_event_record(metadata_version: u32, bytes: &[u8]) -> scale_value::Value<String> {
+        let mut types = TypeRegistry::basic();
+        types
+            .insert_str("builtin::Event", TypeShape::AliasOf(lookup("u8")))
+            .unwrap();
+        types
+            .insert_str("T::Hash", TypeShape::AliasOf(lookup("[u8; 2]")))
+            .unwrap();
+
+        let mut types = TypeRegistrySet::from_iter([types]);
+        types.prepend(event_record_types(metadata_version));
+
+        let ty = lookup("EventRecord<T::Event, T::Hash>").in_pallet("System");
+        scale_value::scale::decode_as_type(&mut &*bytes, ty, &types)
+            .unwrap()
+            .map_context(|ctx| ctx.to_string())
+    }
+
+    #[test]
+    fn decodes_event_records() {
+        // Phase::ApplyExtrinsic(1), event 7, topics [[1,2]].
+        let bytes = [0, 1, 0, 0, 0, 7, 4, 1, 2];
+        let value = decode_event_record(10, &bytes);
+        assert_eq!(
+            value.to_string(),
+            "{ phase: ApplyExtrinsic (1), event: 7, topics: ((1, 2)) }"
+        );
+
+        // Phase::Initialization only exists from V11.
+        let bytes = [2, 7, 0];
+        let value = decode_event_record(11, &bytes);
+        assert_eq!(
+            value.to_string(),
+            "{ phase: Initialization (), event: 7, topics: () }"
+        );
+    }
+
+    #[test]
+    fn decodes_module_errors() {
+        let module_errors = vec![
+            ModuleErrors {
+                index: 0,
+                name: "System".to_owned(),
+                errors: vec![],
+            },
+            ModuleErrors {
+                index: 5,
+                name: "Balances".to_owned(),
+                errors: vec![
+                    "VestingBalance".to_owned(),
+                    "LiquidityRestrictions".to_owned(),
+                ],
+            },
+        ];
+
+        let types = module_error_types(module_errors);
+        let value = scale_value::scale::decode_as_type(
+            &mut &[5u8, 1][..],
+            lookup("builtin::Error"),
+            &types,
+        )
+        .unwrap()
+        .map_context(|ctx| ctx.to_string());
+        assert_eq!(value.to_string(), "Balances (LiquidityRestrictions ())");
+
+        let variants = enum_variants(&types, "builtin::Error").unwrap().unwrap();
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].index, 5);
+        assert_eq!(variants[0].name, "Balances");
+        assert_eq!(
+            variants[0].fields,
+            vec![(None, "builtin::module::error::Balances".to_owned())]
+        );
+    }
+}
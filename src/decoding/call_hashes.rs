@@ -0,0 +1,123 @@
+use super::storage_decoder::StorageKey;
+use scale_value::{At, Value, ValueDef};
+
+/// A call that some storage value refers to by hash, and the block that we expect
+/// to find the call in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallHashRef {
+    pub call_hash: [u8; 32],
+    pub block_number: u32,
+}
+
+/// Does this storage entry refer to calls by their hash?
+pub fn has_call_hash_refs(pallet: &str, entry: &str) -> bool {
+    matches!(
+        (pallet, entry),
+        ("Multisig", "Multisigs") | ("Proxy", "Announcements")
+    )
+}
+
+/// Find the calls that a decoded `Multisig.Multisigs` or `Proxy.Announcements` storage
+/// entry refers to.
+pub fn call_hash_refs(
+    pallet: &str,
+    entry: &str,
+    key: &[StorageKey],
+    value: &Value<String>,
+) -> Vec<CallHashRef> {
+    match (pallet, entry) {
+        // Keyed by (multisig account, call hash). The value records the block where the
+        // multisig operation started, which is where the call will have been submitted
+        // if it was given to `as_multi`.
+        ("Multisig", "Multisigs") => {
+            let call_hash = key
+                .get(1)
+                .and_then(|k| k.value.as_ref())
+                .and_then(value_to_hash);
+            let block_number = value.at("when").at("height").and_then(as_u32);
+            call_hash
+                .zip(block_number)
+                .map(|(call_hash, block_number)| CallHashRef {
+                    call_hash,
+                    block_number,
+                })
+                .into_iter()
+                .collect()
+        }
+        // The value is (Vec<Announcement>, Balance), where each announcement
+        // contains the call hash and the block that it was announced at.
+        ("Proxy", "Announcements") => {
+            let Some(announcements) = value.at(0) else {
+                return Vec::new();
+            };
+            let ValueDef::Composite(announcements) = &announcements.value else {
+                return Vec::new();
+            };
+            announcements
+                .values()
+                .filter_map(|announcement| {
+                    let call_hash = announcement.at("call_hash").and_then(value_to_hash)?;
+                    let block_number = announcement.at("height").and_then(as_u32)?;
+                    Some(CallHashRef {
+                        call_hash,
+                        block_number,
+                    })
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn as_u32(value: &Value<String>) -> Option<u32> {
+    value.as_u128().and_then(|n| n.try_into().ok())
+}
+
+fn value_to_hash(value: &Value<String>) -> Option<[u8; 32]> {
+    crate::utils::value_to_bytes(value)?.try_into().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn with_ctx(value: Value<()>) -> Value<String> {
+        value.map_context(|_| String::new())
+    }
+
+    fn hash_value(byte: u8) -> Value<()> {
+        Value::unnamed_composite([Value::unnamed_composite(
+            (0..32).map(|_| Value::u128(byte as u128)),
+        )])
+    }
+
+    #[test]
+    fn finds_proxy_announcement_refs() {
+        let announcement = |byte, height| {
+            Value::named_composite([
+                ("real", Value::u128(0)),
+                ("call_hash", hash_value(byte)),
+                ("height", Value::u128(height)),
+            ])
+        };
+        let value = with_ctx(Value::unnamed_composite([
+            Value::unnamed_composite([announcement(1, 100), announcement(2, 200)]),
+            Value::u128(1000),
+        ]));
+
+        let refs = call_hash_refs("Proxy", "Announcements", &[], &value);
+        assert_eq!(
+            refs,
+            vec![
+                CallHashRef {
+                    call_hash: [1; 32],
+                    block_number: 100
+                },
+                CallHashRef {
+                    call_hash: [2; 32],
+                    block_number: 200
+                },
+            ]
+        );
+    }
+}
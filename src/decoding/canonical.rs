@@ -0,0 +1,100 @@
+//! Checking that values are encoded the way that they would be if we encoded them ourselves.
+//! Values which decode but aren't canonically encoded (eg bit sequences with stray bits set in
+//! their padding) were probably decoded with the wrong type.
+
+use super::decode_options::DecodeOptions;
+use scale_type_resolver::TypeResolver;
+use scale_value::Value;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// A value which decoded, but isn't encoded as it would be if we encoded it. Errors are tagged
+/// with this so that they can be reported apart from values which don't decode at all.
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct NonCanonical {
+    /// The first byte of the value which differs when we encode it.
+    pub offset: u64,
+    /// The value that the bytes decoded to.
+    pub decoded: String,
+}
+
+impl std::fmt::Display for NonCanonical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let NonCanonical { offset, decoded } = self;
+        write!(
+            f,
+            "Non-canonical encoding: the value decodes, but encodes differently from byte \
+             {offset}; is the type wrong? It decoded to: {decoded}"
+        )
+    }
+}
+
+impl std::error::Error for NonCanonical {}
+
+/// Find the [`NonCanonical`] that an error was caused by, if any.
+pub fn non_canonical_of(err: &anyhow::Error) -> Option<NonCanonical> {
+    err.downcast_ref::<NonCanonical>().cloned()
+}
+
+/// Check that some bytes are what the value that they decoded to encodes to.
+pub fn check_canonical<Resolver, Ctx>(
+    bytes: &[u8],
+    value: &Value<Ctx>,
+    type_id: Resolver::TypeId,
+    types: &Resolver,
+    opts: &DecodeOptions,
+) -> Result<(), NonCanonical>
+where
+    Resolver: TypeResolver,
+    Ctx: std::fmt::Display,
+{
+    let mut encoded = Vec::with_capacity(bytes.len());
+    // If we can't encode the value at all, it's not one that we can say anything about.
+    if scale_value::scale::encode_as_type(value, type_id, types, &mut encoded).is_err() {
+        return Ok(());
+    }
+    if encoded == bytes {
+        return Ok(());
+    }
+
+    let offset = encoded
+        .iter()
+        .zip(bytes)
+        .position(|(a, b)| a != b)
+        .unwrap_or(encoded.len().min(bytes.len()));
+    let mut decoded = String::new();
+    let _ = crate::utils::write_value_fmt(&mut decoded, value, opts);
+    Err(NonCanonical {
+        offset: offset as u64,
+        decoded,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_info_legacy::{ChainTypeRegistry, LookupName};
+
+    #[test]
+    fn finds_non_canonical_values() {
+        let types: ChainTypeRegistry = serde_yaml::from_str(
+            r#"
+            global:
+              types:
+                Flags: BitVec<u8, bitvec::order::Lsb0>
+            "#,
+        )
+        .unwrap();
+        let types = types.for_spec_version(0);
+        let check = |bytes: &[u8]| {
+            let ty = LookupName::parse("Flags").unwrap();
+            let value =
+                scale_value::scale::decode_as_type(&mut &*bytes, ty.clone(), &types).unwrap();
+            check_canonical(bytes, &value, ty, &types, &DecodeOptions::default())
+        };
+
+        // Three bits, 101, and then the padding bits of the byte.
+        assert_eq!(check(&[3 << 2, 0b0000_0101]), Ok(()));
+        assert_eq!(check(&[3 << 2, 0b1000_0101]).unwrap_err().offset, 1);
+    }
+}
@@ -0,0 +1,552 @@
+//! Decoding values with limits on how much they can grow. A wrong historic type can turn a few
+//! bytes into a sequence with a huge length prefix, which would otherwise reserve (and abort the
+//! process trying to allocate) far more memory than we have, or refer to itself such that
+//! decoding recurses until the stack overflows. Here, decoding stops with a [`LimitExceeded`]
+//! error instead.
+
+use super::decode_options::DecodeOptions;
+use super::{addresses, canonical, named_types};
+use scale_decode::visitor::types::{Array, BitSequence, Composite, Sequence, Str, Tuple, Variant};
+use scale_decode::visitor::{DecodeAsTypeResult, DecodeError, Visitor};
+use scale_type_resolver::TypeResolver;
+use scale_value::{Primitive, Value, ValueDef};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// The default for `--max-decode-memory`, in MiB.
+pub const DEFAULT_MAX_DECODE_MEMORY_MIB: usize = 1024;
+/// The default for `--max-decode-depth`.
+pub const DEFAULT_MAX_DECODE_DEPTH: usize = 128;
+/// The default for `--max-decode-nodes`.
+pub const DEFAULT_MAX_DECODE_NODES: usize = 10_000_000;
+
+/// How large any single value (eg a storage value or a call argument) can get while decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// The most memory (in bytes) that the decoded value can use.
+    pub max_memory: usize,
+    /// How deeply the decoded value can nest values inside other values.
+    pub max_depth: usize,
+    /// The most values (including every field, item and so on) that the decoded value can have.
+    pub max_nodes: usize,
+}
+
+impl DecodeLimits {
+    pub const DEFAULT: DecodeLimits = DecodeLimits {
+        max_memory: DEFAULT_MAX_DECODE_MEMORY_MIB << 20,
+        max_depth: DEFAULT_MAX_DECODE_DEPTH,
+        max_nodes: DEFAULT_MAX_DECODE_NODES,
+    };
+}
+
+/// Why a value was abandoned part way through decoding. Errors are tagged with one of these
+/// so that they can be reported apart from values which just don't decode.
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "limit", rename_all = "snake_case")]
+pub enum LimitExceeded {
+    /// Decoding would need more memory than `--max-decode-memory` allows.
+    Memory { needed_bytes: u64, limit_bytes: u64 },
+    /// Values were nested more deeply than `--max-decode-depth` allows.
+    Depth { limit_depth: u64 },
+    /// There were more values than `--max-decode-nodes` allows.
+    Nodes { limit_nodes: u64 },
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::Memory {
+                needed_bytes,
+                limit_bytes,
+            } => write!(
+                f,
+                "Suspiciously large decode: at least {needed_bytes} bytes of memory are needed, \
+                 more than the {limit_bytes} allowed by --max-decode-memory; is the type wrong?"
+            ),
+            LimitExceeded::Depth { limit_depth } => write!(
+                f,
+                "Suspiciously deep decode: values are nested more than {limit_depth} deep, \
+                 beyond --max-decode-depth; does the type (wrongly) refer to itself?"
+            ),
+            LimitExceeded::Nodes { limit_nodes } => write!(
+                f,
+                "Suspiciously large decode: there are more than {limit_nodes} values, \
+                 beyond --max-decode-nodes; is the type wrong?"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Find the [`LimitExceeded`] that an error was caused by, if any.
+pub fn limit_exceeded_of(err: &anyhow::Error) -> Option<LimitExceeded> {
+    err.downcast_ref::<LimitExceeded>().copied()
+}
+
+/// Decode some bytes into a [`Value`], as [`scale_value::scale::decode_as_type`] does, but
+/// giving up with a [`LimitExceeded`] error if the value gets too large. With
+/// `--strict-encoding`, values which aren't canonically encoded fail with a
+/// [`canonical::NonCanonical`] error too.
+pub fn decode_value<Resolver>(
+    cursor: &mut &[u8],
+    type_id: Resolver::TypeId,
+    types: &Resolver,
+    opts: &DecodeOptions,
+) -> anyhow::Result<Value<String>>
+where
+    Resolver: TypeResolver,
+    Resolver::TypeId: Clone + std::fmt::Display,
+{
+    let bytes = *cursor;
+    let mut value = decode_value_within(cursor, type_id.clone(), types, opts.limits)?;
+    if opts.strict_encoding {
+        let decoded_bytes = &bytes[..bytes.len() - cursor.len()];
+        canonical::check_canonical(decoded_bytes, &value, type_id, types, opts)?;
+    }
+    addresses::format_account_ids(&mut value, types, opts);
+    Ok(named_types::name_types(value, types))
+}
+
+fn decode_value_within<Resolver>(
+    cursor: &mut &[u8],
+    type_id: Resolver::TypeId,
+    types: &Resolver,
+    limits: DecodeLimits,
+) -> anyhow::Result<Value<Resolver::TypeId>>
+where
+    Resolver: TypeResolver,
+    Resolver::TypeId: Clone + std::fmt::Display,
+{
+    let budget = Budget {
+        limits,
+        used_memory: Cell::new(0),
+        used_nodes: Cell::new(0),
+    };
+    let visitor = LimitedVisitor {
+        budget: &budget,
+        depth: 0,
+        marker: PhantomData,
+    };
+    match scale_decode::visitor::decode_with_visitor(cursor, type_id, types, visitor) {
+        Ok(value) => Ok(value),
+        Err(Error::Decode(e)) => Err(e.into()),
+        Err(Error::LimitExceeded(e)) => Err(e.into()),
+    }
+}
+
+/// Roughly the memory that each decoded value takes up, not counting any strings or bits in it.
+const NODE_SIZE: usize = std::mem::size_of::<Value<String>>();
+
+struct Budget {
+    limits: DecodeLimits,
+    used_memory: Cell<usize>,
+    used_nodes: Cell<usize>,
+}
+
+impl Budget {
+    /// Note that a value is about to be decoded at the given depth, failing if that goes
+    /// beyond any of our limits.
+    fn enter(&self, depth: usize) -> Result<(), LimitExceeded> {
+        if depth > self.limits.max_depth {
+            return Err(LimitExceeded::Depth {
+                limit_depth: self.limits.max_depth as u64,
+            });
+        }
+        let nodes = self.used_nodes.get() + 1;
+        if nodes > self.limits.max_nodes {
+            return Err(LimitExceeded::Nodes {
+                limit_nodes: self.limits.max_nodes as u64,
+            });
+        }
+        self.used_nodes.set(nodes);
+        self.spend(NODE_SIZE)
+    }
+
+    /// Note that some more memory is needed, failing if that's more than we're allowed. This
+    /// is called before allocating it.
+    fn spend(&self, bytes: usize) -> Result<(), LimitExceeded> {
+        let used = self.used_memory.get().saturating_add(bytes);
+        if used > self.limits.max_memory {
+            return Err(LimitExceeded::Memory {
+                needed_bytes: used as u64,
+                limit_bytes: self.limits.max_memory as u64,
+            });
+        }
+        self.used_memory.set(used);
+        Ok(())
+    }
+}
+
+enum Error {
+    Decode(DecodeError),
+    LimitExceeded(LimitExceeded),
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(e: LimitExceeded) -> Self {
+        Error::LimitExceeded(e)
+    }
+}
+
+/// Decodes values like [`scale_value::scale::ValueVisitor`], but keeping track of how large
+/// they get in a [`Budget`].
+struct LimitedVisitor<'budget, R> {
+    budget: &'budget Budget,
+    /// How many values the value being decoded is inside of.
+    depth: usize,
+    marker: PhantomData<R>,
+}
+
+impl<R> Clone for LimitedVisitor<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<R> Copy for LimitedVisitor<'_, R> {}
+
+// Sequences, tuples and arrays all decode into unnamed composites in the same way.
+macro_rules! visit_unnamed {
+    ($self:ident, $value:ident, $type_id:ident) => {{
+        let mut vals =
+            Vec::with_capacity(capacity($value.remaining(), $value.bytes_from_undecoded()));
+        while let Some(val) = $value.decode_item($self.inner()) {
+            vals.push(val?);
+        }
+        Ok(Value {
+            value: ValueDef::Composite(scale_value::Composite::Unnamed(vals)),
+            context: $type_id,
+        })
+    }};
+}
+
+/// How many items to make room for up front when decoding the given number of them from
+/// some bytes. The length prefixes of sequences can't be trusted when the type is wrong, but
+/// there can't be more (non-empty) items than bytes left.
+fn capacity(len: usize, bytes: &[u8]) -> usize {
+    len.min(bytes.len())
+}
+
+impl<'budget, R> LimitedVisitor<'budget, R>
+where
+    R: TypeResolver,
+    R::TypeId: Clone,
+{
+    /// A visitor for the values inside of the one being decoded.
+    fn inner(self) -> Self {
+        LimitedVisitor {
+            depth: self.depth + 1,
+            ..self
+        }
+    }
+
+    fn primitive(self, value: Primitive, type_id: R::TypeId) -> Result<Value<R::TypeId>, Error> {
+        Ok(Value {
+            value: ValueDef::Primitive(value),
+            context: type_id,
+        })
+    }
+
+    fn composite(
+        self,
+        value: &mut Composite<'_, '_, R>,
+    ) -> Result<scale_value::Composite<R::TypeId>, Error> {
+        // If there are no fields, we'll always assume unnamed (as scale_value does).
+        let len = value.remaining();
+        if len == 0 || value.has_unnamed_fields() {
+            let mut vals = Vec::with_capacity(len);
+            while let Some(val) = value.decode_item(self.inner()) {
+                vals.push(val?);
+            }
+            return Ok(scale_value::Composite::Unnamed(vals));
+        }
+
+        let mut vals = Vec::with_capacity(len);
+        while let Some(name) = value.peek_name() {
+            let Some(val) = value.decode_item(self.inner()) else {
+                break;
+            };
+            vals.push((name.to_owned(), val?));
+        }
+        Ok(scale_value::Composite::Named(vals))
+    }
+}
+
+impl<'budget, R> Visitor for LimitedVisitor<'budget, R>
+where
+    R: TypeResolver,
+    R::TypeId: Clone,
+{
+    type Value<'scale, 'resolver> = Value<R::TypeId>;
+    type Error = Error;
+    type TypeResolver = R;
+
+    // Limits are checked before anything is decoded, because scale_decode skips over anything
+    // that a visitor doesn't decode after it returns, and that could go on forever too.
+    fn unchecked_decode_as_type<'scale, 'resolver>(
+        self,
+        _input: &mut &'scale [u8],
+        _type_id: R::TypeId,
+        _types: &'resolver R,
+    ) -> DecodeAsTypeResult<Self, Result<Self::Value<'scale, 'resolver>, Self::Error>> {
+        match self.budget.enter(self.depth) {
+            Ok(()) => DecodeAsTypeResult::Skipped(self),
+            Err(e) => DecodeAsTypeResult::Decoded(Err(e.into())),
+        }
+    }
+
+    fn visit_bool<'scale, 'resolver>(
+        self,
+        value: bool,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::Bool(value), type_id)
+    }
+    fn visit_char<'scale, 'resolver>(
+        self,
+        value: char,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::Char(value), type_id)
+    }
+    fn visit_u8<'scale, 'resolver>(
+        self,
+        value: u8,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::U128(value.into()), type_id)
+    }
+    fn visit_u16<'scale, 'resolver>(
+        self,
+        value: u16,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::U128(value.into()), type_id)
+    }
+    fn visit_u32<'scale, 'resolver>(
+        self,
+        value: u32,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::U128(value.into()), type_id)
+    }
+    fn visit_u64<'scale, 'resolver>(
+        self,
+        value: u64,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::U128(value.into()), type_id)
+    }
+    fn visit_u128<'scale, 'resolver>(
+        self,
+        value: u128,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::U128(value), type_id)
+    }
+    fn visit_u256<'resolver>(
+        self,
+        value: &[u8; 32],
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'_, 'resolver>, Self::Error> {
+        self.primitive(Primitive::U256(*value), type_id)
+    }
+    fn visit_i8<'scale, 'resolver>(
+        self,
+        value: i8,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::I128(value.into()), type_id)
+    }
+    fn visit_i16<'scale, 'resolver>(
+        self,
+        value: i16,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::I128(value.into()), type_id)
+    }
+    fn visit_i32<'scale, 'resolver>(
+        self,
+        value: i32,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::I128(value.into()), type_id)
+    }
+    fn visit_i64<'scale, 'resolver>(
+        self,
+        value: i64,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::I128(value.into()), type_id)
+    }
+    fn visit_i128<'scale, 'resolver>(
+        self,
+        value: i128,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.primitive(Primitive::I128(value), type_id)
+    }
+    fn visit_i256<'resolver>(
+        self,
+        value: &[u8; 32],
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'_, 'resolver>, Self::Error> {
+        self.primitive(Primitive::I256(*value), type_id)
+    }
+    fn visit_sequence<'scale, 'resolver>(
+        self,
+        value: &mut Sequence<'scale, 'resolver, R>,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        visit_unnamed!(self, value, type_id)
+    }
+    fn visit_tuple<'scale, 'resolver>(
+        self,
+        value: &mut Tuple<'scale, 'resolver, R>,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        visit_unnamed!(self, value, type_id)
+    }
+    fn visit_array<'scale, 'resolver>(
+        self,
+        value: &mut Array<'scale, 'resolver, R>,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        visit_unnamed!(self, value, type_id)
+    }
+    fn visit_bitsequence<'scale, 'resolver>(
+        self,
+        value: &mut BitSequence<'scale>,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        let bits = value.decode()?;
+        self.budget.spend(bits.len() / 8)?;
+        let bits = bits.collect::<Result<_, _>>().map_err(DecodeError::from)?;
+        Ok(Value {
+            value: ValueDef::BitSequence(bits),
+            context: type_id,
+        })
+    }
+    fn visit_str<'scale, 'resolver>(
+        self,
+        value: &mut Str<'scale>,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        self.budget.spend(value.len())?;
+        self.primitive(Primitive::String(value.as_str()?.to_owned()), type_id)
+    }
+    fn visit_variant<'scale, 'resolver>(
+        self,
+        value: &mut Variant<'scale, 'resolver, R>,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        let values = self.composite(value.fields())?;
+        Ok(Value {
+            value: ValueDef::Variant(scale_value::Variant {
+                name: value.name().to_owned(),
+                values,
+            }),
+            context: type_id,
+        })
+    }
+    fn visit_composite<'scale, 'resolver>(
+        self,
+        value: &mut Composite<'scale, 'resolver, R>,
+        type_id: R::TypeId,
+    ) -> Result<Self::Value<'scale, 'resolver>, Self::Error> {
+        Ok(Value {
+            value: ValueDef::Composite(self.composite(value)?),
+            context: type_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parity_scale_codec::{Compact, Encode};
+    use scale_info_legacy::{ChainTypeRegistry, LookupName};
+
+    #[test]
+    fn stops_decoding_huge_values() {
+        let types: ChainTypeRegistry = serde_yaml::from_str(
+            r#"
+            global:
+              types:
+                Empty: ()
+                Loop:
+                  next: Loop
+                Slash:
+                  validator: u32
+                  amount: u64
+            "#,
+        )
+        .unwrap();
+        let types = types.for_spec_version(0);
+        let decode_within = |bytes: &[u8], ty: &str, limits| {
+            decode_value_within(&mut &*bytes, LookupName::parse(ty).unwrap(), &types, limits)
+                .map(|value| value.map_context(|id| id.to_string()))
+        };
+        let decode = |bytes: &[u8], ty: &str| {
+            let limits = DecodeLimits {
+                max_memory: 1 << 20,
+                ..DecodeLimits::DEFAULT
+            };
+            decode_within(bytes, ty, limits)
+        };
+
+        // Values of a normal size decode as scale_value would decode them.
+        let slashes = vec![(7u32, 100u64), (8, 200)].encode();
+        let expected = scale_value::scale::decode_as_type(
+            &mut &*slashes,
+            LookupName::parse("Vec<Slash>").unwrap(),
+            &types,
+        )
+        .unwrap()
+        .map_context(|id| id.to_string());
+        assert_eq!(decode(&slashes, "Vec<Slash>").unwrap(), expected);
+
+        // A few bytes of empty values can be enormous once decoded.
+        let huge = Compact(1_000_000u32).encode();
+        let err = decode(&huge, "Vec<Empty>").unwrap_err();
+        assert!(matches!(
+            limit_exceeded_of(&err),
+            Some(LimitExceeded::Memory { .. })
+        ));
+
+        // Lengths that there aren't enough bytes for are just decode errors.
+        let err = decode(&huge, "Vec<u8>").unwrap_err();
+        assert_eq!(limit_exceeded_of(&err), None);
+
+        // Types which refer to themselves can be decoded forever.
+        let limits = DecodeLimits {
+            max_depth: 16,
+            ..DecodeLimits::DEFAULT
+        };
+        let err = decode_within(&[], "Loop", limits).unwrap_err();
+        assert_eq!(
+            limit_exceeded_of(&err),
+            Some(LimitExceeded::Depth { limit_depth: 16 })
+        );
+
+        let limits = DecodeLimits {
+            max_nodes: 4,
+            ..DecodeLimits::DEFAULT
+        };
+        let slash = vec![(7u32, 100u64)].encode();
+        assert!(decode_within(&slash, "Vec<Slash>", limits).is_ok());
+        let err = decode_within(&slashes, "Vec<Slash>", limits).unwrap_err();
+        assert_eq!(
+            limit_exceeded_of(&err),
+            Some(LimitExceeded::Nodes { limit_nodes: 4 })
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! The options that decide how things are decoded and written out, as given on the command line
+//! (eg `--lenient` or `--address-format`). These are handed to everything that decodes, either
+//! in the [`DecodingContext`](super::decoding_context::DecodingContext) or alongside it.
+
+use super::addresses::{AddressFormat, DEFAULT_SS58_PREFIX};
+use super::decode_limits::DecodeLimits;
+use frame_metadata::RuntimeMetadata;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    /// How large any single value can get while decoding.
+    pub limits: DecodeLimits,
+    /// Whether values which decode but aren't canonically encoded fail to decode.
+    pub strict_encoding: bool,
+    /// Whether call arguments which fail to decode are given back as hex rather than failing
+    /// the whole extrinsic.
+    pub lenient: bool,
+    /// How account IDs are written out. See [`addresses`](super::addresses).
+    pub address_format: Option<AddressFormat>,
+    /// The SS58 prefix of the chain, for writing SS58 addresses.
+    pub ss58_prefix: u16,
+    /// Whether bit sequences are always written out in full.
+    pub expand_bits: bool,
+    /// The spec version to pick types from the types file for, whatever the spec version of
+    /// the block being decoded. This is for deliberately decoding with mismatched types.
+    pub pinned_spec_version: Option<u32>,
+    /// The metadata to decode everything with, whatever the block being decoded.
+    pub pinned_metadata: Option<Arc<RuntimeMetadata>>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            limits: DecodeLimits::DEFAULT,
+            strict_encoding: false,
+            lenient: false,
+            address_format: None,
+            ss58_prefix: DEFAULT_SS58_PREFIX,
+            expand_bits: false,
+            pinned_spec_version: None,
+            pinned_metadata: None,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// The spec version to pick types from the types file for, when decoding something at the
+    /// given spec version. This is the given spec version unless one has been pinned.
+    pub fn types_spec_version(&self, spec_version: u32) -> u32 {
+        self.pinned_spec_version.unwrap_or(spec_version)
+    }
+}
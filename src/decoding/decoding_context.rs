@@ -0,0 +1,349 @@
+//! Preparing everything that we need to decode blocks and storage at some spec version.
+
+use super::builtin_types::{builtin_types_from_metadata, extend_with_metadata_info};
+use super::decode_options::DecodeOptions;
+use crate::utils::profile::{self, Stage};
+use anyhow::{bail, Context};
+use frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed, META_RESERVED};
+use lru::LruCache;
+use parity_scale_codec::Decode;
+use scale_info_legacy::{ChainTypeRegistry, TypeRegistrySet};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// How many decoding contexts a [`DecodingContextCache`] keeps around.
+const CACHE_SIZE: NonZeroUsize = match NonZeroUsize::new(16) {
+    Some(size) => size,
+    None => unreachable!(),
+};
+
+/// The newest version of metadata that we can decode.
+pub const MAX_METADATA_VERSION: u8 = 16;
+
+/// The metadata, types and options to decode things at some spec version with.
+pub struct DecodingContext {
+    /// The spec version of the blocks that this context decodes things at. If the spec version
+    /// has been pinned, the types are those for [`DecodeOptions::types_spec_version`] instead.
+    pub spec_version: u32,
+    pub metadata: Arc<RuntimeMetadata>,
+    /// The historic types for this spec version, along with the types that we derive from
+    /// the metadata (eg `builtin::Call`).
+    pub types: TypeRegistrySet<'static>,
+    /// The options to decode things with.
+    pub options: Arc<DecodeOptions>,
+}
+
+/// Prepare the context to decode things at some spec version with, given the metadata at
+/// that spec version and the historic types for the chain. Any pinned metadata in the options
+/// isn't used here; callers should pass that as the metadata themselves.
+///
+/// Building the types is expensive, so anything decoding more than one thing at a time should
+/// keep the contexts it prepares in a [`DecodingContextCache`] rather than calling this each
+/// time. Use [`types_for_spec_version`] instead to compare different historic types.
+pub fn prepare_decoding_context(
+    spec_version: u32,
+    metadata: impl Into<Arc<RuntimeMetadata>>,
+    historic_types: &ChainTypeRegistry,
+    options: Arc<DecodeOptions>,
+) -> anyhow::Result<DecodingContext> {
+    // This is what `types_for_spec_version` does, but timing each step for `--profile`.
+    let metadata = metadata.into();
+    let started = Instant::now();
+    let builtin_types = builtin_types_from_metadata(&metadata)?;
+    profile::record(
+        spec_version,
+        Stage::TypeRegistryFromMetadata,
+        started.elapsed(),
+    );
+
+    let started = Instant::now();
+    let mut types = historic_types
+        .for_spec_version(options.types_spec_version(spec_version) as u64)
+        .to_owned();
+    types.prepend(builtin_types);
+    profile::record(spec_version, Stage::RegistryMerging, started.elapsed());
+
+    Ok(DecodingContext {
+        spec_version,
+        metadata,
+        types,
+        options,
+    })
+}
+
+/// The decoding contexts that have been prepared so far, to share between every task that's
+/// decoding things. Contexts are keyed by the historic types that they were prepared with as
+/// well as by spec version, so a cache can be shared between chains or types files. Every
+/// context is prepared with the options that the cache was created with.
+pub struct DecodingContextCache {
+    options: Arc<DecodeOptions>,
+    contexts: Mutex<LruCache<CacheKey, Arc<DecodingContext>>>,
+    /// A lock for each context that's being prepared, so that only one task fetches the
+    /// metadata for it at a time.
+    preparing: Mutex<HashMap<CacheKey, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// Historic types are compared by address, so the key holds on to them to make sure that
+/// the address isn't reused by different types while the key is in the cache.
+#[derive(Clone)]
+struct CacheKey {
+    historic_types: Arc<ChainTypeRegistry>,
+    spec_version: u32,
+}
+
+impl PartialEq for CacheKey {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.historic_types, &other.historic_types)
+            && self.spec_version == other.spec_version
+    }
+}
+
+impl Eq for CacheKey {}
+
+impl Hash for CacheKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.historic_types).hash(state);
+        self.spec_version.hash(state);
+    }
+}
+
+impl DecodingContextCache {
+    pub fn new(options: Arc<DecodeOptions>) -> Self {
+        DecodingContextCache {
+            options,
+            contexts: Mutex::new(LruCache::new(CACHE_SIZE)),
+            preparing: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The options that contexts are prepared with.
+    pub fn options(&self) -> &Arc<DecodeOptions> {
+        &self.options
+    }
+
+    /// The context that's already been prepared for some spec version and historic types, if
+    /// any. If there is one, we don't need to fetch the metadata to prepare it again.
+    pub fn get(
+        &self,
+        spec_version: u32,
+        historic_types: &Arc<ChainTypeRegistry>,
+    ) -> Option<Arc<DecodingContext>> {
+        let key = CacheKey {
+            historic_types: historic_types.clone(),
+            spec_version,
+        };
+        self.contexts.lock().unwrap().get(&key).cloned()
+    }
+
+    /// The context to decode things at some spec version with, preparing it with the given
+    /// metadata (see [`prepare_decoding_context`]) if it's not been prepared already.
+    pub fn prepare(
+        &self,
+        spec_version: u32,
+        metadata: impl Into<Arc<RuntimeMetadata>>,
+        historic_types: &Arc<ChainTypeRegistry>,
+    ) -> anyhow::Result<Arc<DecodingContext>> {
+        if let Some(context) = self.get(spec_version, historic_types) {
+            return Ok(context);
+        }
+        // Other tasks may prepare the same context meanwhile, but that's no worse than not caching.
+        let context = Arc::new(prepare_decoding_context(
+            spec_version,
+            metadata,
+            historic_types,
+            self.options.clone(),
+        )?);
+        let key = CacheKey {
+            historic_types: historic_types.clone(),
+            spec_version,
+        };
+        self.contexts.lock().unwrap().put(key, context.clone());
+        Ok(context)
+    }
+
+    /// The context to decode things at some spec version with, preparing it with the metadata
+    /// that `fetch_metadata` fetches if it's not been prepared already.
+    ///
+    /// When several tasks reach a new spec version at once (eg at a runtime upgrade), only one
+    /// of them fetches the metadata and prepares the context, and the rest wait to share it. If
+    /// that fails, the next task to have been waiting tries for itself.
+    pub async fn get_or_prepare<F, Fut, M>(
+        &self,
+        spec_version: u32,
+        historic_types: &Arc<ChainTypeRegistry>,
+        fetch_metadata: F,
+    ) -> anyhow::Result<Arc<DecodingContext>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<M>>,
+        M: Into<Arc<RuntimeMetadata>>,
+    {
+        if let Some(context) = self.get(spec_version, historic_types) {
+            return Ok(context);
+        }
+
+        let key = CacheKey {
+            historic_types: historic_types.clone(),
+            spec_version,
+        };
+        let lock = self
+            .preparing
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let res = async {
+            let _preparing = lock.lock().await;
+            if let Some(context) = self.get(spec_version, historic_types) {
+                return Ok(context);
+            }
+            let metadata = fetch_metadata().await?;
+            self.prepare(spec_version, metadata, historic_types)
+        }
+        .await;
+
+        // Forget the lock once nobody else is waiting on it, rather than keeping one (and the
+        // historic types in its key) for every spec version that we've ever prepared.
+        let mut preparing = self.preparing.lock().unwrap();
+        if Arc::strong_count(&lock) == 2 {
+            preparing.remove(&key);
+        }
+        res
+    }
+}
+
+/// The historic types for some spec version, extended with the types that we derive from the
+/// metadata so that things like `Utility.batch` (which take `builtin::Call`s) "Just Work".
+pub fn types_for_spec_version(
+    spec_version: u32,
+    metadata: &RuntimeMetadata,
+    historic_types: &ChainTypeRegistry,
+) -> anyhow::Result<TypeRegistrySet<'static>> {
+    let mut types = historic_types
+        .for_spec_version(spec_version as u64)
+        .to_owned();
+    extend_with_metadata_info(&mut types, metadata)?;
+    Ok(types)
+}
+
+/// Decode some metadata, with or without the "meta" magic number prefix that
+/// `state_getMetadata` returns. Metadata newer than [`MAX_METADATA_VERSION`] is rejected with
+/// an error saying so, rather than failing to decode in some less obvious way.
+pub fn decode_metadata(bytes: &[u8]) -> anyhow::Result<RuntimeMetadata> {
+    let magic = META_RESERVED.to_le_bytes();
+    let prefixed = bytes.starts_with(&magic);
+    let version = if prefixed {
+        bytes.get(magic.len())
+    } else {
+        bytes.first()
+    };
+    if let Some(&version) = version.filter(|v| **v > MAX_METADATA_VERSION) {
+        bail!("Metadata V{version} is not supported; only metadata V8 - V{MAX_METADATA_VERSION} can be decoded");
+    }
+
+    let metadata = if prefixed {
+        RuntimeMetadataPrefixed::decode(&mut &*bytes)
+            .with_context(|| "Could not decode metadata")?
+            .1
+    } else {
+        RuntimeMetadata::decode(&mut &*bytes).with_context(|| "Could not decode metadata")?
+    };
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use frame_metadata::decode_different::DecodeDifferent;
+    use frame_metadata::v8::RuntimeMetadataV8;
+    use parity_scale_codec::Encode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn empty_metadata() -> RuntimeMetadata {
+        RuntimeMetadata::V8(RuntimeMetadataV8 {
+            modules: DecodeDifferent::Decoded(vec![]),
+        })
+    }
+
+    fn empty_types() -> Arc<ChainTypeRegistry> {
+        Arc::new(serde_yaml::from_str("global:\n  types: {}\n").unwrap())
+    }
+
+    #[tokio::test]
+    async fn fetches_metadata_once_per_spec_version() {
+        let cache = DecodingContextCache::new(Default::default());
+        let historic_types = empty_types();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Ok(empty_metadata())
+        };
+
+        let prepare = || cache.get_or_prepare(1, &historic_types, fetch);
+        let (a, b, c) = tokio::join!(prepare(), prepare(), prepare());
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+        assert!(Arc::ptr_eq(&a, &b) && Arc::ptr_eq(&a, &c));
+    }
+
+    #[tokio::test]
+    async fn forgets_locks_once_prepared() {
+        let cache = DecodingContextCache::new(Default::default());
+        let historic_types = empty_types();
+
+        let fetch = || async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Ok(empty_metadata())
+        };
+        let prepare = |spec_version| cache.get_or_prepare(spec_version, &historic_types, fetch);
+        let (a, b) = tokio::join!(prepare(1), prepare(1));
+        a.unwrap();
+        b.unwrap();
+        assert!(cache.preparing.lock().unwrap().is_empty());
+
+        let fail =
+            || async { anyhow::Result::<RuntimeMetadata>::Err(anyhow::anyhow!("no metadata")) };
+        assert!(cache
+            .get_or_prepare(2, &historic_types, fail)
+            .await
+            .is_err());
+        assert!(cache.preparing.lock().unwrap().is_empty());
+        // Only the contexts themselves hold on to the historic types.
+        assert_eq!(Arc::strong_count(&historic_types), 2);
+    }
+
+    #[test]
+    fn caches_contexts_by_historic_types_and_spec_version() {
+        let cache = DecodingContextCache::new(Default::default());
+        let (types_a, types_b) = (empty_types(), empty_types());
+
+        let a = cache.prepare(1, empty_metadata(), &types_a).unwrap();
+        assert!(Arc::ptr_eq(&a, &cache.get(1, &types_a).unwrap()));
+        assert!(cache.get(2, &types_a).is_none());
+        assert!(cache.get(1, &types_b).is_none());
+
+        let b = cache.prepare(1, empty_metadata(), &types_b).unwrap();
+        assert!(!Arc::ptr_eq(&a, &b));
+        assert!(Arc::ptr_eq(&a, &cache.get(1, &types_a).unwrap()));
+    }
+
+    #[test]
+    fn decodes_metadata_with_or_without_prefix() {
+        let prefixed = RuntimeMetadataPrefixed(META_RESERVED, empty_metadata()).encode();
+        assert_eq!(decode_metadata(&prefixed).unwrap().version(), 8);
+        assert_eq!(decode_metadata(&prefixed[4..]).unwrap().version(), 8);
+
+        let mut v17 = META_RESERVED.encode();
+        v17.extend([17, 0, 0]);
+        let err = decode_metadata(&v17).unwrap_err().to_string();
+        assert!(err.contains("Metadata V17 is not supported"), "{err}");
+        assert!(decode_metadata(&v17[4..]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Writing the Ethereum addresses and signatures used by the `Claims` pallet (for the DOT
+//! claims, and the vesting attached to some of them) in text output. Addresses are written as
+//! EIP-55 checksummed hex, eg `0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed`, and secp256k1
+//! signatures with their `r`, `s` and `v` components labelled. JSON output keeps the bytes.
+//!
+//! These are recognised by the type that their value was decoded as (see
+//! [`super::named_types`]).
+
+use super::named_types::base_name;
+use crate::utils::value_to_bytes;
+use scale_value::Value;
+
+/// The number of bytes in an Ethereum address.
+const ADDRESS_LEN: usize = 20;
+
+/// The number of bytes in a secp256k1 signature: `r` and `s`, followed by the recovery ID `v`.
+const SIGNATURE_LEN: usize = 65;
+
+/// Is this the type name of an Ethereum address or signature?
+pub fn is_ethereum_type(type_name: &str) -> bool {
+    matches!(base_name(type_name), "EthereumAddress" | "EcdsaSignature")
+}
+
+/// Could a value with this many bytes be an Ethereum address or signature?
+pub fn is_ethereum_len(len: usize) -> bool {
+    len == ADDRESS_LEN || len == SIGNATURE_LEN
+}
+
+/// A custom formatter (for `scale_value::stringify`) which writes Ethereum addresses as
+/// checksummed hex and signatures with their components labelled.
+pub fn format_ethereum<T: std::fmt::Display, W: std::fmt::Write>(
+    value: &Value<T>,
+    w: &mut W,
+) -> Option<std::fmt::Result> {
+    if !is_ethereum_type(&value.context.to_string()) {
+        return None;
+    }
+    let bytes = value_to_bytes(value)?;
+    match bytes.len() {
+        ADDRESS_LEN => Some(write!(w, "{}", checksummed_address(&bytes))),
+        SIGNATURE_LEN => Some(write!(
+            w,
+            "{{ r: 0x{}, s: 0x{}, v: {} }}",
+            hex::encode(&bytes[..32]),
+            hex::encode(&bytes[32..64]),
+            bytes[64]
+        )),
+        _ => None,
+    }
+}
+
+/// Write an Ethereum address as hex with the mixed case checksum described in EIP-55.
+fn checksummed_address(address: &[u8]) -> String {
+    let lower = hex::encode(address);
+    let hash = sp_crypto_hashing::keccak_256(lower.as_bytes());
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(idx, c)| {
+            let nibble = (hash[idx / 2] >> if idx % 2 == 0 { 4 } else { 0 }) & 0xf;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{checksummed}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decoding::decode_options::DecodeOptions;
+
+    #[test]
+    fn writes_addresses_and_signatures() {
+        // Examples from EIP-55.
+        for expected in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        ] {
+            let address = hex::decode(expected[2..].to_lowercase()).unwrap();
+            assert_eq!(checksummed_address(&address), expected);
+        }
+
+        let format = |bytes: Vec<u8>, ty: &str| {
+            let value = Value::unnamed_composite([Value::from_bytes(bytes)]);
+            let mut out = String::new();
+            format_ethereum(&value.map_context(|_| ty.to_owned()), &mut out)
+                .map(|res| res.map(|_| out))
+        };
+        let address = hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        assert_eq!(
+            format(address.clone(), "EthereumAddress"),
+            Some(Ok("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_owned()))
+        );
+        let mut signature = vec![1; 32];
+        signature.extend([2; 32]);
+        signature.push(27);
+        let r = "01".repeat(32);
+        let s = "02".repeat(32);
+        assert_eq!(
+            format(signature, "polkadot_runtime_common::claims::EcdsaSignature"),
+            Some(Ok(format!("{{ r: 0x{r}, s: 0x{s}, v: 27 }}")))
+        );
+        assert_eq!(format(address, "H160"), None);
+    }
+
+    #[test]
+    fn names_addresses_from_metadata() {
+        #[derive(scale_info::TypeInfo)]
+        #[allow(dead_code)]
+        struct EthereumAddress([u8; 20]);
+
+        let mut registry = scale_info::Registry::new();
+        let id = registry
+            .register_type(&scale_info::meta_type::<EthereumAddress>())
+            .id;
+        let types: scale_info::PortableRegistry = registry.into();
+
+        let bytes = hex::decode("fb6916095ca1df60bb79ce92ce3ea74c37c5d359").unwrap();
+        let value = crate::decoding::decode_limits::decode_value(
+            &mut &*bytes,
+            id,
+            &types,
+            &DecodeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(value.context, "EthereumAddress");
+        let mut out = String::new();
+        crate::utils::write_value_fmt(&mut out, &value, &DecodeOptions::default()).unwrap();
+        assert_eq!(
+            out,
+            "<EthereumAddress> 0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! The events emitted in a block, as found in `System.Events` at it. This is a list of
+//! `EventRecord`s, each giving the phase of the block that the event was emitted in, the event
+//! and any topics. Before V14 metadata, the types of these come from the types that we derive
+//! from the metadata (see [`super::builtin_types`]).
+
+use super::decode_options::DecodeOptions;
+use super::storage_decoder;
+use anyhow::anyhow;
+use frame_metadata::RuntimeMetadata;
+use scale_info_legacy::TypeRegistrySet;
+use scale_value::{Composite, Value, ValueDef};
+use std::collections::BTreeMap;
+
+/// A single decoded event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// When in the block the event was emitted, eg `ApplyExtrinsic(1)` or `Finalization`.
+    pub phase: Value<String>,
+    pub pallet: String,
+    pub name: String,
+    /// The event's fields, which are unnamed before V14 metadata.
+    pub fields: Composite<String>,
+    pub topics: Value<String>,
+}
+
+impl Event {
+    /// The phase that the event was emitted in, eg `ApplyExtrinsic 1` or `Finalization`.
+    pub fn phase_name(&self) -> String {
+        match self.extrinsic_index() {
+            Some(index) => format!("{} {index}", self.phase_kind()),
+            None => self.phase_kind().to_owned(),
+        }
+    }
+
+    /// The index of the extrinsic that emitted the event, if it was emitted while applying one.
+    pub fn extrinsic_index(&self) -> Option<usize> {
+        match &self.phase.value {
+            ValueDef::Variant(phase) if phase.name == "ApplyExtrinsic" => phase
+                .values
+                .values()
+                .next()
+                .and_then(Value::as_u128)
+                .map(|index| index as usize),
+            _ => None,
+        }
+    }
+
+    /// The kind of phase that the event was emitted in, without the index of any extrinsic,
+    /// eg `ApplyExtrinsic`.
+    pub fn phase_kind(&self) -> &str {
+        match &self.phase.value {
+            ValueDef::Variant(phase) => &phase.name,
+            _ => "Unknown",
+        }
+    }
+
+    /// The event's fields, named by their position if they're unnamed.
+    pub fn named_fields(&self) -> Vec<(String, &Value<String>)> {
+        match &self.fields {
+            Composite::Named(fields) => fields.iter().map(|(name, v)| (name.clone(), v)).collect(),
+            Composite::Unnamed(fields) => fields
+                .iter()
+                .enumerate()
+                .map(|(idx, v)| (idx.to_string(), v))
+                .collect(),
+        }
+    }
+}
+
+/// Whether each extrinsic in a block succeeded, by its index, according to the
+/// `System.ExtrinsicSuccess` or `System.ExtrinsicFailed` event that it emitted.
+pub fn extrinsic_outcomes(events: &[Event]) -> BTreeMap<usize, bool> {
+    events
+        .iter()
+        .filter(|event| event.pallet == "System")
+        .filter_map(|event| {
+            let success = match event.name.as_str() {
+                "ExtrinsicSuccess" => true,
+                "ExtrinsicFailed" => false,
+                _ => return None,
+            };
+            Some((event.extrinsic_index()?, success))
+        })
+        .collect()
+}
+
+/// The storage key of `System.Events`.
+pub fn events_storage_key(
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet,
+) -> anyhow::Result<Vec<u8>> {
+    storage_decoder::encode_storage_key("System", "Events", &[], metadata, historic_types)
+}
+
+/// Decode the events in the given `System.Events` bytes.
+pub fn decode_events(
+    bytes: &[u8],
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet,
+    opts: &DecodeOptions,
+) -> anyhow::Result<Vec<Event>> {
+    crate::utils::catch_panic(|| {
+        let records = storage_decoder::decode_storage_value(
+            "System",
+            "Events",
+            bytes,
+            metadata,
+            historic_types,
+            opts,
+        )?;
+        events_from_records(records)
+    })
+}
+
+fn events_from_records(records: Value<String>) -> anyhow::Result<Vec<Event>> {
+    let ValueDef::Composite(records) = records.value else {
+        return Err(anyhow!("System.Events should be a list of event records"));
+    };
+    records
+        .into_values()
+        .enumerate()
+        .map(|(index, record)| {
+            event_from_record(record)
+                .ok_or_else(|| anyhow!("Event record {index} isn't shaped like an EventRecord"))
+        })
+        .collect()
+}
+
+/// Events are a variant named after their pallet, which wraps a variant named after the event.
+fn event_from_record(record: Value<String>) -> Option<Event> {
+    let ValueDef::Composite(Composite::Named(fields)) = record.value else {
+        return None;
+    };
+    let (mut phase, mut event, mut topics) = (None, None, None);
+    for (name, value) in fields {
+        match name.as_str() {
+            "phase" => phase = Some(value),
+            "event" => event = Some(value),
+            "topics" => topics = Some(value),
+            _ => {}
+        }
+    }
+
+    let ValueDef::Variant(pallet) = event?.value else {
+        return None;
+    };
+    let [inner]: [Value<String>; 1] = pallet
+        .values
+        .into_values()
+        .collect::<Vec<_>>()
+        .try_into()
+        .ok()?;
+    let ValueDef::Variant(event) = inner.value else {
+        return None;
+    };
+    Some(Event {
+        phase: phase?,
+        pallet: pallet.name,
+        name: event.name,
+        fields: event.values,
+        // Topics were added alongside the phase, so every record that we decode has them.
+        topics: topics?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn with_ctx(value: Value<()>) -> Value<String> {
+        value.map_context(|_| String::new())
+    }
+
+    #[test]
+    fn splits_event_records() {
+        let record = Value::named_composite([
+            (
+                "phase",
+                Value::unnamed_variant("ApplyExtrinsic", [Value::u128(1)]),
+            ),
+            (
+                "event",
+                Value::unnamed_variant(
+                    "Balances",
+                    [Value::unnamed_variant(
+                        "Transfer",
+                        [Value::u128(1), Value::u128(2), Value::u128(100)],
+                    )],
+                ),
+            ),
+            ("topics", Value::unnamed_composite([])),
+        ]);
+        let events = events_from_records(with_ctx(Value::unnamed_composite([record]))).unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].pallet, "Balances");
+        assert_eq!(events[0].name, "Transfer");
+        assert_eq!(events[0].fields.len(), 3);
+        assert_eq!(events[0].phase_name(), "ApplyExtrinsic 1");
+        assert_eq!(events[0].phase_kind(), "ApplyExtrinsic");
+        assert_eq!(events[0].named_fields()[2].0, "2");
+
+        let outcome = |phase: Value<()>, name: &str| Event {
+            phase: with_ctx(phase),
+            pallet: "System".to_owned(),
+            name: name.to_owned(),
+            fields: Composite::Unnamed(vec![]),
+            topics: with_ctx(Value::unnamed_composite([])),
+        };
+        let apply = |index| Value::unnamed_variant("ApplyExtrinsic", [Value::u128(index)]);
+        let outcomes = extrinsic_outcomes(&[
+            outcome(apply(0), "ExtrinsicSuccess"),
+            outcome(apply(2), "ExtrinsicFailed"),
+            outcome(
+                Value::unnamed_variant("Finalization", []),
+                "ExtrinsicSuccess",
+            ),
+        ]);
+        assert_eq!(outcomes, BTreeMap::from([(0, true), (2, false)]));
+
+        let not_a_record = with_ctx(Value::unnamed_composite([Value::u128(1)]));
+        assert!(events_from_records(not_a_record).is_err());
+    }
+}
@@ -1,10 +1,20 @@
+use super::addresses::format_account_id;
+use super::decode_limits::decode_value;
+use super::decode_options::DecodeOptions;
+use crate::utils::error_category::ErrorCategory;
 use anyhow::bail;
+use frame_decode::extrinsics::{
+    ExtrinsicCallInfo, ExtrinsicDecodeError, ExtrinsicExtensionInfo, ExtrinsicInfoError,
+    ExtrinsicSignatureInfo, ExtrinsicTypeInfo,
+};
 use frame_metadata::RuntimeMetadata;
 use scale_info_legacy::TypeRegistrySet;
 use scale_type_resolver::TypeResolver;
-use subxt::utils::{to_hex, AccountId32};
+use schemars::JsonSchema;
+use serde::Serialize;
+use subxt::utils::to_hex;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Extrinsic {
     Unsigned {
         call_data: ExtrinsicCallData,
@@ -21,7 +31,135 @@ pub enum Extrinsic {
     },
 }
 
-#[derive(Debug)]
+impl Extrinsic {
+    /// The call that this extrinsic makes.
+    pub fn call_data(&self) -> &ExtrinsicCallData {
+        match self {
+            Extrinsic::Unsigned { call_data }
+            | Extrinsic::Signed { call_data, .. }
+            | Extrinsic::General { call_data, .. } => call_data,
+        }
+    }
+
+    /// The call that this extrinsic makes, to modify.
+    pub fn call_data_mut(&mut self) -> &mut ExtrinsicCallData {
+        match self {
+            Extrinsic::Unsigned { call_data }
+            | Extrinsic::Signed { call_data, .. }
+            | Extrinsic::General { call_data, .. } => call_data,
+        }
+    }
+
+    /// The blocks that this extrinsic was valid in, from its `CheckMortality` (or older
+    /// `CheckEra`) signed extension, given the number of the block that it was included in.
+    /// This is `None` for unsigned extrinsics or if there's no such signed extension.
+    pub fn mortality(&self, block_number: u64) -> Option<Mortality> {
+        let signed_exts = match self {
+            Extrinsic::Unsigned { .. } => return None,
+            Extrinsic::Signed { signed_exts, .. } | Extrinsic::General { signed_exts, .. } => {
+                signed_exts
+            }
+        };
+        let (_, era) = signed_exts
+            .iter()
+            .find(|(name, _)| name == "CheckMortality" || name == "CheckEra")?;
+        Mortality::from_era(era, block_number)
+    }
+}
+
+/// When a transaction was valid, resolved from its era against the block it was included in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mortality {
+    Immortal,
+    Mortal {
+        period: u64,
+        phase: u64,
+        /// The first block that the transaction was valid in.
+        birth: u64,
+        /// The first block that the transaction was no longer valid in.
+        death: u64,
+    },
+}
+
+impl Mortality {
+    /// An `Era` is an enum whose `MortalN(u8)` variants encode the period and phase across the
+    /// variant index and the `u8`. It's either given on its own, or wrapped in a struct like
+    /// `CheckMortality { era }` or `CheckMortality(era)`.
+    fn from_era(era: &scale_value::Value<String>, block_number: u64) -> Option<Mortality> {
+        use scale_value::{Primitive, ValueDef};
+
+        let mut era = era;
+        while let ValueDef::Composite(composite) = &era.value {
+            let mut values = composite.values();
+            era = match (values.next(), values.next()) {
+                (Some(inner), None) => inner,
+                _ => return None,
+            };
+        }
+        let ValueDef::Variant(variant) = &era.value else {
+            return None;
+        };
+        if variant.name == "Immortal" {
+            return Some(Mortality::Immortal);
+        }
+        let first: u64 = variant.name.strip_prefix("Mortal")?.parse().ok()?;
+        let second = match &variant.values.values().next()?.value {
+            ValueDef::Primitive(Primitive::U128(n)) => u64::try_from(*n).ok()?,
+            _ => return None,
+        };
+
+        // As in `sp_runtime::generic::Era`.
+        let encoded = first + (second << 8);
+        let period = 2 << (encoded % (1 << 4));
+        let quantize_factor = (period >> 12).max(1);
+        let phase = (encoded >> 4) * quantize_factor;
+        if period < 4 || phase >= period {
+            return None;
+        }
+        let birth = (block_number.max(phase) - phase) / period * period + phase;
+        Some(Mortality::Mortal {
+            period,
+            phase,
+            birth,
+            death: birth + period,
+        })
+    }
+}
+
+/// An extrinsic in a version of the extrinsic format that we can't decode, like the pre-v4
+/// formats found in some very early blocks. Errors are tagged with this so that they can be
+/// reported apart from extrinsics which fail to decode for other reasons.
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedVersion {
+    /// The version given by the extrinsic's first byte.
+    pub version: u8,
+}
+
+impl std::fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unsupported extrinsic version {}: only versions 4 and 5 can be decoded",
+            self.version
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+/// Find the [`UnsupportedVersion`] that an error was caused by, if any.
+pub fn unsupported_version_of(err: &anyhow::Error) -> Option<UnsupportedVersion> {
+    err.downcast_ref::<UnsupportedVersion>().copied()
+}
+
+fn extrinsic_error(err: ExtrinsicDecodeError) -> anyhow::Error {
+    match err {
+        ExtrinsicDecodeError::VersionNotSupported(version) => UnsupportedVersion { version }.into(),
+        err => err.into(),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ExtrinsicCallData {
     pub pallet_name: String,
     pub call_name: String,
@@ -32,18 +170,20 @@ pub fn decode_extrinsic(
     bytes: &[u8],
     metadata: &RuntimeMetadata,
     historic_types: &TypeRegistrySet,
+    opts: &DecodeOptions,
 ) -> anyhow::Result<Extrinsic> {
-    let ext = match metadata {
-        RuntimeMetadata::V8(m) => decode_extrinsic_inner(bytes, m, historic_types),
-        RuntimeMetadata::V9(m) => decode_extrinsic_inner(bytes, m, historic_types),
-        RuntimeMetadata::V10(m) => decode_extrinsic_inner(bytes, m, historic_types),
-        RuntimeMetadata::V11(m) => decode_extrinsic_inner(bytes, m, historic_types),
-        RuntimeMetadata::V12(m) => decode_extrinsic_inner(bytes, m, historic_types),
-        RuntimeMetadata::V13(m) => decode_extrinsic_inner(bytes, m, historic_types),
-        RuntimeMetadata::V14(m) => decode_extrinsic_inner(bytes, m, &m.types),
-        RuntimeMetadata::V15(m) => decode_extrinsic_inner(bytes, m, &m.types),
-        _ => bail!("Only metadata V8 - V15 is supported"),
-    }?;
+    let ext = crate::utils::catch_panic(|| match metadata {
+        RuntimeMetadata::V8(m) => decode_extrinsic_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V9(m) => decode_extrinsic_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V10(m) => decode_extrinsic_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V11(m) => decode_extrinsic_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V12(m) => decode_extrinsic_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V13(m) => decode_extrinsic_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V14(m) => decode_extrinsic_inner(bytes, m, &m.types, opts),
+        RuntimeMetadata::V15(m) => decode_extrinsic_inner(bytes, m, &m.types, opts),
+        RuntimeMetadata::V16(m) => decode_extrinsic_inner(bytes, m, &m.types, opts),
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    })?;
 
     Ok(ext)
 }
@@ -52,6 +192,7 @@ fn decode_extrinsic_inner<Info, Resolver>(
     bytes: &[u8],
     args_info: &Info,
     type_resolver: &Resolver,
+    opts: &DecodeOptions,
 ) -> anyhow::Result<Extrinsic>
 where
     Info: frame_decode::extrinsics::ExtrinsicTypeInfo,
@@ -59,23 +200,73 @@ where
     Resolver: TypeResolver<TypeId = Info::TypeId>,
 {
     let cursor = &mut &*bytes;
-    let extrinsic_info =
-        frame_decode::extrinsics::decode_extrinsic(cursor, args_info, type_resolver)?;
+    let (extrinsic_info, undecoded_arg) =
+        match frame_decode::extrinsics::decode_extrinsic(cursor, args_info, type_resolver) {
+            Ok(extrinsic_info) => (extrinsic_info.into_owned(), None),
+            // In lenient mode, decode the extrinsic again without the argument that failed
+            // (or any after it, since we don't know where they start), and hand back the rest
+            // of the bytes as that argument.
+            Err(ExtrinsicDecodeError::CannotDecodeCallData {
+                argument_name,
+                reason,
+                ..
+            }) if opts.lenient => {
+                *cursor = bytes;
+                let args_before = ArgsBefore {
+                    info: args_info,
+                    arg_name: &argument_name,
+                };
+                let extrinsic_info = frame_decode::extrinsics::decode_extrinsic(
+                    cursor,
+                    &args_before,
+                    type_resolver,
+                )?
+                .into_owned();
+                let call_info = args_info
+                    .get_call_info(extrinsic_info.pallet_index(), extrinsic_info.call_index())
+                    .map_err(|e| ErrorCategory::TypeResolution.error(e))?;
+                let mut args = call_info
+                    .args
+                    .iter()
+                    .skip_while(|arg| arg.name != argument_name);
+                let type_id = args
+                    .next()
+                    .map(|arg| arg.id.to_string())
+                    .unwrap_or_default();
+                let later_args: Vec<_> = args.map(|arg| &*arg.name).collect();
+                let mut error = reason.to_string();
+                if !later_args.is_empty() {
+                    error.push_str(&format!(
+                        "; these bytes also contain the arguments after it ({}), since where it \
+                         ends isn't known",
+                        later_args.join(", ")
+                    ));
+                }
+                let undecoded = undecoded_value(std::mem::take(cursor), type_id, error);
+                (extrinsic_info, Some((argument_name, undecoded)))
+            }
+            Err(e) => return Err(extrinsic_error(e)),
+        };
 
-    // Decode each call data argument into a Value<String>
+    // Decode each call data argument into a Value<String>. In lenient mode, arguments which
+    // fail are given back as hex.
     let call_data = {
-        let args = extrinsic_info
+        let mut args = extrinsic_info
             .call_data()
             .map(|arg| {
-                let decoded_arg = scale_value::scale::decode_as_type(
-                    &mut &bytes[arg.range()],
-                    arg.ty().clone(),
-                    type_resolver,
-                )?
-                .map_context(|ctx| ctx.to_string());
+                let arg_bytes = &bytes[arg.range()];
+                let decoded_arg =
+                    match decode_value(&mut &*arg_bytes, arg.ty().clone(), type_resolver, opts) {
+                        Ok(decoded_arg) => decoded_arg,
+                        Err(e) if opts.lenient => {
+                            undecoded_value(arg_bytes, arg.ty().to_string(), format!("{e:#}"))
+                        }
+                        Err(e) => return Err(e),
+                    };
                 Ok((arg.name().to_owned(), decoded_arg))
             })
             .collect::<anyhow::Result<Vec<_>>>()?;
+        args.extend(undecoded_arg);
 
         ExtrinsicCallData {
             pallet_name: extrinsic_info.pallet_name().to_owned(),
@@ -89,7 +280,7 @@ where
         let address_bytes = &bytes[signature_info.address_range()];
         let address_string = address_bytes
             .try_into()
-            .map(|b| AccountId32(b).to_string())
+            .map(|b| format_account_id(&b, opts))
             .unwrap_or_else(|_e| format!("0x{}", hex::encode(address_bytes)));
 
         let signature_bytes = &bytes[signature_info.signature_range()];
@@ -104,12 +295,12 @@ where
         let signed_exts = exts
             .iter()
             .map(|signed_ext| {
-                let decoded_ext = scale_value::scale::decode_as_type(
+                let decoded_ext = decode_value(
                     &mut &bytes[signed_ext.range()],
                     signed_ext.ty().clone(),
                     type_resolver,
-                )?
-                .map_context(|ctx| ctx.to_string());
+                    opts,
+                )?;
                 Ok((signed_ext.name().to_owned(), decoded_ext))
             })
             .collect::<anyhow::Result<Vec<_>>>()?;
@@ -133,12 +324,12 @@ where
         )?;
         for (arg_name, arg_value) in call_data.args {
             write!(s, "  {arg_name}: ")?;
-            crate::utils::write_value_fmt(&mut s, &arg_value)?;
+            crate::utils::write_value_fmt(&mut s, &arg_value, opts)?;
             writeln!(s)?;
         }
 
         writeln!(s, "leftover bytes: 0x{}", hex::encode(cursor))?;
-        bail!("{s}");
+        return Err(ErrorCategory::LeftoverBytes.error(s));
     }
 
     match (signature, extensions) {
@@ -155,3 +346,308 @@ where
         _ => Ok(Extrinsic::Unsigned { call_data }),
     }
 }
+
+/// A call argument which couldn't be decoded: its bytes as hex, and why it failed. The value
+/// takes the name of the argument type as its context.
+fn undecoded_value(bytes: &[u8], type_id: String, error: String) -> scale_value::Value<String> {
+    let string = |s: String| scale_value::Value::string(s).map_context(|()| String::new());
+    scale_value::Value {
+        value: scale_value::ValueDef::Composite(scale_value::Composite::Named(vec![
+            ("undecoded".to_owned(), string(to_hex(bytes))),
+            ("error".to_owned(), string(error)),
+        ])),
+        context: type_id,
+    }
+}
+
+/// Extrinsic type information which leaves out the given call argument and any after it, so
+/// that we can find where the arguments before it are when it can't be decoded.
+struct ArgsBefore<'a, Info> {
+    info: &'a Info,
+    arg_name: &'a str,
+}
+
+impl<Info: ExtrinsicTypeInfo> ExtrinsicTypeInfo for ArgsBefore<'_, Info> {
+    type TypeId = Info::TypeId;
+
+    fn get_call_info(
+        &self,
+        pallet_index: u8,
+        call_index: u8,
+    ) -> Result<ExtrinsicCallInfo<'_, Self::TypeId>, ExtrinsicInfoError<'_>> {
+        let mut info = self.info.get_call_info(pallet_index, call_index)?;
+        if let Some(idx) = info.args.iter().position(|arg| arg.name == self.arg_name) {
+            info.args.truncate(idx);
+        }
+        Ok(info)
+    }
+
+    fn get_signature_info(
+        &self,
+    ) -> Result<ExtrinsicSignatureInfo<Self::TypeId>, ExtrinsicInfoError<'_>> {
+        self.info.get_signature_info()
+    }
+
+    fn get_extension_info(
+        &self,
+        extension_version: Option<u8>,
+    ) -> Result<ExtrinsicExtensionInfo<'_, Self::TypeId>, ExtrinsicInfoError<'_>> {
+        self.info.get_extension_info(extension_version)
+    }
+}
+
+/// A call found in an extrinsic, along with its hash (as used in eg multisig and proxy
+/// announcements to refer to calls).
+#[derive(Debug, Clone)]
+pub struct HashedCall {
+    pub hash: [u8; 32],
+    pub call: scale_value::Value<String>,
+}
+
+/// Return the call in the given extrinsic and any calls which are passed as arguments to it
+/// (eg the call given to `Multisig.as_multi`), along with their hashes.
+pub fn decode_extrinsic_calls(
+    bytes: &[u8],
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet,
+    opts: &DecodeOptions,
+) -> anyhow::Result<Vec<HashedCall>> {
+    crate::utils::catch_panic(|| match metadata {
+        RuntimeMetadata::V8(m) => decode_extrinsic_calls_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V9(m) => decode_extrinsic_calls_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V10(m) => decode_extrinsic_calls_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V11(m) => decode_extrinsic_calls_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V12(m) => decode_extrinsic_calls_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V13(m) => decode_extrinsic_calls_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V14(m) => decode_extrinsic_calls_inner(bytes, m, &m.types, opts),
+        RuntimeMetadata::V15(m) => decode_extrinsic_calls_inner(bytes, m, &m.types, opts),
+        RuntimeMetadata::V16(m) => decode_extrinsic_calls_inner(bytes, m, &m.types, opts),
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    })
+}
+
+fn decode_extrinsic_calls_inner<Info, Resolver>(
+    bytes: &[u8],
+    args_info: &Info,
+    type_resolver: &Resolver,
+    opts: &DecodeOptions,
+) -> anyhow::Result<Vec<HashedCall>>
+where
+    Info: frame_decode::extrinsics::ExtrinsicTypeInfo,
+    Info::TypeId: Clone + core::fmt::Display + core::fmt::Debug + Send + Sync + 'static,
+    Resolver: TypeResolver<TypeId = Info::TypeId>,
+{
+    use parity_scale_codec::{Compact, Decode};
+    use sp_crypto_hashing::blake2_256;
+
+    let extrinsic_info =
+        frame_decode::extrinsics::decode_extrinsic(&mut &*bytes, args_info, type_resolver)
+            .map_err(extrinsic_error)?;
+
+    let mut calls = vec![];
+    let mut args = vec![];
+    for arg in extrinsic_info.call_data() {
+        let arg_bytes = &bytes[arg.range()];
+        let decoded_arg = decode_value(&mut &*arg_bytes, arg.ty().clone(), type_resolver, opts)?;
+
+        // Calls passed as arguments are either encoded as-is, or (eg as an OpaqueCall)
+        // prefixed with their length. Calls are hashed without this prefix.
+        if arg.name() == "call" {
+            let cursor = &mut &*arg_bytes;
+            let call_bytes = match Compact::<u32>::decode(cursor) {
+                Ok(len) if len.0 as usize == cursor.len() => *cursor,
+                _ => arg_bytes,
+            };
+            calls.push(HashedCall {
+                hash: blake2_256(call_bytes),
+                call: decoded_arg.clone(),
+            });
+        }
+
+        args.push((arg.name().to_owned(), decoded_arg));
+    }
+
+    let call = variant_value(
+        extrinsic_info.pallet_name(),
+        scale_value::Composite::Unnamed(vec![variant_value(
+            extrinsic_info.call_name(),
+            scale_value::Composite::Named(args),
+        )]),
+    );
+    calls.insert(
+        0,
+        HashedCall {
+            hash: blake2_256(&bytes[extrinsic_info.call_data_range()]),
+            call,
+        },
+    );
+
+    Ok(calls)
+}
+
+/// Decode some call on its own (ie not as part of an extrinsic), as is stored in eg
+/// democracy preimages.
+pub fn decode_call(
+    bytes: &[u8],
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet,
+    opts: &DecodeOptions,
+) -> anyhow::Result<scale_value::Value<String>> {
+    crate::utils::catch_panic(|| match metadata {
+        RuntimeMetadata::V8(m) => decode_call_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V9(m) => decode_call_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V10(m) => decode_call_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V11(m) => decode_call_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V12(m) => decode_call_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V13(m) => decode_call_inner(bytes, m, historic_types, opts),
+        RuntimeMetadata::V14(m) => decode_call_inner(bytes, m, &m.types, opts),
+        RuntimeMetadata::V15(m) => decode_call_inner(bytes, m, &m.types, opts),
+        RuntimeMetadata::V16(m) => decode_call_inner(bytes, m, &m.types, opts),
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    })
+}
+
+fn decode_call_inner<Info, Resolver>(
+    bytes: &[u8],
+    args_info: &Info,
+    type_resolver: &Resolver,
+    opts: &DecodeOptions,
+) -> anyhow::Result<scale_value::Value<String>>
+where
+    Info: frame_decode::extrinsics::ExtrinsicTypeInfo,
+    Info::TypeId: Clone + core::fmt::Display + core::fmt::Debug + Send + Sync + 'static,
+    Resolver: TypeResolver<TypeId = Info::TypeId>,
+{
+    let [pallet_index, call_index, ..] = bytes else {
+        bail!("Not enough bytes to decode a call");
+    };
+    let call_info = args_info
+        .get_call_info(*pallet_index, *call_index)
+        .map_err(|e| ErrorCategory::TypeResolution.error(e))?;
+
+    let cursor = &mut &bytes[2..];
+    let mut args = vec![];
+    for arg in call_info.args {
+        let decoded_arg = decode_value(cursor, arg.id, type_resolver, opts)?;
+        args.push((arg.name.into_owned(), decoded_arg));
+    }
+    if !cursor.is_empty() {
+        return Err(ErrorCategory::LeftoverBytes.error(format!(
+            "{} bytes were left over after decoding the call",
+            cursor.len()
+        )));
+    }
+
+    Ok(variant_value(
+        &call_info.pallet_name,
+        scale_value::Composite::Unnamed(vec![variant_value(
+            &call_info.call_name,
+            scale_value::Composite::Named(args),
+        )]),
+    ))
+}
+
+fn variant_value(name: &str, values: scale_value::Composite<String>) -> scale_value::Value<String> {
+    scale_value::Value {
+        value: scale_value::ValueDef::Variant(scale_value::Variant {
+            name: name.to_owned(),
+            values,
+        }),
+        context: name.to_owned(),
+    }
+}
+
+/// The first line of the docs that the metadata gives for some call, if it has any.
+pub fn call_docs(pallet_name: &str, call_name: &str, metadata: &RuntimeMetadata) -> Option<String> {
+    use super::decoded;
+    use scale_info::TypeDef;
+
+    macro_rules! legacy_docs {
+        ($m:ident) => {
+            decoded(&$m.modules)
+                .iter()
+                .find(|module| decoded(&module.name) == pallet_name)
+                .and_then(|module| module.calls.as_ref())
+                .and_then(|calls| {
+                    decoded(calls)
+                        .iter()
+                        .find(|call| decoded(&call.name) == call_name)
+                })
+                .map(|call| decoded(&call.documentation).clone())
+        };
+    }
+    macro_rules! modern_docs {
+        ($m:ident) => {
+            $m.pallets
+                .iter()
+                .find(|pallet| pallet.name == pallet_name)
+                .and_then(|pallet| pallet.calls.as_ref())
+                .and_then(|calls| $m.types.resolve(calls.ty.id))
+                .and_then(|ty| match &ty.type_def {
+                    TypeDef::Variant(calls) => calls.variants.iter().find(|v| v.name == call_name),
+                    _ => None,
+                })
+                .map(|call| call.docs.clone())
+        };
+    }
+
+    let docs = match metadata {
+        RuntimeMetadata::V8(m) => legacy_docs!(m),
+        RuntimeMetadata::V9(m) => legacy_docs!(m),
+        RuntimeMetadata::V10(m) => legacy_docs!(m),
+        RuntimeMetadata::V11(m) => legacy_docs!(m),
+        RuntimeMetadata::V12(m) => legacy_docs!(m),
+        RuntimeMetadata::V13(m) => legacy_docs!(m),
+        RuntimeMetadata::V14(m) => modern_docs!(m),
+        RuntimeMetadata::V15(m) => modern_docs!(m),
+        RuntimeMetadata::V16(m) => modern_docs!(m),
+        _ => None,
+    }?;
+    docs.iter()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_value::Value;
+
+    #[test]
+    fn resolves_era_against_block_number() {
+        // Era::mortal(64, 1_000) encodes as [0x85, 0x02]: a period of 64 and a phase of 40.
+        let era = Value::named_composite([(
+            "era",
+            Value::unnamed_variant("Mortal133", [Value::u128(2)]),
+        )])
+        .map_context(|_| String::new());
+        assert_eq!(
+            Mortality::from_era(&era, 1_003),
+            Some(Mortality::Mortal {
+                period: 64,
+                phase: 40,
+                birth: 1_000,
+                death: 1_064,
+            })
+        );
+
+        let immortal = Value::unnamed_composite([Value::unnamed_variant("Immortal", [])])
+            .map_context(|_| String::new());
+        assert_eq!(
+            Mortality::from_era(&immortal, 1_003),
+            Some(Mortality::Immortal)
+        );
+    }
+
+    #[test]
+    fn reports_unsupported_versions() {
+        let err = extrinsic_error(ExtrinsicDecodeError::VersionNotSupported(3));
+        assert_eq!(
+            unsupported_version_of(&err),
+            Some(UnsupportedVersion { version: 3 })
+        );
+        let err = extrinsic_error(ExtrinsicDecodeError::NotEnoughBytes);
+        assert_eq!(unsupported_version_of(&err), None);
+    }
+}
@@ -0,0 +1,204 @@
+use super::storage_decoder::StorageKey;
+use scale_value::{At, Value, ValueDef};
+
+/// Where to find the call that some governance storage value refers to by hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProposalRef {
+    /// A council or technical committee motion, whose call is stored in `{pallet}.ProposalOf`.
+    Motion { pallet: String, hash: [u8; 32] },
+    /// A democracy proposal whose encoded call is stored as a preimage (in `Democracy.Preimages`
+    /// or later `Preimage.PreimageFor`), whose length we might know.
+    Preimage { hash: [u8; 32], len: Option<u32> },
+    /// A democracy proposal whose encoded call is small enough to be given inline.
+    Inline(Vec<u8>),
+}
+
+impl ProposalRef {
+    /// The hash of the proposed call.
+    pub fn hash(&self) -> [u8; 32] {
+        match self {
+            ProposalRef::Motion { hash, .. } | ProposalRef::Preimage { hash, .. } => *hash,
+            ProposalRef::Inline(bytes) => sp_crypto_hashing::blake2_256(bytes),
+        }
+    }
+}
+
+/// Does this storage entry refer to proposed calls by their hash?
+pub fn has_proposal_refs(pallet: &str, entry: &str) -> bool {
+    matches!(
+        (pallet, entry),
+        ("Council" | "TechnicalCommittee", "Proposals" | "Voting") | ("Democracy", "PublicProps")
+    )
+}
+
+/// Find the proposed calls that a decoded governance storage entry refers to. The calls
+/// themselves are stored at the same block as the entry.
+pub fn proposal_refs(
+    pallet: &str,
+    entry: &str,
+    key: &[StorageKey],
+    value: &Value<String>,
+) -> Vec<ProposalRef> {
+    let motion = |hash| ProposalRef::Motion {
+        pallet: pallet.to_owned(),
+        hash,
+    };
+    match (pallet, entry) {
+        // The hashes of the open motions, in order.
+        (_, "Proposals") => {
+            let mut hashes = Vec::new();
+            find_hashes(value, &mut hashes);
+            hashes.into_iter().map(motion).collect()
+        }
+        // Keyed by the hash of the motion being voted on.
+        (_, "Voting") => key
+            .first()
+            .and_then(|k| k.value.as_ref())
+            .and_then(value_to_hash)
+            .map(motion)
+            .into_iter()
+            .collect(),
+        // A list of (PropIndex, proposal, proposer). The proposal was a hash of a preimage
+        // until it became a `Bounded` call, which is either a hash or the call itself.
+        ("Democracy", "PublicProps") => {
+            let mut props = Vec::new();
+            find_tuples(value, &mut props);
+            props
+                .into_iter()
+                .filter_map(|prop| public_prop_ref(prop.at(1)?))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn public_prop_ref(proposal: &Value<String>) -> Option<ProposalRef> {
+    if let Some(hash) = value_to_hash(proposal) {
+        return Some(ProposalRef::Preimage { hash, len: None });
+    }
+    let ValueDef::Variant(variant) = &proposal.value else {
+        return None;
+    };
+    match variant.name.as_str() {
+        "Legacy" => Some(ProposalRef::Preimage {
+            hash: proposal.at("hash").and_then(value_to_hash)?,
+            len: None,
+        }),
+        "Lookup" => Some(ProposalRef::Preimage {
+            hash: proposal.at("hash").and_then(value_to_hash)?,
+            len: proposal
+                .at("len")
+                .and_then(|len| len.as_u128())
+                .and_then(|len| len.try_into().ok()),
+        }),
+        "Inline" => Some(ProposalRef::Inline(crate::utils::value_to_bytes(
+            proposal.at(0)?,
+        )?)),
+        _ => None,
+    }
+}
+
+/// Find every hash in some value (eg a `Vec<Hash>`, or a `BoundedVec` wrapping one).
+fn find_hashes(value: &Value<String>, out: &mut Vec<[u8; 32]>) {
+    if let Some(hash) = value_to_hash(value) {
+        out.push(hash);
+    } else if let ValueDef::Composite(composite) = &value.value {
+        for value in composite.values() {
+            find_hashes(value, out);
+        }
+    }
+}
+
+/// Find every tuple starting with a number in some value (eg a `Vec<(PropIndex, Hash, AccountId)>`,
+/// or a `BoundedVec` wrapping one).
+fn find_tuples<'a>(value: &'a Value<String>, out: &mut Vec<&'a Value<String>>) {
+    let ValueDef::Composite(composite) = &value.value else {
+        return;
+    };
+    if value.at(0).and_then(|first| first.as_u128()).is_some() {
+        out.push(value);
+    } else {
+        for value in composite.values() {
+            find_tuples(value, out);
+        }
+    }
+}
+
+fn value_to_hash(value: &Value<String>) -> Option<[u8; 32]> {
+    crate::utils::value_to_bytes(value)?.try_into().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn with_ctx(value: Value<()>) -> Value<String> {
+        value.map_context(|_| String::new())
+    }
+
+    fn hash_value(byte: u8) -> Value<()> {
+        Value::unnamed_composite([Value::unnamed_composite(
+            (0..32).map(|_| Value::u128(byte as u128)),
+        )])
+    }
+
+    #[test]
+    fn finds_public_prop_refs() {
+        let prop = |index, proposal| {
+            Value::unnamed_composite([Value::u128(index), proposal, Value::from_bytes([0; 32])])
+        };
+
+        // Before democracy proposals were `Bounded`, they were a preimage hash.
+        let value = with_ctx(Value::unnamed_composite([prop(0, hash_value(1))]));
+        assert_eq!(
+            proposal_refs("Democracy", "PublicProps", &[], &value),
+            vec![ProposalRef::Preimage {
+                hash: [1; 32],
+                len: None
+            }]
+        );
+
+        // Afterwards, they're stored in a BoundedVec and could be given inline.
+        let value = with_ctx(Value::unnamed_composite([Value::unnamed_composite([
+            prop(
+                1,
+                Value::named_variant(
+                    "Lookup",
+                    [("hash", hash_value(2)), ("len", Value::u128(100))],
+                ),
+            ),
+            prop(
+                2,
+                Value::unnamed_variant("Inline", [Value::from_bytes([0, 1])]),
+            ),
+        ])]));
+        assert_eq!(
+            proposal_refs("Democracy", "PublicProps", &[], &value),
+            vec![
+                ProposalRef::Preimage {
+                    hash: [2; 32],
+                    len: Some(100)
+                },
+                ProposalRef::Inline(vec![0, 1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_motion_refs() {
+        let value = with_ctx(Value::unnamed_composite([hash_value(1), hash_value(2)]));
+        assert_eq!(
+            proposal_refs("Council", "Proposals", &[], &value),
+            vec![
+                ProposalRef::Motion {
+                    pallet: "Council".to_owned(),
+                    hash: [1; 32]
+                },
+                ProposalRef::Motion {
+                    pallet: "Council".to_owned(),
+                    hash: [2; 32]
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use super::decode_options::DecodeOptions;
+use crate::utils::exit_code::ErrorKind;
+use crate::utils::output::{self, OutputFormat};
+use anyhow::Context;
+use scale_info_legacy::ChainTypeRegistry;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// The `forSpec` ranges in the types files that we've loaded, so that we can warn about spec
+/// versions which none of them have types for.
+static SPEC_RANGES: Mutex<Vec<SpecRange>> = Mutex::new(Vec::new());
+/// The spec versions that we've warned about already.
+static WARNED_SPEC_VERSIONS: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+/// The names given to the keys of storage entries in the types files that we've loaded, by
+/// pallet and entry name.
+static STORAGE_KEY_NAMES: Mutex<BTreeMap<(String, String), Vec<String>>> =
+    Mutex::new(BTreeMap::new());
+
+/// Load the historic types file, applying any pallet aliases that it contains.
+pub fn load_historic_types(path: &Path) -> anyhow::Result<ChainTypeRegistry> {
+    let historic_types = load_historic_types_yaml(path)?;
+    historic_types_from_yaml(historic_types)
+}
+
+/// Parse historic types given as a string rather than a file, as [`load_historic_types`] does.
+pub fn parse_historic_types(historic_types_str: &str) -> anyhow::Result<ChainTypeRegistry> {
+    let historic_types = parse_historic_types_yaml(historic_types_str)?;
+    historic_types_from_yaml(historic_types)
+}
+
+fn historic_types_from_yaml(historic_types: Value) -> anyhow::Result<ChainTypeRegistry> {
+    let historic_types = serde_yaml::from_value(historic_types)
+        .with_context(|| "Can't parse historic types from JSON")
+        .context(ErrorKind::Config)?;
+    Ok(historic_types)
+}
+
+/// Load the historic types file as YAML, applying any pallet aliases and spec version overrides
+/// that it contains. This is useful for looking at the individual entries in the file.
+pub fn load_historic_types_yaml(path: &Path) -> anyhow::Result<Value> {
+    let historic_types_str = std::fs::read_to_string(path)
+        .with_context(|| "Could not load historic types")
+        .context(ErrorKind::Config)?;
+    parse_historic_types_yaml(&historic_types_str)
+}
+
+fn parse_historic_types_yaml(historic_types_str: &str) -> anyhow::Result<Value> {
+    let mut historic_types: Value = serde_yaml::from_str(historic_types_str)
+        .with_context(|| "Can't parse historic types from JSON")
+        .context(ErrorKind::Config)?;
+    SPEC_RANGES
+        .lock()
+        .unwrap()
+        .extend(for_spec_entries(historic_types.get("forSpec")).map(|(range, _)| range));
+    let key_names = take_storage_key_names(&mut historic_types)
+        .with_context(|| "Can't parse storage key names in historic types")
+        .context(ErrorKind::Config)?;
+    STORAGE_KEY_NAMES.lock().unwrap().extend(key_names);
+    apply_pallet_aliases(&mut historic_types)
+        .with_context(|| "Can't apply pallet aliases in historic types")
+        .context(ErrorKind::Config)?;
+    apply_spec_version_overrides(&mut historic_types)
+        .with_context(|| "Can't apply spec version overrides in historic types")
+        .context(ErrorKind::Config)?;
+    Ok(historic_types)
+}
+
+/// Some spec versions had odd encodings for a type which no other spec version shared. A
+/// `forSpecVersion` entry in the types file like:
+///
+/// ```yaml
+/// forSpecVersion:
+///   - specVersion: 1055
+///     types:
+///       Foo: u8
+///     palletTypes:
+///       Balances:
+///         Bar: u16
+/// ```
+///
+/// pins the given types to exactly that spec version. These are added as the last `forSpec`
+/// entries, so they take precedence over everything else (including pallet aliases).
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpecVersionOverride {
+    spec_version: u64,
+    #[serde(default)]
+    types: Mapping,
+    #[serde(default)]
+    pallet_types: Mapping,
+}
+
+fn apply_spec_version_overrides(types: &mut Value) -> anyhow::Result<()> {
+    let Some(root) = types.as_mapping_mut() else {
+        return Ok(());
+    };
+    let Some(overrides) = root.remove("forSpecVersion") else {
+        return Ok(());
+    };
+    let overrides: Vec<SpecVersionOverride> = serde_yaml::from_value(overrides)?;
+
+    let for_spec = root
+        .entry("forSpec".into())
+        .or_insert_with(|| Value::Sequence(Vec::new()));
+    let Some(for_spec) = for_spec.as_sequence_mut() else {
+        anyhow::bail!("forSpec should be a list");
+    };
+    for o in overrides {
+        let mut entry = Mapping::new();
+        entry.insert(
+            "range".into(),
+            serde_yaml::to_value((o.spec_version, o.spec_version))?,
+        );
+        entry.insert("types".into(), Value::Mapping(o.types));
+        entry.insert("palletTypes".into(), Value::Mapping(o.pallet_types));
+        for_spec.push(Value::Mapping(entry));
+    }
+    Ok(())
+}
+
+/// The metadata doesn't name the keys of storage entries, which makes entries with several
+/// keys hard to read. A `storageKeyNames` entry in the types file like:
+///
+/// ```yaml
+/// storageKeyNames:
+///   Staking:
+///     ErasStakers: [era, validator]
+/// ```
+///
+/// names each key of the given entries, in order. This is removed from the types, since it
+/// isn't a part of the types file format that the type registry understands.
+fn take_storage_key_names(
+    types: &mut Value,
+) -> anyhow::Result<BTreeMap<(String, String), Vec<String>>> {
+    let Some(names) = types
+        .as_mapping_mut()
+        .and_then(|root| root.remove("storageKeyNames"))
+    else {
+        return Ok(BTreeMap::new());
+    };
+    let names: BTreeMap<String, BTreeMap<String, Vec<String>>> = serde_yaml::from_value(names)?;
+    Ok(names
+        .into_iter()
+        .flat_map(|(pallet, entries)| {
+            entries
+                .into_iter()
+                .map(move |(entry, keys)| ((pallet.clone(), entry), keys))
+        })
+        .collect())
+}
+
+/// The names given to each key of some storage entry in the types files, if any.
+pub fn storage_key_names(pallet_name: &str, storage_entry: &str) -> Vec<String> {
+    STORAGE_KEY_NAMES
+        .lock()
+        .unwrap()
+        .get(&(pallet_name.to_owned(), storage_entry.to_owned()))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Pallets are sometimes renamed across spec versions, but types in the types file are scoped
+/// using the pallet names from the metadata. A `palletAliases` entry in the types file like:
+///
+/// ```yaml
+/// palletAliases:
+///   - range: [null, 1000]
+///     pallet: NewName
+///     typesFrom: OldName
+/// ```
+///
+/// makes the `palletTypes` given for `OldName` also apply to the pallet `NewName` in the given
+/// spec range (or in all spec versions if no range is given). This is done by adding `forSpec`
+/// entries, which take precedence over existing ones.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PalletAlias {
+    #[serde(default)]
+    range: Option<(Option<u64>, Option<u64>)>,
+    pallet: String,
+    types_from: String,
+}
+
+pub(super) type SpecRange = (Option<u64>, Option<u64>);
+
+fn apply_pallet_aliases(types: &mut Value) -> anyhow::Result<()> {
+    let Some(root) = types.as_mapping_mut() else {
+        return Ok(());
+    };
+    let Some(aliases) = root.remove("palletAliases") else {
+        return Ok(());
+    };
+    let aliases: Vec<PalletAlias> = serde_yaml::from_value(aliases)?;
+
+    let global_pallet_types = root
+        .get("global")
+        .and_then(|g| g.get("palletTypes"))
+        .cloned();
+    let for_spec: Vec<(SpecRange, Value)> = for_spec_entries(root.get("forSpec"))
+        .filter_map(|(range, entry)| Some((range, entry.get("palletTypes")?.clone())))
+        .collect();
+
+    let mut new_entries = Vec::new();
+    for alias in aliases {
+        let alias_range = alias.range.unwrap_or((None, None));
+
+        if let Some(types) = global_pallet_types
+            .as_ref()
+            .and_then(|t| t.get(&alias.types_from))
+        {
+            new_entries.push(for_spec_entry(alias_range, &alias.pallet, types.clone()));
+        }
+
+        for (range, pallet_types) in &for_spec {
+            let Some(types) = pallet_types.get(&alias.types_from) else {
+                continue;
+            };
+            let Some(range) = intersect_ranges(alias_range, *range) else {
+                continue;
+            };
+            new_entries.push(for_spec_entry(range, &alias.pallet, types.clone()));
+        }
+    }
+
+    if new_entries.is_empty() {
+        return Ok(());
+    }
+
+    let for_spec = root
+        .entry("forSpec".into())
+        .or_insert_with(|| Value::Sequence(Vec::new()));
+    let Some(for_spec) = for_spec.as_sequence_mut() else {
+        anyhow::bail!("forSpec should be a list");
+    };
+    for_spec.extend(new_entries);
+    Ok(())
+}
+
+/// The `forSpec` entries in a types file, along with the range of spec versions that each
+/// applies to.
+fn for_spec_entries(for_spec: Option<&Value>) -> impl Iterator<Item = (SpecRange, &Value)> {
+    for_spec
+        .and_then(|for_spec| for_spec.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let range = entry.get("range").cloned()?;
+            let range: SpecRange = serde_yaml::from_value(range).ok()?;
+            Some((range, entry))
+        })
+}
+
+/// A warning that the types files have types for particular spec versions, but none for the
+/// spec version of some block, and so only their global types are used to decode it.
+#[derive(Serialize)]
+struct NoSpecTypesWarning {
+    warning: &'static str,
+    block_number: u64,
+    spec_version: u32,
+}
+
+/// Warn (once per spec version) if the types files that we've loaded have `forSpec` entries,
+/// but none for the spec version of the given block. Types from the wrong era are otherwise
+/// used silently, and only show up as odd decode errors (or worse, odd values). With JSON
+/// output, the warning is written to stderr as JSON.
+pub fn warn_if_no_spec_types(
+    block_number: u64,
+    spec_version: u32,
+    opts: &DecodeOptions,
+    format: OutputFormat,
+) {
+    let spec_version = opts.types_spec_version(spec_version);
+    if !lacks_spec_types(&SPEC_RANGES.lock().unwrap(), spec_version as u64)
+        || !WARNED_SPEC_VERSIONS.lock().unwrap().insert(spec_version)
+    {
+        return;
+    }
+
+    if format.is_json() {
+        let warning = NoSpecTypesWarning {
+            warning: "no_types_for_spec_version",
+            block_number,
+            spec_version,
+        };
+        let _ = output::write_json_line(std::io::stderr().lock(), &warning);
+    } else {
+        eprintln!(
+            "WARNING: the types file has no forSpec entry for spec version {spec_version} \
+             (first seen at block {block_number}), so only its global types will be used"
+        );
+    }
+}
+
+/// Are there spec ranges, but none containing the given spec version?
+fn lacks_spec_types(ranges: &[SpecRange], spec_version: u64) -> bool {
+    !ranges.is_empty()
+        && !ranges.iter().any(|(min, max)| {
+            min.is_none_or(|min| spec_version >= min) && max.is_none_or(|max| spec_version <= max)
+        })
+}
+
+fn for_spec_entry(range: SpecRange, pallet: &str, types: Value) -> Value {
+    let mut pallet_types = Mapping::new();
+    pallet_types.insert(pallet.into(), types);
+
+    let mut entry = Mapping::new();
+    entry.insert(
+        "range".into(),
+        serde_yaml::to_value(range).expect("range should serialize"),
+    );
+    entry.insert("types".into(), Value::Mapping(Mapping::new()));
+    entry.insert("palletTypes".into(), Value::Mapping(pallet_types));
+    Value::Mapping(entry)
+}
+
+/// The overlap of two inclusive spec version ranges, where `None` means unbounded.
+fn intersect_ranges(a: SpecRange, b: SpecRange) -> Option<SpecRange> {
+    let start = match (a.0, b.0) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    };
+    let end = match (a.1, b.1) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    match (start, end) {
+        (Some(start), Some(end)) if start > end => None,
+        _ => Some((start, end)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_info_legacy::LookupName;
+
+    fn decode_u8_or_u16(types: &ChainTypeRegistry, spec_version: u64, pallet: &str) -> String {
+        let types = types.for_spec_version(spec_version);
+        let ty = LookupName::parse("Foo").unwrap().in_pallet(pallet);
+        match scale_value::scale::decode_as_type(&mut &[1u8, 1][..], ty, &types) {
+            Ok(value) => value.to_string(),
+            Err(_) => "not found".to_owned(),
+        }
+    }
+
+    #[test]
+    fn pallet_aliases_apply_in_range() {
+        let yaml = r#"
+            global:
+              palletTypes:
+                Old:
+                  Foo: u8
+            forSpec:
+              - range: [15, null]
+                palletTypes:
+                  Old:
+                    Foo: u16
+            palletAliases:
+              - range: [10, 20]
+                pallet: New
+                typesFrom: Old
+        "#;
+
+        let mut types: Value = serde_yaml::from_str(yaml).unwrap();
+        apply_pallet_aliases(&mut types).unwrap();
+        let types: ChainTypeRegistry = serde_yaml::from_value(types).unwrap();
+
+        assert_eq!(decode_u8_or_u16(&types, 5, "New"), "not found");
+        assert_eq!(decode_u8_or_u16(&types, 10, "New"), "1");
+        assert_eq!(decode_u8_or_u16(&types, 15, "New"), "257");
+        assert_eq!(decode_u8_or_u16(&types, 25, "New"), "not found");
+        assert_eq!(decode_u8_or_u16(&types, 5, "Old"), "1");
+    }
+
+    #[test]
+    fn spec_version_overrides_take_precedence() {
+        let yaml = r#"
+            global:
+              types: {}
+            forSpec:
+              - range: [10, null]
+                palletTypes:
+                  Old:
+                    Foo: u16
+            forSpecVersion:
+              - specVersion: 12
+                palletTypes:
+                  New:
+                    Foo: u8
+            palletAliases:
+              - pallet: New
+                typesFrom: Old
+        "#;
+
+        let mut types: Value = serde_yaml::from_str(yaml).unwrap();
+        apply_pallet_aliases(&mut types).unwrap();
+        apply_spec_version_overrides(&mut types).unwrap();
+        let types: ChainTypeRegistry = serde_yaml::from_value(types).unwrap();
+
+        assert_eq!(decode_u8_or_u16(&types, 11, "New"), "257");
+        assert_eq!(decode_u8_or_u16(&types, 12, "New"), "1");
+        assert_eq!(decode_u8_or_u16(&types, 13, "New"), "257");
+        assert_eq!(decode_u8_or_u16(&types, 12, "Old"), "257");
+    }
+
+    #[test]
+    fn takes_storage_key_names() {
+        let yaml = r#"
+            global:
+              types:
+                Foo: u8
+            storageKeyNames:
+              Staking:
+                ErasStakers: [era, validator]
+        "#;
+
+        let mut types: Value = serde_yaml::from_str(yaml).unwrap();
+        let names = take_storage_key_names(&mut types).unwrap();
+        assert!(types.get("storageKeyNames").is_none());
+        assert_eq!(
+            names[&("Staking".to_owned(), "ErasStakers".to_owned())],
+            ["era", "validator"]
+        );
+    }
+
+    #[test]
+    fn finds_spec_versions_without_types() {
+        let ranges = [(Some(10), Some(20)), (Some(30), None)];
+        assert!(lacks_spec_types(&ranges, 5));
+        assert!(!lacks_spec_types(&ranges, 10));
+        assert!(lacks_spec_types(&ranges, 25));
+        assert!(!lacks_spec_types(&ranges, 1000));
+        assert!(!lacks_spec_types(&[], 5));
+    }
+
+    #[test]
+    fn intersects_ranges() {
+        assert_eq!(
+            intersect_ranges((None, Some(10)), (Some(5), None)),
+            Some((Some(5), Some(10)))
+        );
+        assert_eq!(intersect_ranges((None, Some(4)), (Some(5), None)), None);
+        assert_eq!(
+            intersect_ranges((None, None), (None, None)),
+            Some((None, None))
+        );
+    }
+}
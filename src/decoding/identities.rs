@@ -0,0 +1,269 @@
+use super::addresses;
+use super::decode_options::DecodeOptions;
+use super::extrinsic_decoder::Extrinsic;
+use super::storage_decoder::decode_storage_value;
+use crate::utils::value_to_bytes;
+use frame_metadata::RuntimeMetadata;
+use scale_info_legacy::TypeRegistrySet;
+use scale_value::{At, Composite, Primitive, Value, ValueDef};
+use std::collections::HashMap;
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::utils::H256;
+use subxt::PolkadotConfig;
+
+/// Account IDs mapped to their on-chain identity display names, if they have one.
+pub type Identities = HashMap<[u8; 32], Option<String>>;
+
+/// Find anything that looks like an account ID in the given value. We don't have reliable
+/// type names to go on, so any 32 byte sequence is treated as a potential account ID, as is
+/// any string that was formatted as one (see [`addresses::format_account_ids`]).
+pub fn find_account_ids<T>(value: &Value<T>, out: &mut Vec<[u8; 32]>) {
+    if let Some(account_id) = addresses::value_to_account_id(value) {
+        out.push(account_id);
+        return;
+    }
+    match &value.value {
+        ValueDef::Composite(composite) => {
+            composite.values().for_each(|v| find_account_ids(v, out));
+        }
+        ValueDef::Variant(variant) => {
+            variant
+                .values
+                .values()
+                .for_each(|v| find_account_ids(v, out));
+        }
+        _ => {}
+    }
+}
+
+/// Find anything that looks like an account ID in the given extrinsic.
+pub fn find_extrinsic_account_ids(ext: &Extrinsic, out: &mut Vec<[u8; 32]>) {
+    let (address, signed_exts, call_data) = match ext {
+        Extrinsic::Unsigned { call_data } => (None, None, call_data),
+        Extrinsic::Signed {
+            address,
+            signed_exts,
+            call_data,
+            ..
+        } => (Some(address), Some(signed_exts), call_data),
+        Extrinsic::General {
+            signed_exts,
+            call_data,
+        } => (None, Some(signed_exts), call_data),
+    };
+
+    if let Some(account_id) = address.and_then(|a| addresses::parse_account_id(a)) {
+        out.push(account_id);
+    }
+    for (_, value) in signed_exts.into_iter().flatten() {
+        find_account_ids(value, out);
+    }
+    for (_, value) in &call_data.args {
+        find_account_ids(value, out);
+    }
+}
+
+/// Fetch the display names for any of the given accounts that we don't already know about
+/// from `Identity.IdentityOf` at the given block.
+pub async fn fetch_identities(
+    rpcs: &LegacyRpcMethods<PolkadotConfig>,
+    at: H256,
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet<'_>,
+    opts: &DecodeOptions,
+    account_ids: impl IntoIterator<Item = [u8; 32]>,
+    identities: &mut Identities,
+) -> anyhow::Result<()> {
+    for account_id in account_ids {
+        if identities.contains_key(&account_id) {
+            continue;
+        }
+
+        let key = identity_of_key(&account_id);
+        let display_name = match rpcs.state_get_storage(&key, Some(at)).await? {
+            Some(bytes) => decode_storage_value(
+                "Identity",
+                "IdentityOf",
+                &bytes,
+                metadata,
+                historic_types,
+                opts,
+            )
+            .ok()
+            .and_then(|identity| display_name(&identity)),
+            None => None,
+        };
+        identities.insert(account_id, display_name);
+    }
+    Ok(())
+}
+
+/// Replace any account IDs in the value which have a display name with a string like
+/// `NAME (5Grw...)`.
+pub fn replace_account_ids(
+    value: &mut Value<String>,
+    identities: &Identities,
+    opts: &DecodeOptions,
+) {
+    if let Some(account_id) = addresses::value_to_account_id(value) {
+        if let Some(name) = account_name(&account_id, identities, opts) {
+            *value = Value {
+                value: ValueDef::Primitive(Primitive::String(name)),
+                context: value.context.clone(),
+            };
+        }
+        return;
+    }
+    match &mut value.value {
+        ValueDef::Composite(composite) => replace_in_composite(composite, identities, opts),
+        ValueDef::Variant(variant) => replace_in_composite(&mut variant.values, identities, opts),
+        _ => {}
+    }
+}
+
+/// Replace any account IDs in the extrinsic which have a display name, as
+/// [`replace_account_ids`] does.
+pub fn replace_extrinsic_account_ids(
+    ext: &mut Extrinsic,
+    identities: &Identities,
+    opts: &DecodeOptions,
+) {
+    let (address, signed_exts, call_data) = match ext {
+        Extrinsic::Unsigned { call_data } => (None, None, call_data),
+        Extrinsic::Signed {
+            address,
+            signed_exts,
+            call_data,
+            ..
+        } => (Some(address), Some(signed_exts), call_data),
+        Extrinsic::General {
+            signed_exts,
+            call_data,
+        } => (None, Some(signed_exts), call_data),
+    };
+
+    if let Some(address) = address {
+        replace_address(address, identities, opts);
+    }
+    for (_, value) in signed_exts.into_iter().flatten() {
+        replace_account_ids(value, identities, opts);
+    }
+    for (_, value) in &mut call_data.args {
+        replace_account_ids(value, identities, opts);
+    }
+}
+
+/// Format an address (as found in signed extrinsics) with its display name, if it has one.
+fn replace_address(address: &mut String, identities: &Identities, opts: &DecodeOptions) {
+    let Some(account_id) = addresses::parse_account_id(address) else {
+        return;
+    };
+    if let Some(name) = account_name(&account_id, identities, opts) {
+        *address = name;
+    }
+}
+
+fn replace_in_composite(
+    composite: &mut Composite<String>,
+    identities: &Identities,
+    opts: &DecodeOptions,
+) {
+    match composite {
+        Composite::Named(vals) => vals
+            .iter_mut()
+            .for_each(|(_, v)| replace_account_ids(v, identities, opts)),
+        Composite::Unnamed(vals) => vals
+            .iter_mut()
+            .for_each(|v| replace_account_ids(v, identities, opts)),
+    }
+}
+
+fn account_name(
+    account_id: &[u8; 32],
+    identities: &Identities,
+    opts: &DecodeOptions,
+) -> Option<String> {
+    let name = identities.get(account_id)?.as_ref()?;
+    Some(format!(
+        "{name} ({})",
+        addresses::format_account_id(account_id, opts)
+    ))
+}
+
+/// `Identity.IdentityOf` is a map from account ID to identity, hashed with `Twox64Concat`.
+fn identity_of_key(account_id: &[u8; 32]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(32 + 8 + 32);
+    key.extend(&sp_crypto_hashing::twox_128(b"Identity"));
+    key.extend(&sp_crypto_hashing::twox_128(b"IdentityOf"));
+    key.extend(&sp_crypto_hashing::twox_64(account_id));
+    key.extend(account_id);
+    key
+}
+
+/// Pull the display name out of some identity registration. In newer runtimes, the
+/// registration is the first item in a tuple alongside an optional username.
+fn display_name(identity: &Value<String>) -> Option<String> {
+    let registration = match identity.at("info") {
+        Some(_) => identity,
+        None => identity.at(0)?,
+    };
+    let display = registration.at("info")?.at("display")?;
+
+    // Display names are stored as `Data`, which is an enum whose variants are
+    // named `Raw0` to `Raw32` (or just `Raw`) when it contains the name itself.
+    let ValueDef::Variant(variant) = &display.value else {
+        return None;
+    };
+    if !variant.name.starts_with("Raw") {
+        return None;
+    }
+    let bytes = match variant.values.values().next() {
+        Some(inner) => value_to_bytes(inner)?,
+        None => Vec::new(),
+    };
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use subxt::utils::AccountId32;
+
+    fn account_value(byte: u8) -> Value<()> {
+        Value::unnamed_composite((0..32).map(|_| Value::u128(byte as u128)))
+    }
+
+    #[test]
+    fn replaces_known_account_ids() {
+        let mut value = Value::named_composite([
+            ("dest", account_value(1)),
+            ("other", account_value(2)),
+            ("value", Value::u128(100)),
+        ])
+        .map_context(|_| String::new());
+
+        let mut found = vec![];
+        find_account_ids(&value, &mut found);
+        assert_eq!(found, vec![[1; 32], [2; 32]]);
+
+        let identities = Identities::from([([1; 32], Some("ALICE".to_owned())), ([2; 32], None)]);
+        replace_account_ids(&mut value, &identities, &DecodeOptions::default());
+
+        let name = value.at("dest").unwrap().as_str().unwrap();
+        assert_eq!(name, format!("ALICE ({})", AccountId32([1; 32])));
+        assert!(value_to_bytes(value.at("other").unwrap()).is_some());
+    }
+
+    #[test]
+    fn finds_display_names() {
+        let display = Value::unnamed_variant("Raw5", [Value::from_bytes(b"ALICE")]);
+        let registration =
+            Value::named_composite([("info", Value::named_composite([("display", display)]))]);
+        let identity = Value::unnamed_composite([
+            registration,
+            Value::variant("None", Composite::Unnamed(vec![])),
+        ])
+        .map_context(|_| String::new());
+        assert_eq!(display_name(&identity), Some("ALICE".to_owned()));
+    }
+}
@@ -0,0 +1,167 @@
+use super::addresses::{format_account_id, value_to_account_id};
+use super::decode_options::DecodeOptions;
+use super::extrinsic_decoder::Extrinsic;
+use super::storage_decoder::{decode_storage_value, hash_storage_key, storage_key_hashers};
+use frame_metadata::RuntimeMetadata;
+use parity_scale_codec::{Compact, Decode, Encode};
+use scale_info_legacy::TypeRegistrySet;
+use scale_value::At;
+use std::collections::HashMap;
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::utils::H256;
+use subxt::PolkadotConfig;
+
+/// Account indices mapped to the account IDs that they refer to, if they refer to one.
+pub type AccountIndices = HashMap<u32, Option<[u8; 32]>>;
+
+/// The account that some extrinsic address refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    Id([u8; 32]),
+    Index(u32),
+}
+
+/// Parse the bytes of an extrinsic address which isn't just an account ID. Newer runtimes use
+/// a `MultiAddress`, and older ones use the custom encoding of the `Indices` pallet's lookup
+/// source, where the first byte says what follows it:
+///
+/// - `0xff`: a 32 byte account ID.
+/// - `0xfc`: a `u16` account index.
+/// - `0xfd`: a `u32` account index.
+/// - `0x00` to `0xee`: the account index itself.
+pub fn parse_address(bytes: &[u8]) -> Option<Address> {
+    let (&first, rest) = bytes.split_first()?;
+    match (first, rest.len()) {
+        // MultiAddress::Id and MultiAddress::Index
+        (0x00, 32) | (0xff, 32) => Some(Address::Id(rest.try_into().ok()?)),
+        (0x00..=0xee, 0) => Some(Address::Index(first as u32)),
+        (0x01, _) => {
+            let cursor = &mut &*rest;
+            let index = Compact::<u32>::decode(cursor).ok()?;
+            cursor.is_empty().then_some(Address::Index(index.0))
+        }
+        (0xfc, 2) => Some(Address::Index(u16::decode(&mut &*rest).ok()? as u32)),
+        (0xfd, 4) => Some(Address::Index(u32::decode(&mut &*rest).ok()?)),
+        _ => None,
+    }
+}
+
+/// Replace the address of any signed extrinsic which refers to an account by its index (or
+/// wraps an account ID in some enum) with the SS58 address of the account. Account indices are
+/// looked up in the `Indices` pallet storage at the given block, and cached in `indices`.
+pub async fn resolve_extrinsic_addresses(
+    rpcs: &LegacyRpcMethods<PolkadotConfig>,
+    at: H256,
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet<'_>,
+    opts: &DecodeOptions,
+    extrinsics: impl IntoIterator<Item = &mut Extrinsic>,
+    indices: &mut AccountIndices,
+) -> anyhow::Result<()> {
+    for ext in extrinsics {
+        let Extrinsic::Signed { address, .. } = ext else {
+            continue;
+        };
+        let Some(bytes) = address
+            .strip_prefix("0x")
+            .and_then(|hex| hex::decode(hex).ok())
+        else {
+            continue;
+        };
+
+        let account_id = match parse_address(&bytes) {
+            Some(Address::Id(account_id)) => account_id,
+            Some(Address::Index(index)) => {
+                let account_id = match indices.get(&index) {
+                    Some(account_id) => *account_id,
+                    None => {
+                        let account_id =
+                            fetch_account_id(rpcs, at, metadata, historic_types, opts, index)
+                                .await?;
+                        indices.insert(index, account_id);
+                        account_id
+                    }
+                };
+                match account_id {
+                    Some(account_id) => account_id,
+                    None => continue,
+                }
+            }
+            None => continue,
+        };
+        *address = format_account_id(&account_id, opts);
+    }
+    Ok(())
+}
+
+/// The number of accounts in each `Indices.EnumSet` entry.
+const ENUM_SET_SIZE: u32 = 64;
+
+/// Look up the account that some index refers to. Runtimes store this in `Indices.Accounts`,
+/// but early ones instead store sets of accounts in `Indices.EnumSet`, where the index of an
+/// account is its position across all of the sets.
+async fn fetch_account_id(
+    rpcs: &LegacyRpcMethods<PolkadotConfig>,
+    at: H256,
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet<'_>,
+    opts: &DecodeOptions,
+    index: u32,
+) -> anyhow::Result<Option<[u8; 32]>> {
+    let (entry, key, position) = if storage_key_hashers("Indices", "Accounts", metadata).is_ok() {
+        ("Accounts", index, 0)
+    } else {
+        ("EnumSet", index / ENUM_SET_SIZE, index % ENUM_SET_SIZE)
+    };
+
+    let hashers = storage_key_hashers("Indices", entry, metadata)?;
+    let [hasher] = hashers[..] else {
+        anyhow::bail!("Indices.{entry} should be a map with a single key");
+    };
+
+    let mut storage_key = Vec::new();
+    storage_key.extend(sp_crypto_hashing::twox_128(b"Indices"));
+    storage_key.extend(sp_crypto_hashing::twox_128(entry.as_bytes()));
+    storage_key.extend(hash_storage_key(hasher, &key.encode()));
+
+    let Some(bytes) = rpcs.state_get_storage(&storage_key, Some(at)).await? else {
+        return Ok(None);
+    };
+    let value = decode_storage_value("Indices", entry, &bytes, metadata, historic_types, opts)?;
+
+    // Accounts values are (AccountId, Balance, ..), and EnumSet values are Vec<AccountId>.
+    let account_id = value.at(position as usize).and_then(value_to_account_id);
+    Ok(account_id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_addresses() {
+        let mut multi_id = vec![0x00];
+        multi_id.extend([1; 32]);
+        assert_eq!(parse_address(&multi_id), Some(Address::Id([1; 32])));
+
+        let mut legacy_id = vec![0xff];
+        legacy_id.extend([2; 32]);
+        assert_eq!(parse_address(&legacy_id), Some(Address::Id([2; 32])));
+
+        let mut multi_index = vec![0x01];
+        multi_index.extend(Compact(1000u32).encode());
+        assert_eq!(parse_address(&multi_index), Some(Address::Index(1000)));
+
+        assert_eq!(parse_address(&[0x05]), Some(Address::Index(5)));
+        assert_eq!(
+            parse_address(&[0xfc, 0x01, 0x01]),
+            Some(Address::Index(257))
+        );
+        assert_eq!(
+            parse_address(&[0xfd, 0x01, 0x00, 0x01, 0x00]),
+            Some(Address::Index(65537))
+        );
+        assert_eq!(parse_address(&[0xef]), None);
+        assert_eq!(parse_address(&[]), None);
+    }
+}
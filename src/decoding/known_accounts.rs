@@ -0,0 +1,53 @@
+//! Labels for well known accounts (exchanges, the treasury, validators and so on), given in a
+//! JSON file mapping addresses to labels, eg `{ "13UVJyLnbVp9RBZYFwFGyDvVd1y27Tt8tkntv6Q7JVPhFsTB": "Treasury" }`.
+//! Accounts are labelled in the same way that identities are (see
+//! [`super::identities::replace_account_ids`]), and take precedence over them.
+
+use super::addresses;
+use super::identities::Identities;
+use crate::utils::exit_code::ErrorKind;
+use anyhow::{anyhow, Context};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Load the known accounts from a JSON file.
+pub fn load_known_accounts(path: &Path) -> anyhow::Result<Identities> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read known accounts {}", path.display()))
+        .context(ErrorKind::Config)?;
+    parse_known_accounts(&contents)
+        .with_context(|| format!("Could not parse known accounts {}", path.display()))
+        .context(ErrorKind::Config)
+}
+
+/// Parse known accounts from JSON mapping addresses (in SS58 format with any prefix, or hex
+/// encoded account IDs) to labels.
+fn parse_known_accounts(json: &str) -> anyhow::Result<Identities> {
+    let labels: BTreeMap<String, String> = serde_json::from_str(json)?;
+    labels
+        .into_iter()
+        .map(|(address, label)| {
+            let account_id = addresses::parse_account_id(&address)
+                .ok_or_else(|| anyhow!("{address} is not an SS58 address or 32 byte hex"))?;
+            Ok((account_id, Some(label)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_known_accounts() {
+        let hex = subxt::utils::to_hex([1u8; 32]);
+        let ss58 = subxt::utils::AccountId32([2; 32]).to_string();
+        let json = format!(r#"{{ "{hex}": "Exchange", "{ss58}": "Treasury" }}"#);
+
+        let known = parse_known_accounts(&json).unwrap();
+        assert_eq!(known[&[1; 32]].as_deref(), Some("Exchange"));
+        assert_eq!(known[&[2; 32]].as_deref(), Some("Treasury"));
+
+        assert!(parse_known_accounts(r#"{ "alice": "Alice" }"#).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use super::decode_limits::decode_value;
+use super::decode_options::DecodeOptions;
+use scale_type_resolver::TypeResolver;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// A guess at how the layout of some value differs from the type that it failed to decode as.
+/// Historic runtimes often changed values (or fields in them) to and from `Option`s, and the
+/// types file not keeping up with this at some spec version leads to these. Errors are tagged
+/// with one of these (via [`anyhow::Context`]) when it explains why decoding failed.
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutHint {
+    /// The bytes are a `Some` (`0x01`) followed by a value of the type, so the value is
+    /// probably an `Option` of the type at this spec version.
+    WrappedInOption,
+    /// The type is an `Option`, but the bytes are a value of the type inside it, so the value
+    /// is probably not an `Option` at this spec version.
+    NotAnOption,
+    /// The value decoded with a single `0x00` byte left over, so the type is probably missing
+    /// a trailing `Option` field that's `None` here (eg one added at this spec version).
+    TrailingNone,
+}
+
+impl std::fmt::Display for LayoutHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutHint::WrappedInOption => write!(
+                f,
+                "Hint: the bytes are Some(value) of the expected type; is it an Option at this spec version?"
+            ),
+            LayoutHint::NotAnOption => write!(
+                f,
+                "Hint: the bytes are the value inside the expected Option; is it not an Option at this spec version?"
+            ),
+            LayoutHint::TrailingNone => write!(
+                f,
+                "Hint: a single 0x00 byte is left over; is the type missing a trailing Option field at this spec version?"
+            ),
+        }
+    }
+}
+
+/// Find the [`LayoutHint`] that an error has been tagged with, if any.
+pub fn layout_hint_of(err: &anyhow::Error) -> Option<LayoutHint> {
+    err.downcast_ref::<LayoutHint>().copied()
+}
+
+/// Guess how the layout of some bytes that failed to decode as the given type differs from it,
+/// by decoding them again in each of the ways that a layout commonly changed.
+pub fn layout_hint<Resolver>(
+    bytes: &[u8],
+    type_id: Resolver::TypeId,
+    types: &Resolver,
+    opts: &DecodeOptions,
+) -> Option<LayoutHint>
+where
+    Resolver: TypeResolver,
+    Resolver::TypeId: Clone + std::fmt::Display,
+{
+    let decodes_exactly = |bytes: &[u8], type_id| {
+        let cursor = &mut &*bytes;
+        decode_value(cursor, type_id, types, opts).is_ok() && cursor.is_empty()
+    };
+
+    // A trailing 0x00 is easily left over by other layout changes too, so check those first.
+    let option_hint = match option_inner_type(type_id.clone(), types) {
+        Some(inner) => decodes_exactly(bytes, inner).then_some(LayoutHint::NotAnOption),
+        None => (bytes.first() == Some(&1) && decodes_exactly(&bytes[1..], type_id.clone()))
+            .then_some(LayoutHint::WrappedInOption),
+    };
+    if option_hint.is_some() {
+        return option_hint;
+    }
+
+    let cursor = &mut &*bytes;
+    let decoded = decode_value(cursor, type_id, types, opts).is_ok();
+    (decoded && *cursor == [0]).then_some(LayoutHint::TrailingNone)
+}
+
+/// If the given type is an `Option`, the type inside it.
+fn option_inner_type<Resolver>(
+    type_id: Resolver::TypeId,
+    types: &Resolver,
+) -> Option<Resolver::TypeId>
+where
+    Resolver: TypeResolver,
+    Resolver::TypeId: Clone,
+{
+    let visitor = scale_type_resolver::visitor::new::<_, Resolver::TypeId, _, _>((), |_, _| None)
+        .visit_variant(|_, _, variants| {
+            let mut names = Vec::new();
+            let mut inner = None;
+            for mut variant in variants {
+                names.push(variant.name);
+                if variant.name == "Some" {
+                    inner = variant.fields.next().map(|field| field.id);
+                }
+            }
+            inner.filter(|_| names == ["None", "Some"])
+        });
+    types.resolve_type(type_id, visitor).ok().flatten()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_info_legacy::{ChainTypeRegistry, LookupName};
+
+    #[test]
+    fn finds_layout_hints() {
+        let types: ChainTypeRegistry = serde_yaml::from_str(
+            r#"
+            global:
+              types:
+                Slash:
+                  validator: u32
+                  amount: u64
+            "#,
+        )
+        .unwrap();
+        let types = types.for_spec_version(0);
+        let opts = DecodeOptions::default();
+        let hint = |bytes: &[u8], ty: &str| {
+            layout_hint(bytes, LookupName::parse(ty).unwrap(), &types, &opts)
+        };
+
+        let slash = [[7, 0, 0, 0].as_slice(), &[100, 0, 0, 0, 0, 0, 0, 0]].concat();
+        assert_eq!(
+            hint(&[&[1], slash.as_slice()].concat(), "Slash"),
+            Some(LayoutHint::WrappedInOption)
+        );
+        assert_eq!(hint(&slash, "Option<Slash>"), Some(LayoutHint::NotAnOption));
+        assert_eq!(
+            hint(&[slash.as_slice(), &[0]].concat(), "Slash"),
+            Some(LayoutHint::TrailingNone)
+        );
+        assert_eq!(hint(&slash[1..], "Slash"), None);
+    }
+}
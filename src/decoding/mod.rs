@@ -1,2 +1,37 @@
+pub mod addresses;
+pub mod builtin_types;
+pub mod call_hashes;
+pub mod canonical;
+pub mod decode_limits;
+pub mod decode_options;
+pub mod decoding_context;
+pub mod ethereum;
+pub mod events;
 pub mod extrinsic_decoder;
+pub mod governance;
+pub mod historic_types;
+pub mod identities;
+pub mod indices;
+pub mod known_accounts;
+pub mod layout_hints;
+pub mod named_types;
+pub mod para_inherent;
+pub mod per_things;
+pub mod remarks;
+pub mod runtime_code;
+pub mod schedules;
+pub mod session_keys;
 pub mod storage_decoder;
+pub mod type_provenance;
+pub mod weights;
+
+use frame_metadata::decode_different::DecodeDifferent;
+
+/// Metadata that we've decoded (rather than metadata built in a runtime) only contains the
+/// decoded half of any [`DecodeDifferent`] values.
+fn decoded<B, O>(item: &DecodeDifferent<B, O>) -> &O {
+    match item {
+        DecodeDifferent::Decoded(item) => item,
+        DecodeDifferent::Encode(_) => panic!("metadata should have been decoded"),
+    }
+}
@@ -0,0 +1,161 @@
+//! Some values are written specially in text output, such as proportions (see
+//! [`super::per_things`]), weights (see [`super::weights`]) and Ethereum addresses (see
+//! [`super::ethereum`]). These are recognised by the
+//! type that they were decoded as. Historic types name them directly, but in V14+ metadata
+//! values only carry a type ID, so values of these types are given the type's name instead when
+//! they're decoded.
+
+use super::{ethereum, per_things, weights};
+use scale_type_resolver::TypeResolver;
+use scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// The name of a type without any path or `Compact<..>` around it, eg `Perbill` for
+/// `Compact<sp_arithmetic::per_things::Perbill>`.
+pub fn base_name(type_name: &str) -> &str {
+    let name = type_name
+        .strip_prefix("Compact<")
+        .and_then(|name| name.strip_suffix('>'))
+        .unwrap_or(type_name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+fn is_named(type_name: &str) -> bool {
+    per_things::is_per_thing(type_name)
+        || weights::is_weight(type_name)
+        || ethereum::is_ethereum_type(type_name)
+}
+
+/// Convert the type IDs in some decoded value to strings, naming any values whose types are
+/// written specially by their type.
+pub fn name_types<Resolver>(value: Value<Resolver::TypeId>, types: &Resolver) -> Value<String>
+where
+    Resolver: TypeResolver,
+    Resolver::TypeId: Clone + std::fmt::Display,
+{
+    // Only look up the types of values shaped like those we name, rather than of every value.
+    let name = might_be_named(&value)
+        .then(|| type_name(value.context.clone(), types))
+        .flatten();
+    let context = name.unwrap_or_else(|| value.context.to_string());
+    let name_composite = |composite: Composite<Resolver::TypeId>| match composite {
+        Composite::Named(vals) => Composite::Named(
+            vals.into_iter()
+                .map(|(name, v)| (name, name_types(v, types)))
+                .collect(),
+        ),
+        Composite::Unnamed(vals) => {
+            Composite::Unnamed(vals.into_iter().map(|v| name_types(v, types)).collect())
+        }
+    };
+    let value = match value.value {
+        ValueDef::Composite(composite) => ValueDef::Composite(name_composite(composite)),
+        ValueDef::Variant(variant) => ValueDef::Variant(scale_value::Variant {
+            name: variant.name,
+            values: name_composite(variant.values),
+        }),
+        ValueDef::Primitive(primitive) => ValueDef::Primitive(primitive),
+        ValueDef::BitSequence(bits) => ValueDef::BitSequence(bits),
+    };
+    Value { value, context }
+}
+
+/// Proportions (and old weights) are a composite wrapping a single integer, newer weights
+/// are a struct with a `ref_time`, and Ethereum addresses and signatures wrap an array of bytes.
+fn might_be_named<T>(value: &Value<T>) -> bool {
+    match &value.value {
+        ValueDef::Composite(Composite::Unnamed(vals)) => match vals.as_slice() {
+            [val] => match &val.value {
+                ValueDef::Primitive(Primitive::U128(_)) => true,
+                ValueDef::Composite(Composite::Unnamed(bytes)) => {
+                    ethereum::is_ethereum_len(bytes.len())
+                }
+                _ => false,
+            },
+            _ => false,
+        },
+        ValueDef::Composite(Composite::Named(vals)) => {
+            vals.iter().any(|(name, _)| name == "ref_time")
+        }
+        _ => false,
+    }
+}
+
+/// The name of the V14+ type (or the type that it is a compact of), if it's one that we name.
+fn type_name<Resolver>(type_id: Resolver::TypeId, types: &Resolver) -> Option<String>
+where
+    Resolver: TypeResolver,
+{
+    let visitor = scale_type_resolver::visitor::new::<_, Resolver::TypeId, _, _>((), |_, _| None)
+        .visit_composite(|_, path, _| {
+            let name = path.last()?;
+            is_named(name).then(|| Ok(name.to_owned()))
+        })
+        .visit_compact(|_, inner| Some(Err(inner)));
+    match types.resolve_type(type_id, visitor).ok()?? {
+        Ok(name) => Some(name),
+        Err(inner) => type_name(inner, types),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decoding::decode_limits::decode_value;
+    use crate::decoding::decode_options::DecodeOptions;
+    use parity_scale_codec::Encode;
+    use scale_info::{PortableRegistry, Registry};
+    use scale_info_legacy::{ChainTypeRegistry, LookupName};
+
+    #[test]
+    fn names_types_from_metadata() {
+        #[derive(scale_info::TypeInfo)]
+        #[allow(dead_code)]
+        struct Perbill(u32);
+        #[derive(scale_info::TypeInfo)]
+        #[allow(dead_code)]
+        struct Weight {
+            ref_time: u64,
+            proof_size: u64,
+        }
+        #[derive(scale_info::TypeInfo)]
+        #[allow(dead_code)]
+        struct Prefs {
+            commission: Perbill,
+            blocked: bool,
+            max_weight: Weight,
+        }
+
+        let mut registry = Registry::new();
+        let id = registry.register_type(&scale_info::meta_type::<Prefs>()).id;
+        let types: PortableRegistry = registry.into();
+
+        let bytes = (1_000_000u32, false, 5u64, 6u64).encode();
+        let value = decode_value(&mut &*bytes, id, &types, &DecodeOptions::default()).unwrap();
+        let commission = scale_value::At::at(&value, "commission").unwrap();
+        assert_eq!(commission.context, "Perbill");
+        let max_weight = scale_value::At::at(&value, "max_weight").unwrap();
+        assert_eq!(max_weight.context, "Weight");
+        assert_eq!(value.context, id.to_string());
+
+        let mut out = String::new();
+        crate::utils::write_value_fmt(&mut out, &value, &DecodeOptions::default()).unwrap();
+        assert!(out.contains("0.10%"));
+        assert!(out.contains("proof_size: 6 bytes"));
+
+        // Historic types already name them, so they're left alone.
+        let legacy: ChainTypeRegistry =
+            serde_yaml::from_str("global:\n  types:\n    Perbill: u32\n").unwrap();
+        let legacy = legacy.for_spec_version(0);
+        let value = Value::u128(1).map_context(|_| LookupName::parse("Perbill").unwrap());
+        assert_eq!(name_types(value, &legacy).context, "Perbill");
+    }
+
+    #[test]
+    fn finds_base_names() {
+        assert_eq!(
+            base_name("Compact<sp_arithmetic::per_things::Perbill>"),
+            "Perbill"
+        );
+        assert_eq!(base_name("Weight"), "Weight");
+    }
+}
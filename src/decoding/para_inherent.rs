@@ -0,0 +1,99 @@
+use super::extrinsic_decoder::Extrinsic;
+use scale_value::{At, Composite, Value, ValueDef};
+
+/// The parts of the parachains inherent data that we count. Each of these is a list.
+const COUNTED: &[&str] = &["bitfields", "backed_candidates", "disputes"];
+
+/// Is this the call that the parachains inherent is included in each block with?
+pub fn is_para_inherent(pallet: &str, call: &str) -> bool {
+    matches!(
+        (pallet, call),
+        (
+            "ParaInherent" | "ParasInherent" | "ParachainsInherent",
+            "enter"
+        )
+    )
+}
+
+/// Replace the data given to the parachains inherent with the number of bitfields, backed
+/// candidates and disputes in it (and the number of the parent block that it's for), since
+/// the candidates and bitfields take up most of the output of recent blocks. Data that
+/// doesn't look as expected is left alone.
+pub fn summarise_para_inherent(ext: &mut Extrinsic) {
+    let call_data = ext.call_data_mut();
+    if !is_para_inherent(&call_data.pallet_name, &call_data.call_name) {
+        return;
+    }
+    for (_, data) in &mut call_data.args {
+        if let Some(summary) = summary(data) {
+            *data = summary;
+        }
+    }
+}
+
+fn summary(data: &Value<String>) -> Option<Value<String>> {
+    let mut fields = Vec::new();
+    for name in COUNTED {
+        let ValueDef::Composite(items) = &data.at(*name)?.value else {
+            return None;
+        };
+        fields.push((name.to_string(), Value::u128(items.len() as u128)));
+    }
+    if let Some(number) = data
+        .at("parent_header")
+        .and_then(|header| header.at("number"))
+        .and_then(|number| number.as_u128())
+    {
+        fields.push(("parent_header_number".to_owned(), Value::u128(number)));
+    }
+    let summary = Value {
+        value: ValueDef::Composite(Composite::Named(fields)),
+        context: (),
+    };
+    Some(summary.map_context(|_| String::new()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decoding::extrinsic_decoder::ExtrinsicCallData;
+
+    fn para_inherent(data: Value<()>) -> Extrinsic {
+        Extrinsic::Unsigned {
+            call_data: ExtrinsicCallData {
+                pallet_name: "ParaInherent".to_owned(),
+                call_name: "enter".to_owned(),
+                args: vec![("data".to_owned(), data.map_context(|_| String::new()))],
+            },
+        }
+    }
+
+    #[test]
+    fn summarises_para_inherent_data() {
+        let list = |n| Value::unnamed_composite((0..n).map(|_| Value::u128(0)));
+        let mut ext = para_inherent(Value::named_composite([
+            ("bitfields", list(3)),
+            ("backed_candidates", list(2)),
+            ("disputes", list(0)),
+            (
+                "parent_header",
+                Value::named_composite([("number", Value::u128(100))]),
+            ),
+        ]));
+        summarise_para_inherent(&mut ext);
+
+        let summary = &ext.call_data().args[0].1;
+        let count = |name| summary.at(name).and_then(|v| v.as_u128());
+        assert_eq!(count("bitfields"), Some(3));
+        assert_eq!(count("backed_candidates"), Some(2));
+        assert_eq!(count("disputes"), Some(0));
+        assert_eq!(count("parent_header_number"), Some(100));
+    }
+
+    #[test]
+    fn leaves_unexpected_data_alone() {
+        let mut ext = para_inherent(Value::named_composite([("bitfields", Value::u128(1))]));
+        summarise_para_inherent(&mut ext);
+        assert!(ext.call_data().args[0].1.at("bitfields").is_some());
+    }
+}
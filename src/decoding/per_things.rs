@@ -0,0 +1,95 @@
+//! Writing fixed point proportions (`Percent`, `Permill`, `Perbill` and `Perquintill`, used for
+//! things like commissions and slash fractions) as percentages, eg `12.50%` rather than
+//! `125000000`, in text output. JSON output keeps the integers.
+//!
+//! These are recognised by the type that their value was decoded as (see
+//! [`super::named_types`]).
+
+use super::named_types::base_name;
+use scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// Each proportion, and the integer that stands for 100%.
+const PER_THINGS: &[(&str, u128)] = &[
+    ("Percent", 100),
+    ("Permill", 1_000_000),
+    ("Perbill", 1_000_000_000),
+    ("Perquintill", 1_000_000_000_000_000_000),
+];
+
+/// The integer that stands for 100% in the proportion with this type name, if it is one. This
+/// also recognises compact proportions and those given by path, eg `Compact<Perbill>` and
+/// `sp_arithmetic::per_things::Perbill`.
+fn denominator(type_name: &str) -> Option<u128> {
+    let name = base_name(type_name);
+    PER_THINGS
+        .iter()
+        .find(|(per_thing, _)| *per_thing == name)
+        .map(|(_, denominator)| *denominator)
+}
+
+/// Is this the type name of a proportion?
+pub fn is_per_thing(type_name: &str) -> bool {
+    denominator(type_name).is_some()
+}
+
+/// A custom formatter (for `scale_value::stringify`) which writes proportions as percentages.
+pub fn format_per_thing<T: std::fmt::Display, W: std::fmt::Write>(
+    value: &Value<T>,
+    w: &mut W,
+) -> Option<std::fmt::Result> {
+    let denominator = denominator(&value.context.to_string())?;
+    let parts = match &value.value {
+        ValueDef::Primitive(Primitive::U128(parts)) => *parts,
+        ValueDef::Composite(Composite::Unnamed(vals)) => match vals.as_slice() {
+            [val] => val.as_u128()?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(write!(w, "{}", percentage(parts, denominator)))
+}
+
+/// Write `parts / denominator` as an exact percentage, with at least two decimal places.
+fn percentage(parts: u128, denominator: u128) -> String {
+    // Percent has no decimal places of its own, and each bigger denominator adds some.
+    let decimals = denominator.ilog10() as usize - 2;
+    let whole = parts / (denominator / 100);
+    let fraction = parts % (denominator / 100);
+    let mut fraction = format!("{fraction:0decimals$}");
+    while fraction.len() > 2 && fraction.ends_with('0') {
+        fraction.pop();
+    }
+    while fraction.len() < 2 {
+        fraction.push('0');
+    }
+    format!("{whole}.{fraction}%")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn writes_percentages() {
+        assert_eq!(percentage(125_000_000, 1_000_000_000), "12.50%");
+        assert_eq!(percentage(1, 1_000_000_000), "0.0000001%");
+        assert_eq!(percentage(1_000_000_000, 1_000_000_000), "100.00%");
+        assert_eq!(percentage(7, 100), "7.00%");
+        assert_eq!(percentage(123_456, 1_000_000), "12.3456%");
+
+        let format = |value: Value<()>, ty: &str| {
+            let mut out = String::new();
+            format_per_thing(&value.map_context(|_| ty.to_owned()), &mut out)
+                .map(|res| res.map(|_| out))
+        };
+        assert_eq!(
+            format(Value::u128(50), "Compact<Percent>"),
+            Some(Ok("50.00%".to_owned()))
+        );
+        assert_eq!(
+            format(Value::unnamed_composite([Value::u128(5000)]), "Permill"),
+            Some(Ok("0.50%".to_owned()))
+        );
+        assert_eq!(format(Value::u128(50), "u32"), None);
+    }
+}
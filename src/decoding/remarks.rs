@@ -0,0 +1,222 @@
+use super::extrinsic_decoder::Extrinsic;
+use crate::utils::value_to_bytes;
+use scale_value::{Composite, Value, ValueDef};
+
+/// Something that makes sense of the bytes given to `System.remark`, such as a protocol that
+/// stores its data in remarks. Each gives the fields that it finds in some remark, or nothing
+/// if the remark isn't one that it understands.
+pub trait RemarkInterpreter: Sync {
+    /// What the remark is interpreted as, eg `text`.
+    fn name(&self) -> &'static str;
+    fn interpret(&self, bytes: &[u8]) -> Option<Vec<(&'static str, Value<()>)>>;
+}
+
+/// Remarks made with RMRK (NFTs on Kusama), which look like `RMRK::MINT::2.0.0::<data>`.
+pub struct Rmrk;
+
+impl RemarkInterpreter for Rmrk {
+    fn name(&self) -> &'static str {
+        "rmrk"
+    }
+    fn interpret(&self, bytes: &[u8]) -> Option<Vec<(&'static str, Value<()>)>> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        let mut parts = text.strip_prefix("RMRK::")?.splitn(3, "::");
+        let interaction = parts.next()?;
+        let version = parts.next()?;
+        Some(vec![
+            ("interaction", Value::string(interaction)),
+            ("version", Value::string(version)),
+            ("data", Value::string(parts.next().unwrap_or_default())),
+        ])
+    }
+}
+
+/// Remarks that are just some text.
+pub struct Text;
+
+impl RemarkInterpreter for Text {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+    fn interpret(&self, bytes: &[u8]) -> Option<Vec<(&'static str, Value<()>)>> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        // Binary data can happen to be valid UTF-8 too, so insist on printable text.
+        let is_printable = |c: char| !c.is_control() || c.is_whitespace();
+        (!text.is_empty() && text.chars().all(is_printable))
+            .then(|| vec![("text", Value::string(text))])
+    }
+}
+
+/// The interpreters to try on each remark, in order. The first one that understands a remark
+/// is used, so more specific ones come first.
+pub const INTERPRETERS: &[&dyn RemarkInterpreter] = &[&Rmrk, &Text];
+
+/// Interpret the bytes of any remarks made by this extrinsic, including those made by calls
+/// nested in it (eg in `Utility.batch`), with the first of the given interpreters that
+/// understands them. Each is replaced with its hex encoded bytes along with what was found in
+/// them, and remarks that none of the interpreters understand are left alone.
+pub fn interpret_remarks(ext: &mut Extrinsic, interpreters: &[&dyn RemarkInterpreter]) {
+    let call_data = ext.call_data_mut();
+    let is_remark = is_remark(&call_data.pallet_name, &call_data.call_name);
+    for (name, value) in &mut call_data.args {
+        if is_remark && name == "remark" {
+            interpret_remark(value, interpreters);
+        } else {
+            interpret_nested_remarks(value, interpreters);
+        }
+    }
+}
+
+fn is_remark(pallet: &str, call: &str) -> bool {
+    matches!((pallet, call), ("System", "remark" | "remark_with_event"))
+}
+
+/// Nested calls are decoded as a variant named after the pallet, containing a variant named
+/// after the call, whose fields are the call's arguments.
+fn interpret_nested_remarks(value: &mut Value<String>, interpreters: &[&dyn RemarkInterpreter]) {
+    let composite = match &mut value.value {
+        ValueDef::Variant(pallet) => {
+            if let Some(call) = nested_remark_call(&mut pallet.values) {
+                if let ValueDef::Variant(call) = &mut call.value {
+                    if let Composite::Named(args) = &mut call.values {
+                        for (_, value) in args.iter_mut().filter(|(name, _)| name == "remark") {
+                            interpret_remark(value, interpreters);
+                        }
+                    }
+                }
+                return;
+            }
+            &mut pallet.values
+        }
+        ValueDef::Composite(composite) => composite,
+        _ => return,
+    };
+    match composite {
+        Composite::Named(vals) => vals
+            .iter_mut()
+            .for_each(|(_, v)| interpret_nested_remarks(v, interpreters)),
+        Composite::Unnamed(vals) => vals
+            .iter_mut()
+            .for_each(|v| interpret_nested_remarks(v, interpreters)),
+    }
+}
+
+/// If these are the values of a `System` variant holding a remark call, that call.
+fn nested_remark_call(values: &mut Composite<String>) -> Option<&mut Value<String>> {
+    let Composite::Unnamed(vals) = values else {
+        return None;
+    };
+    let [call] = vals.as_mut_slice() else {
+        return None;
+    };
+    match &call.value {
+        ValueDef::Variant(variant) if is_remark("System", &variant.name) => Some(call),
+        _ => None,
+    }
+}
+
+fn interpret_remark(value: &mut Value<String>, interpreters: &[&dyn RemarkInterpreter]) {
+    let Some(bytes) = value_to_bytes(value) else {
+        return;
+    };
+    let Some((interpreter, fields)) = interpreters
+        .iter()
+        .find_map(|i| i.interpret(&bytes).map(|fields| (i, fields)))
+    else {
+        return;
+    };
+
+    let mut interpreted = vec![
+        (
+            "hex".to_owned(),
+            Value::string(subxt::utils::to_hex(&bytes)),
+        ),
+        (
+            "interpreted_as".to_owned(),
+            Value::string(interpreter.name()),
+        ),
+    ];
+    interpreted.extend(fields.into_iter().map(|(name, v)| (name.to_owned(), v)));
+    let interpreted = Value {
+        value: ValueDef::Composite(Composite::Named(interpreted)),
+        context: (),
+    };
+    *value = interpreted.map_context(|_| value.context.clone());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decoding::extrinsic_decoder::ExtrinsicCallData;
+    use scale_value::At;
+
+    fn extrinsic(pallet: &str, call: &str, args: Vec<(&str, Value<()>)>) -> Extrinsic {
+        Extrinsic::Unsigned {
+            call_data: ExtrinsicCallData {
+                pallet_name: pallet.to_owned(),
+                call_name: call.to_owned(),
+                args: args
+                    .into_iter()
+                    .map(|(name, v)| (name.to_owned(), v.map_context(|_| String::new())))
+                    .collect(),
+            },
+        }
+    }
+
+    fn interpreted(ext: &Extrinsic, field: &str) -> Option<String> {
+        let remark = &ext.call_data().args[0].1;
+        Some(remark.at(field)?.as_str()?.to_owned())
+    }
+
+    #[test]
+    fn interprets_remarks() {
+        let remark = |bytes: &[u8]| {
+            let mut ext = extrinsic(
+                "System",
+                "remark",
+                vec![("remark", Value::from_bytes(bytes))],
+            );
+            interpret_remarks(&mut ext, INTERPRETERS);
+            ext
+        };
+
+        let ext = remark(b"RMRK::MINT::2.0.0::%7B%7D");
+        assert_eq!(interpreted(&ext, "interpreted_as").as_deref(), Some("rmrk"));
+        assert_eq!(interpreted(&ext, "interaction").as_deref(), Some("MINT"));
+        assert_eq!(interpreted(&ext, "data").as_deref(), Some("%7B%7D"));
+
+        let ext = remark(b"hello");
+        assert_eq!(interpreted(&ext, "text").as_deref(), Some("hello"));
+        assert_eq!(interpreted(&ext, "hex").as_deref(), Some("0x68656c6c6f"));
+
+        // Remarks that nothing understands are left as bytes.
+        let ext = remark(&[0, 159, 146, 150]);
+        assert_eq!(interpreted(&ext, "hex"), None);
+    }
+
+    #[test]
+    fn interprets_nested_remarks() {
+        let call = Value::unnamed_variant(
+            "System",
+            [Value::named_variant(
+                "remark_with_event",
+                [("remark", Value::from_bytes(b"hi"))],
+            )],
+        );
+        let mut ext = extrinsic(
+            "Utility",
+            "batch",
+            vec![("calls", Value::unnamed_composite([call]))],
+        );
+        interpret_remarks(&mut ext, INTERPRETERS);
+
+        let remark = ext.call_data().args[0]
+            .1
+            .at(0)
+            .and_then(|call| call.at(0))
+            .and_then(|call| call.at("remark"))
+            .and_then(|remark| remark.at("text"))
+            .and_then(|text| text.as_str());
+        assert_eq!(remark, Some("hi"));
+    }
+}
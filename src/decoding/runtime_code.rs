@@ -0,0 +1,139 @@
+use anyhow::{anyhow, bail, Context};
+use parity_scale_codec::Decode;
+use std::io::Read;
+
+/// Runtime code which starts with this is zstd compressed.
+const ZSTD_PREFIX: [u8; 8] = [82, 188, 83, 118, 70, 219, 142, 5];
+
+/// The most that we'll decompress runtime code to, in case it's something else entirely.
+const MAX_CODE_SIZE: u64 = 50 * 1024 * 1024;
+
+/// The runtime version that's embedded in some runtime code, which is what the runtime reports
+/// its version as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedVersion {
+    pub spec_name: String,
+    pub spec_version: u32,
+}
+
+/// Find the runtime version embedded in some runtime code (as stored at `:code`), which may be
+/// compressed. This is in the `runtime_version` custom section of the Wasm.
+pub fn embedded_version(code: &[u8]) -> anyhow::Result<EmbeddedVersion> {
+    let decompressed;
+    let wasm = match code.strip_prefix(&ZSTD_PREFIX) {
+        Some(compressed) => {
+            let mut decoder = ruzstd::StreamingDecoder::new(compressed)
+                .map_err(|e| anyhow!("Could not decompress runtime code: {e}"))?;
+            let mut bytes = Vec::new();
+            (&mut decoder)
+                .take(MAX_CODE_SIZE)
+                .read_to_end(&mut bytes)
+                .with_context(|| "Could not decompress runtime code")?;
+            decompressed = bytes;
+            &decompressed[..]
+        }
+        None => code,
+    };
+
+    let section = custom_section(wasm, "runtime_version")?
+        .ok_or_else(|| anyhow!("Runtime code has no runtime_version section"))?;
+    // This starts like a `RuntimeVersion`, which goes on to list the runtime APIs and so on.
+    let (spec_name, _impl_name, _authoring_version, spec_version) =
+        <(String, String, u32, u32)>::decode(&mut &*section)
+            .with_context(|| "Could not decode the runtime_version section")?;
+    Ok(EmbeddedVersion {
+        spec_name,
+        spec_version,
+    })
+}
+
+/// Find the contents of the custom section with the given name in some Wasm, if there is one.
+fn custom_section<'a>(wasm: &'a [u8], name: &str) -> anyhow::Result<Option<&'a [u8]>> {
+    let Some(mut sections) = wasm.strip_prefix(b"\0asm") else {
+        bail!("Runtime code is not Wasm");
+    };
+    // Skip the version.
+    sections = sections
+        .get(4..)
+        .ok_or_else(|| anyhow!("Runtime code is truncated"))?;
+
+    while let Some((&id, rest)) = sections.split_first() {
+        sections = rest;
+        let len = read_leb128(&mut sections)? as usize;
+        if sections.len() < len {
+            bail!("Runtime code is truncated");
+        }
+        let (mut section, rest) = sections.split_at(len);
+        sections = rest;
+
+        // Custom sections have an ID of 0, and start with their name.
+        if id != 0 {
+            continue;
+        }
+        let name_len = read_leb128(&mut section)? as usize;
+        if section.len() < name_len {
+            bail!("Runtime code is truncated");
+        }
+        let (section_name, contents) = section.split_at(name_len);
+        if section_name == name.as_bytes() {
+            return Ok(Some(contents));
+        }
+    }
+    Ok(None)
+}
+
+/// Read an unsigned LEB128 encoded number, as used in Wasm.
+fn read_leb128(input: &mut &[u8]) -> anyhow::Result<u32> {
+    let mut n: u32 = 0;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = input
+            .split_first()
+            .ok_or_else(|| anyhow!("Runtime code is truncated"))?;
+        *input = rest;
+        n |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    bail!("Invalid LEB128 number in runtime code")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parity_scale_codec::Encode;
+
+    #[test]
+    fn finds_embedded_version() {
+        let version = (
+            "polkadot".to_owned(),
+            "parity-polkadot".to_owned(),
+            0u32,
+            9430u32,
+        )
+            .encode();
+        let name = b"runtime_version";
+        let section = [&[name.len() as u8][..], name, &version].concat();
+        // A type section to skip over, then the custom section with a two byte LEB128 length.
+        let wasm = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[1, 1, 0],
+            &[
+                0,
+                0x80 | (section.len() as u8 & 0x7f),
+                section.len() as u8 >> 7,
+            ],
+            &section,
+        ]
+        .concat();
+
+        assert_eq!(
+            embedded_version(&wasm).unwrap(),
+            EmbeddedVersion {
+                spec_name: "polkadot".to_owned(),
+                spec_version: 9430
+            }
+        );
+        assert!(embedded_version(&wasm[..20]).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+//! Approximate dates for the block numbers in vesting schedules, crowdloans, auctions and
+//! slot leases. Dates are worked out from the timestamp of the block being decoded, assuming
+//! that every other block is [`BLOCK_TIME_MS`] away from it, so they drift a little wherever
+//! blocks were produced late (or were missed).
+
+use super::decoded;
+use frame_metadata::RuntimeMetadata;
+use scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// The time that each block is expected to take on the relay chains.
+pub const BLOCK_TIME_MS: u64 = 6000;
+
+/// What we need to know to give a date to some block number or lease period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+    /// The block being decoded, and its timestamp (ie `Timestamp.Now` at it).
+    pub block_number: u32,
+    pub timestamp_ms: u64,
+    pub lease_periods: Option<LeasePeriods>,
+}
+
+/// How lease periods line up with block numbers, from the `Slots` pallet's constants. Lease
+/// period `n` starts at block `offset + n * length`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeasePeriods {
+    pub length: u32,
+    pub offset: u32,
+}
+
+impl Clock {
+    /// The approximate date of some block, eg `2021-12-18 14:02 UTC`.
+    pub fn date_of_block(&self, block_number: u32) -> String {
+        let blocks_after = block_number as i128 - self.block_number as i128;
+        let ms = self.timestamp_ms as i128 + blocks_after * BLOCK_TIME_MS as i128;
+        format_date(ms)
+    }
+
+    /// The block that some lease period starts at.
+    fn lease_period_start(&self, period: u32) -> Option<u32> {
+        let periods = self.lease_periods?;
+        period
+            .checked_mul(periods.length)?
+            .checked_add(periods.offset)
+    }
+
+    /// The lease period that the block being decoded is in.
+    fn current_lease_period(&self) -> Option<u32> {
+        let periods = self.lease_periods.filter(|p| p.length > 0)?;
+        Some(self.block_number.checked_sub(periods.offset)? / periods.length)
+    }
+}
+
+/// Find the length and offset of lease periods in the `Slots` pallet's constants, if it has
+/// them. Older runtimes have no `LeaseOffset`, which is the same as an offset of 0.
+pub fn lease_periods(metadata: &RuntimeMetadata) -> Option<LeasePeriods> {
+    let length = u32_constant(metadata, "Slots", "LeasePeriod")?;
+    let offset = u32_constant(metadata, "Slots", "LeaseOffset").unwrap_or(0);
+    Some(LeasePeriods { length, offset })
+}
+
+fn u32_constant(metadata: &RuntimeMetadata, pallet_name: &str, name: &str) -> Option<u32> {
+    macro_rules! legacy_constant {
+        ($m:ident) => {
+            decoded(&$m.modules)
+                .iter()
+                .find(|module| decoded(&module.name) == pallet_name)
+                .and_then(|module| {
+                    decoded(&module.constants)
+                        .iter()
+                        .find(|constant| decoded(&constant.name) == name)
+                })
+                .map(|constant| decoded(&constant.value).clone())
+        };
+    }
+    macro_rules! modern_constant {
+        ($m:ident) => {
+            $m.pallets
+                .iter()
+                .find(|pallet| pallet.name == pallet_name)
+                .and_then(|pallet| pallet.constants.iter().find(|c| c.name == name))
+                .map(|constant| constant.value.clone())
+        };
+    }
+
+    let bytes = match metadata {
+        RuntimeMetadata::V8(m) => legacy_constant!(m),
+        RuntimeMetadata::V9(m) => legacy_constant!(m),
+        RuntimeMetadata::V10(m) => legacy_constant!(m),
+        RuntimeMetadata::V11(m) => legacy_constant!(m),
+        RuntimeMetadata::V12(m) => legacy_constant!(m),
+        RuntimeMetadata::V13(m) => legacy_constant!(m),
+        RuntimeMetadata::V14(m) => modern_constant!(m),
+        RuntimeMetadata::V15(m) => modern_constant!(m),
+        RuntimeMetadata::V16(m) => modern_constant!(m),
+        _ => None,
+    }?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Does this storage entry contain block numbers or lease periods that we can give dates to?
+pub fn has_schedules(pallet: &str, entry: &str) -> bool {
+    matches!(
+        (pallet, entry),
+        ("Vesting", "Vesting")
+            | ("Crowdloan", "Funds")
+            | ("Auctions", "AuctionInfo")
+            | ("Slots", "Leases")
+    )
+}
+
+/// Add approximate dates alongside the block numbers and lease periods in some decoded storage
+/// value. Values that aren't shaped as expected are left alone.
+pub fn add_schedule_dates(pallet: &str, entry: &str, value: &mut Value<String>, clock: &Clock) {
+    match (pallet, entry) {
+        // A VestingInfo, or (since multiple schedules were allowed) a list of them.
+        ("Vesting", "Vesting") => add_vesting_dates(value, clock),
+        ("Crowdloan", "Funds") => add_fund_dates(value, clock),
+        // (the first lease period being auctioned, the block that the ending period starts at).
+        ("Auctions", "AuctionInfo") => {
+            let ValueDef::Composite(Composite::Unnamed(vals)) = &value.value else {
+                return;
+            };
+            let [lease_period, ending_starts] = vals.as_slice() else {
+                return;
+            };
+            let (Some(lease_period), Some(ending_starts)) =
+                (as_u32(lease_period), as_u32(ending_starts))
+            else {
+                return;
+            };
+            let mut named = vec![("lease_period".to_owned(), number(lease_period))];
+            if let Some(start) = clock.lease_period_start(lease_period) {
+                named.push(("lease_period_date".to_owned(), date(clock, start)));
+            }
+            named.push(("ending_period_start".to_owned(), number(ending_starts)));
+            named.push((
+                "ending_period_start_date".to_owned(),
+                date(clock, ending_starts),
+            ));
+            value.value = ValueDef::Composite(Composite::Named(named));
+        }
+        // A list of the leases (if any) in each lease period, starting from the current one.
+        ("Slots", "Leases") => {
+            let ValueDef::Composite(Composite::Unnamed(leases)) = &value.value else {
+                return;
+            };
+            let Some(current) = clock.current_lease_period() else {
+                return;
+            };
+            let Ok(count) = u32::try_from(leases.len()) else {
+                return;
+            };
+            let mut named = vec![("lease_period".to_owned(), number(current))];
+            if let Some(start) = clock.lease_period_start(current) {
+                named.push(("lease_period_date".to_owned(), date(clock, start)));
+            }
+            if let Some(end) = current
+                .checked_add(count)
+                .and_then(|p| clock.lease_period_start(p))
+            {
+                named.push(("leases_end_date".to_owned(), date(clock, end)));
+            }
+            let leases = std::mem::replace(value, number(0));
+            named.push(("leases".to_owned(), leases));
+            value.value = ValueDef::Composite(Composite::Named(named));
+        }
+        _ => {}
+    }
+}
+
+fn add_vesting_dates(value: &mut Value<String>, clock: &Clock) {
+    let vals = match &mut value.value {
+        ValueDef::Composite(Composite::Named(vals)) => vals,
+        ValueDef::Composite(Composite::Unnamed(vals)) => {
+            vals.iter_mut().for_each(|v| add_vesting_dates(v, clock));
+            return;
+        }
+        _ => return,
+    };
+    let field = |name: &str| {
+        vals.iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.as_u128())
+    };
+    let (Some(locked), Some(per_block), Some(starting_block)) =
+        (field("locked"), field("per_block"), field("starting_block"))
+    else {
+        return;
+    };
+    let Ok(starting_block) = u32::try_from(starting_block) else {
+        return;
+    };
+    vals.push(("starting_date".to_owned(), date(clock, starting_block)));
+    // Everything is unlocked once per_block has been unlocked enough times.
+    let ending_block = locked
+        .checked_div(per_block)
+        .map(|blocks| blocks + u128::from(locked % per_block != 0))
+        .and_then(|blocks| u32::try_from(blocks).ok())
+        .and_then(|blocks| starting_block.checked_add(blocks));
+    if let Some(ending_block) = ending_block {
+        vals.push(("ending_block".to_owned(), number(ending_block)));
+        vals.push(("ending_date".to_owned(), date(clock, ending_block)));
+    }
+}
+
+fn add_fund_dates(value: &mut Value<String>, clock: &Clock) {
+    let ValueDef::Composite(Composite::Named(vals)) = &mut value.value else {
+        return;
+    };
+    let field = |name: &str| {
+        vals.iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| as_u32(v))
+    };
+    let end = field("end");
+    let first_period_start = field("first_period").and_then(|p| clock.lease_period_start(p));
+    let last_period_end = field("last_period")
+        .and_then(|p| p.checked_add(1))
+        .and_then(|p| clock.lease_period_start(p));
+
+    if let Some(end) = end {
+        vals.push(("end_date".to_owned(), date(clock, end)));
+    }
+    if let Some(start) = first_period_start {
+        vals.push(("first_period_date".to_owned(), date(clock, start)));
+    }
+    if let Some(end) = last_period_end {
+        vals.push(("last_period_end_date".to_owned(), date(clock, end)));
+    }
+}
+
+fn as_u32(value: &Value<String>) -> Option<u32> {
+    value.as_u128()?.try_into().ok()
+}
+
+fn number(n: u32) -> Value<String> {
+    Value::u128(n.into()).map_context(|_| String::new())
+}
+
+fn date(clock: &Clock, block_number: u32) -> Value<String> {
+    Value {
+        value: ValueDef::Primitive(Primitive::String(clock.date_of_block(block_number))),
+        context: String::new(),
+    }
+}
+
+/// Format some number of milliseconds since the Unix epoch as a UTC date and time, to the minute.
+fn format_date(ms: i128) -> String {
+    let minutes = ms.div_euclid(60_000);
+    let (days, minute_of_day) = (minutes.div_euclid(24 * 60), minutes.rem_euclid(24 * 60));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        minute_of_day / 60,
+        minute_of_day % 60
+    )
+}
+
+/// The (year, month, day) that is some number of days after 1970-01-01, in the proleptic
+/// Gregorian calendar (see <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>).
+fn civil_from_days(days: i128) -> (i128, i128, i128) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i128::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_value::At;
+
+    fn with_ctx(value: Value<()>) -> Value<String> {
+        value.map_context(|_| String::new())
+    }
+
+    // Block 10,000,000 at 2022-05-08 00:00 UTC, with 12 week lease periods offset by 1000 blocks.
+    const CLOCK: Clock = Clock {
+        block_number: 10_000_000,
+        timestamp_ms: 1_651_968_000_000,
+        lease_periods: Some(LeasePeriods {
+            length: 1_209_600,
+            offset: 1000,
+        }),
+    };
+
+    fn str_at<'a>(value: &'a Value<String>, field: &str) -> Option<&'a str> {
+        value.at(field)?.as_str()
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_date(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_date(951_782_400_000), "2000-02-29 00:00 UTC");
+        assert_eq!(format_date(1_590_507_378_000), "2020-05-26 15:36 UTC");
+        assert_eq!(format_date(-60_000), "1969-12-31 23:59 UTC");
+
+        assert_eq!(CLOCK.date_of_block(10_000_000), "2022-05-08 00:00 UTC");
+        // 14,400 blocks a day.
+        assert_eq!(CLOCK.date_of_block(10_014_400), "2022-05-09 00:00 UTC");
+        assert_eq!(CLOCK.date_of_block(9_985_610), "2022-05-07 00:01 UTC");
+    }
+
+    #[test]
+    fn adds_vesting_dates() {
+        let schedule = Value::named_composite([
+            ("locked", Value::u128(100)),
+            ("per_block", Value::u128(3)),
+            ("starting_block", Value::u128(10_014_400)),
+        ]);
+        let mut value = with_ctx(Value::unnamed_composite([schedule]));
+        add_schedule_dates("Vesting", "Vesting", &mut value, &CLOCK);
+
+        let schedule = value.at(0).unwrap();
+        assert_eq!(
+            str_at(schedule, "starting_date"),
+            Some("2022-05-09 00:00 UTC")
+        );
+        assert_eq!(
+            schedule.at("ending_block").and_then(|v| v.as_u128()),
+            Some(10_014_434)
+        );
+        assert!(str_at(schedule, "ending_date").is_some());
+    }
+
+    #[test]
+    fn adds_auction_and_lease_dates() {
+        let mut auction = with_ctx(Value::unnamed_composite([
+            Value::u128(7),
+            Value::u128(10_014_400),
+        ]));
+        add_schedule_dates("Auctions", "AuctionInfo", &mut auction, &CLOCK);
+        assert_eq!(
+            auction.at("lease_period").and_then(|v| v.as_u128()),
+            Some(7)
+        );
+        assert_eq!(
+            str_at(&auction, "ending_period_start_date"),
+            Some("2022-05-09 00:00 UTC")
+        );
+        assert!(str_at(&auction, "lease_period_date").is_some());
+
+        let none = Value::unnamed_variant("None", []);
+        let mut leases = with_ctx(Value::unnamed_composite([none.clone(), none]));
+        add_schedule_dates("Slots", "Leases", &mut leases, &CLOCK);
+        // Block 10,000,000 is 9,999,000 blocks into the lease periods, so in period 8.
+        assert_eq!(leases.at("lease_period").and_then(|v| v.as_u128()), Some(8));
+        assert!(str_at(&leases, "leases_end_date").is_some());
+        assert!(leases.at("leases").and_then(|l| l.at(1)).is_some());
+    }
+
+    #[test]
+    fn leaves_unexpected_values_alone() {
+        let mut value = with_ctx(Value::unnamed_composite([Value::u128(1)]));
+        let original = value.clone();
+        add_schedule_dates("Auctions", "AuctionInfo", &mut value, &CLOCK);
+        add_schedule_dates("Vesting", "Vesting", &mut value, &CLOCK);
+        assert_eq!(value, original);
+    }
+}
@@ -0,0 +1,179 @@
+use scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// The key types in each validator's session keys, by how many there are. Before V14
+/// metadata, the types file gives the session keys as an anonymous tuple of public keys, and
+/// new key types were added to it over time (the parachain validator key was also renamed
+/// along the way), so this is how we know which key is which.
+const SESSION_KEY_TYPES: &[&[&str]] = &[
+    &["grandpa", "babe", "im_online", "parachain_validator"],
+    &[
+        "grandpa",
+        "babe",
+        "im_online",
+        "parachain_validator",
+        "authority_discovery",
+    ],
+    &[
+        "grandpa",
+        "babe",
+        "im_online",
+        "para_validator",
+        "para_assignment",
+        "authority_discovery",
+    ],
+    &[
+        "grandpa",
+        "babe",
+        "im_online",
+        "para_validator",
+        "para_assignment",
+        "authority_discovery",
+        "beefy",
+    ],
+];
+
+/// Does this storage entry contain session keys?
+pub fn has_session_keys(pallet: &str, entry: &str) -> bool {
+    matches!(
+        (pallet, entry),
+        ("Session", "QueuedKeys" | "NextKeys" | "NextKeyFor")
+    )
+}
+
+/// Is this storage entry a set of validators (or their session keys), which is easier to make
+/// sense of knowing which era and session it was decoded in?
+pub fn is_validator_set(pallet: &str, entry: &str) -> bool {
+    has_session_keys(pallet, entry)
+        || matches!(
+            (pallet, entry),
+            ("Session", "Validators" | "DisabledValidators")
+                | (
+                    "Staking",
+                    "Validators" | "CurrentElected" | "SnapshotValidators"
+                )
+        )
+}
+
+/// Label the session keys in some decoded storage value with their key types, if they were
+/// decoded as an anonymous tuple. Session keys decoded using V14+ metadata are already named.
+pub fn label_session_keys(pallet: &str, entry: &str, value: &mut Value<String>) {
+    match (pallet, entry) {
+        // A list of (validator, keys) for the next session.
+        ("Session", "QueuedKeys") => {
+            let ValueDef::Composite(Composite::Unnamed(queued)) = &mut value.value else {
+                return;
+            };
+            for validator_keys in queued {
+                if let ValueDef::Composite(Composite::Unnamed(vals)) = &mut validator_keys.value {
+                    if let [_, keys] = vals.as_mut_slice() {
+                        label_keys(keys);
+                    }
+                }
+            }
+        }
+        // Keyed by validator.
+        ("Session", "NextKeys" | "NextKeyFor") => label_keys(value),
+        _ => {}
+    }
+}
+
+fn label_keys(keys: &mut Value<String>) {
+    let ValueDef::Composite(Composite::Unnamed(vals)) = &mut keys.value else {
+        return;
+    };
+    let Some(names) = SESSION_KEY_TYPES
+        .iter()
+        .find(|names| names.len() == vals.len())
+    else {
+        return;
+    };
+    if !vals.iter().all(is_public_key) {
+        return;
+    }
+    let named = names
+        .iter()
+        .map(|name| name.to_string())
+        .zip(std::mem::take(vals))
+        .collect();
+    keys.value = ValueDef::Composite(Composite::Named(named));
+}
+
+/// Public keys are 32 bytes, or strings if they were formatted as addresses.
+fn is_public_key(value: &Value<String>) -> bool {
+    matches!(value.value, ValueDef::Primitive(Primitive::String(_)))
+        || crate::utils::value_to_bytes(value).is_some_and(|bytes| bytes.len() == 32)
+}
+
+/// Find the era or session index in the value of `Staking.ActiveEra` (an `ActiveEraInfo`
+/// whose `index` is the era), `Staking.CurrentEra` (once an `Option`) or
+/// `Session.CurrentIndex`.
+pub fn index_of(value: &Value<String>) -> Option<u32> {
+    if let Some(index) = value.as_u128() {
+        return index.try_into().ok();
+    }
+    let inner = match &value.value {
+        ValueDef::Composite(Composite::Named(vals)) => vals
+            .iter()
+            .find(|(name, _)| name == "index")
+            .map(|(_, v)| v),
+        ValueDef::Variant(variant) if variant.name == "Some" => variant.values.values().next(),
+        _ => None,
+    };
+    inner.and_then(index_of)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn with_ctx(value: Value<()>) -> Value<String> {
+        value.map_context(|_| String::new())
+    }
+
+    fn keys(n: u8) -> Value<()> {
+        Value::unnamed_composite((0..n).map(|i| Value::from_bytes([i; 32])))
+    }
+
+    #[test]
+    fn labels_queued_keys() {
+        let mut value = with_ctx(Value::unnamed_composite([Value::unnamed_composite([
+            Value::from_bytes([9; 32]),
+            keys(6),
+        ])]));
+        label_session_keys("Session", "QueuedKeys", &mut value);
+
+        let ValueDef::Composite(Composite::Unnamed(queued)) = &value.value else {
+            panic!("queued keys should be a list");
+        };
+        let ValueDef::Composite(Composite::Unnamed(vals)) = &queued[0].value else {
+            panic!("queued keys should be (validator, keys)");
+        };
+        let ValueDef::Composite(Composite::Named(keys)) = &vals[1].value else {
+            panic!("keys should be labelled");
+        };
+        let names: Vec<_> = keys.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, SESSION_KEY_TYPES[2]);
+    }
+
+    #[test]
+    fn leaves_unknown_keys_alone() {
+        let mut value = with_ctx(keys(3));
+        label_session_keys("Session", "NextKeys", &mut value);
+        assert!(matches!(
+            value.value,
+            ValueDef::Composite(Composite::Unnamed(_))
+        ));
+    }
+
+    #[test]
+    fn finds_era_indexes() {
+        let active_era = with_ctx(Value::named_composite([
+            ("index", Value::u128(12)),
+            ("start", Value::unnamed_variant("None", [])),
+        ]));
+        assert_eq!(index_of(&active_era), Some(12));
+        let current_era = with_ctx(Value::unnamed_variant("Some", [Value::u128(13)]));
+        assert_eq!(index_of(&current_era), Some(13));
+        assert_eq!(index_of(&with_ctx(Value::u128(14))), Some(14));
+    }
+}
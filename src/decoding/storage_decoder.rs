@@ -1,4 +1,8 @@
-use anyhow::bail;
+use super::decode_limits::{decode_value, limit_exceeded_of};
+use super::decode_options::DecodeOptions;
+use super::layout_hints::layout_hint;
+use crate::utils::error_category::ErrorCategory;
+use anyhow::{bail, Context};
 use frame_decode::storage::StorageHasher;
 use frame_metadata::RuntimeMetadata;
 use scale_info_legacy::TypeRegistrySet;
@@ -9,8 +13,13 @@ pub type StorageKeys = Vec<StorageKey>;
 
 /// The decoded representation of a storage key.
 pub struct StorageKey {
+    /// The name given to this key in the types file, if any.
+    pub name: Option<String>,
     pub hash: Vec<u8>,
     pub value: Option<scale_value::Value<String>>,
+    /// The SCALE encoded bytes of the value, which follow the hash in the key for concat and
+    /// identity hashers. Empty for other hashers.
+    pub value_bytes: Vec<u8>,
     pub hasher: frame_decode::storage::StorageHasher,
 }
 
@@ -21,34 +30,38 @@ pub fn decode_storage_keys(
     bytes: &[u8],
     metadata: &RuntimeMetadata,
     historic_types: &TypeRegistrySet,
+    opts: &DecodeOptions,
 ) -> anyhow::Result<StorageKeys> {
-    match metadata {
+    crate::utils::catch_panic(|| match metadata {
         RuntimeMetadata::V8(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V9(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V10(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V11(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V12(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V13(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V14(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, &m.types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, &m.types, opts)
         }
         RuntimeMetadata::V15(m) => {
-            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, &m.types)
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, &m.types, opts)
         }
-        _ => bail!("Only metadata V8 - V15 is supported"),
-    }
+        RuntimeMetadata::V16(m) => {
+            decode_storage_keys_inner(pallet_name, storage_entry, bytes, m, &m.types, opts)
+        }
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    })
 }
 
 /// Decode the bytes representing some storage value.
@@ -58,34 +71,38 @@ pub fn decode_storage_value(
     bytes: &[u8],
     metadata: &RuntimeMetadata,
     historic_types: &TypeRegistrySet,
+    opts: &DecodeOptions,
 ) -> anyhow::Result<StorageValue> {
-    match metadata {
+    crate::utils::catch_panic(|| match metadata {
         RuntimeMetadata::V8(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V9(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V10(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V11(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V12(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V13(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, historic_types, opts)
         }
         RuntimeMetadata::V14(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, &m.types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, &m.types, opts)
         }
         RuntimeMetadata::V15(m) => {
-            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, &m.types)
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, &m.types, opts)
         }
-        _ => bail!("Only metadata V8 - V15 is supported"),
-    }
+        RuntimeMetadata::V16(m) => {
+            decode_storage_value_inner(pallet_name, storage_entry, bytes, m, &m.types, opts)
+        }
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    })
 }
 
 fn decode_storage_keys_inner<Info, Resolver>(
@@ -94,6 +111,7 @@ fn decode_storage_keys_inner<Info, Resolver>(
     bytes: &[u8],
     info: &Info,
     type_resolver: &Resolver,
+    opts: &DecodeOptions,
 ) -> anyhow::Result<StorageKeys>
 where
     Info: frame_decode::storage::StorageTypeInfo,
@@ -109,62 +127,88 @@ where
         type_resolver,
     )?;
 
+    let names = super::historic_types::storage_key_names(pallet_name, storage_entry);
     let decoded: anyhow::Result<_> = key_info
         .parts()
-        .map(|part| {
+        .enumerate()
+        .map(|(idx, part)| {
             let hash = bytes[part.hash_range()].to_vec();
             let hasher = part.hasher();
+            let value_bytes = part
+                .value()
+                .map_or_else(Vec::new, |val_info| bytes[val_info.range()].to_vec());
             let value = part
                 .value()
                 .map(|val_info| {
-                    let value = scale_value::scale::decode_as_type(
-                        &mut &bytes[val_info.range()],
+                    decode_value(
+                        &mut &*value_bytes,
                         val_info.ty().clone(),
                         type_resolver,
-                    )?
-                    .map_context(|id| id.to_string());
-                    anyhow::Result::<scale_value::Value<String>>::Ok(value)
+                        opts,
+                    )
                 })
                 .transpose()?;
 
+            // A key of the wrong type can happen to decode from the right number of bytes, but
+            // then won't hash to the hash in front of it.
+            if !key_hash_matches(hasher, &hash, &value_bytes) {
+                return Err(ErrorCategory::Corrupt.error(format!(
+                    "Key {idx} has the {} hash {}, but its value {} hashes to {}",
+                    hasher_name(hasher),
+                    subxt::utils::to_hex(&hash),
+                    subxt::utils::to_hex(&value_bytes),
+                    subxt::utils::to_hex(&hash_storage_key(hasher, &value_bytes)[..hash.len()])
+                )));
+            }
+
             Ok(StorageKey {
+                name: names.get(idx).cloned(),
                 hash,
                 value,
+                value_bytes,
                 hasher,
             })
         })
         .collect();
 
     if !cursor.is_empty() && decoded.is_ok() {
-        let decoded = print_storage_key_res(&decoded)?;
-        bail!(
+        let decoded = print_storage_key_res(&decoded, opts)?;
+        return Err(ErrorCategory::LeftoverBytes.error(format!(
             "{} leftover bytes decoding storage keys: {cursor:?}. decoded: {decoded}",
             cursor.len()
-        );
+        )));
     }
 
     decoded
 }
 
-fn print_storage_key_res(keys: &anyhow::Result<StorageKeys>) -> anyhow::Result<String> {
+fn print_storage_key_res(
+    keys: &anyhow::Result<StorageKeys>,
+    opts: &DecodeOptions,
+) -> anyhow::Result<String> {
     match keys {
         Err(e) => Ok(format!("Error: {e}")),
         Ok(keys) => {
             let mut s = String::new();
-            write_storage_keys_fmt(&mut s, keys)?;
+            write_storage_keys_fmt(&mut s, keys, opts)?;
             Ok(s)
         }
     }
 }
 
-pub fn write_storage_keys<W: std::io::Write>(writer: W, keys: &[StorageKey]) -> anyhow::Result<()> {
+pub fn write_storage_keys<W: std::io::Write>(
+    writer: W,
+    keys: &[StorageKey],
+    opts: &DecodeOptions,
+) -> anyhow::Result<()> {
     let writer = crate::utils::ToFmtWrite(writer);
-    write_storage_keys_fmt(writer, keys)
+    write_storage_keys_fmt(writer, keys, opts)
 }
 
 pub fn write_storage_keys_fmt<W: std::fmt::Write>(
     mut writer: W,
     keys: &[StorageKey],
+    opts: &DecodeOptions,
 ) -> anyhow::Result<()> {
     // Plain entries have no keys:
     if keys.is_empty() {
@@ -173,10 +217,13 @@ pub fn write_storage_keys_fmt<W: std::fmt::Write>(
     }
 
     // blake2: AccountId(0x2331) + ident: Foo(123) + blake2:0x23edbfe
-    for (idx, key) in keys.into_iter().enumerate() {
+    for (idx, key) in keys.iter().enumerate() {
         if idx != 0 {
             write!(&mut writer, " + ")?;
         }
+        if let Some(name) = &key.name {
+            write!(&mut writer, "{name}: ")?;
+        }
 
         match (key.hasher, &key.value) {
             (StorageHasher::Blake2_128, None) => {
@@ -189,7 +236,7 @@ pub fn write_storage_keys_fmt<W: std::fmt::Write>(
             }
             (StorageHasher::Blake2_128Concat, Some(value)) => {
                 write!(&mut writer, "blake2_128_concat: ")?;
-                crate::utils::write_compact_value_fmt(&mut writer, &value)?;
+                crate::utils::write_compact_value_fmt(&mut writer, value, opts)?;
             }
             (StorageHasher::Twox128, None) => {
                 write!(&mut writer, "twox_128: ")?;
@@ -201,11 +248,11 @@ pub fn write_storage_keys_fmt<W: std::fmt::Write>(
             }
             (StorageHasher::Twox64Concat, Some(value)) => {
                 write!(&mut writer, "twox64_concat: ")?;
-                crate::utils::write_compact_value_fmt(&mut writer, &value)?;
+                crate::utils::write_compact_value_fmt(&mut writer, value, opts)?;
             }
             (StorageHasher::Identity, Some(value)) => {
                 write!(&mut writer, "ident: ")?;
-                crate::utils::write_compact_value_fmt(&mut writer, &value)?;
+                crate::utils::write_compact_value_fmt(&mut writer, value, opts)?;
             }
             _ => {
                 bail!("Invalid storage hasher/value pair")
@@ -216,37 +263,506 @@ pub fn write_storage_keys_fmt<W: std::fmt::Write>(
     Ok(())
 }
 
+/// A short name for the given storage hasher.
+pub fn hasher_name(hasher: StorageHasher) -> &'static str {
+    match hasher {
+        StorageHasher::Blake2_128 => "blake2_128",
+        StorageHasher::Blake2_256 => "blake2_256",
+        StorageHasher::Blake2_128Concat => "blake2_128_concat",
+        StorageHasher::Twox128 => "twox_128",
+        StorageHasher::Twox256 => "twox_256",
+        StorageHasher::Twox64Concat => "twox64_concat",
+        StorageHasher::Identity => "ident",
+    }
+}
+
+/// The hashers used for each key of some storage entry. This is empty for plain storage values.
+pub fn storage_key_hashers(
+    pallet_name: &str,
+    storage_entry: &str,
+    metadata: &RuntimeMetadata,
+) -> anyhow::Result<Vec<StorageHasher>> {
+    use frame_decode::storage::StorageTypeInfo;
+    fn hashers<Info: StorageTypeInfo>(
+        pallet_name: &str,
+        storage_entry: &str,
+        info: &Info,
+    ) -> anyhow::Result<Vec<StorageHasher>> {
+        let storage_info = info
+            .get_storage_info(pallet_name, storage_entry)
+            .map_err(|e| ErrorCategory::TypeResolution.error(e))?;
+        Ok(storage_info.keys.iter().map(|k| k.hasher).collect())
+    }
+
+    match metadata {
+        RuntimeMetadata::V8(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V9(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V10(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V11(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V12(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V13(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V14(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V15(m) => hashers(pallet_name, storage_entry, m),
+        RuntimeMetadata::V16(m) => hashers(pallet_name, storage_entry, m),
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    }
+}
+
+/// The names of the types of each key of some storage entry, and of its value.
+pub fn storage_type_names(
+    pallet_name: &str,
+    storage_entry: &str,
+    metadata: &RuntimeMetadata,
+) -> anyhow::Result<(Vec<String>, String)> {
+    use frame_decode::storage::StorageTypeInfo;
+    fn type_names<Info>(
+        pallet_name: &str,
+        storage_entry: &str,
+        info: &Info,
+    ) -> anyhow::Result<(Vec<String>, String)>
+    where
+        Info: StorageTypeInfo,
+        Info::TypeId: core::fmt::Display,
+    {
+        let storage_info = info
+            .get_storage_info(pallet_name, storage_entry)
+            .map_err(|e| ErrorCategory::TypeResolution.error(e))?;
+        let keys = storage_info
+            .keys
+            .iter()
+            .map(|k| k.key_id.to_string())
+            .collect();
+        Ok((keys, storage_info.value_id.to_string()))
+    }
+
+    match metadata {
+        RuntimeMetadata::V8(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V9(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V10(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V11(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V12(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V13(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V14(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V15(m) => type_names(pallet_name, storage_entry, m),
+        RuntimeMetadata::V16(m) => type_names(pallet_name, storage_entry, m),
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    }
+}
+
+/// What the metadata says about some storage entry, to help make sense of its values.
+pub struct StorageEntryInfo {
+    pub docs: Vec<String>,
+    /// `Optional` if reading the entry returns nothing when there's nothing stored at it, or
+    /// `Default` if it returns the entry's default value.
+    pub modifier: String,
+    pub key_types: Vec<String>,
+    pub value_type: String,
+}
+
+/// Find the docs, modifier and type names of some storage entry. In V14+ metadata, types are
+/// named by their path where they have one, rather than by their ID.
+pub fn storage_entry_info(
+    pallet_name: &str,
+    storage_entry: &str,
+    metadata: &RuntimeMetadata,
+) -> anyhow::Result<StorageEntryInfo> {
+    use super::decoded;
+
+    macro_rules! legacy_info {
+        ($m:ident) => {
+            decoded(&$m.modules)
+                .iter()
+                .find(|module| decoded(&module.name) == pallet_name)
+                .and_then(|module| module.storage.as_ref())
+                .and_then(|storage| {
+                    decoded(&decoded(storage).entries)
+                        .iter()
+                        .find(|entry| decoded(&entry.name) == storage_entry)
+                })
+                .map(|entry| {
+                    (
+                        decoded(&entry.documentation).clone(),
+                        format!("{:?}", entry.modifier),
+                    )
+                })
+        };
+    }
+    macro_rules! modern_info {
+        ($m:ident) => {
+            $m.pallets
+                .iter()
+                .find(|pallet| pallet.name == pallet_name)
+                .and_then(|pallet| pallet.storage.as_ref())
+                .and_then(|storage| storage.entries.iter().find(|e| e.name == storage_entry))
+                .map(|entry| (entry.docs.clone(), format!("{:?}", entry.modifier)))
+        };
+    }
+
+    let (info, types) = match metadata {
+        RuntimeMetadata::V8(m) => (legacy_info!(m), None),
+        RuntimeMetadata::V9(m) => (legacy_info!(m), None),
+        RuntimeMetadata::V10(m) => (legacy_info!(m), None),
+        RuntimeMetadata::V11(m) => (legacy_info!(m), None),
+        RuntimeMetadata::V12(m) => (legacy_info!(m), None),
+        RuntimeMetadata::V13(m) => (legacy_info!(m), None),
+        RuntimeMetadata::V14(m) => (modern_info!(m), Some(&m.types)),
+        RuntimeMetadata::V15(m) => (modern_info!(m), Some(&m.types)),
+        RuntimeMetadata::V16(m) => (modern_info!(m), Some(&m.types)),
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    };
+    let (docs, modifier) = info.ok_or_else(|| {
+        ErrorCategory::MissingData.error(format!(
+            "Storage entry {pallet_name}.{storage_entry} not found"
+        ))
+    })?;
+
+    let (key_types, value_type) = storage_type_names(pallet_name, storage_entry, metadata)?;
+    let type_name = |name: String| match types {
+        Some(types) => name
+            .parse()
+            .map_or(name, |id| portable_type_name(types, id)),
+        None => name,
+    };
+
+    Ok(StorageEntryInfo {
+        docs,
+        modifier,
+        key_types: key_types.into_iter().map(type_name).collect(),
+        value_type: type_name(value_type),
+    })
+}
+
+/// A readable name for some type in a V14+ type registry.
+fn portable_type_name(types: &scale_info::PortableRegistry, id: u32) -> String {
+    use scale_info::{TypeDef, TypeDefPrimitive};
+
+    let Some(ty) = types.resolve(id) else {
+        return format!("<unknown type {id}>");
+    };
+    if let Some(name) = ty.path.segments.last() {
+        let params: Vec<_> = ty
+            .type_params
+            .iter()
+            .filter_map(|param| param.ty.map(|param| portable_type_name(types, param.id)))
+            .collect();
+        return match params.as_slice() {
+            [] => name.clone(),
+            params => format!("{name}<{}>", params.join(", ")),
+        };
+    }
+
+    match &ty.type_def {
+        TypeDef::Sequence(seq) => format!("Vec<{}>", portable_type_name(types, seq.type_param.id)),
+        TypeDef::Array(arr) => format!(
+            "[{}; {}]",
+            portable_type_name(types, arr.type_param.id),
+            arr.len
+        ),
+        TypeDef::Tuple(tuple) => {
+            let fields: Vec<_> = tuple
+                .fields
+                .iter()
+                .map(|field| portable_type_name(types, field.id))
+                .collect();
+            format!("({})", fields.join(", "))
+        }
+        TypeDef::Compact(compact) => {
+            format!(
+                "Compact<{}>",
+                portable_type_name(types, compact.type_param.id)
+            )
+        }
+        TypeDef::BitSequence(_) => "BitVec".to_owned(),
+        TypeDef::Primitive(primitive) => match primitive {
+            TypeDefPrimitive::Bool => "bool",
+            TypeDefPrimitive::Char => "char",
+            TypeDefPrimitive::Str => "String",
+            TypeDefPrimitive::U8 => "u8",
+            TypeDefPrimitive::U16 => "u16",
+            TypeDefPrimitive::U32 => "u32",
+            TypeDefPrimitive::U64 => "u64",
+            TypeDefPrimitive::U128 => "u128",
+            TypeDefPrimitive::U256 => "u256",
+            TypeDefPrimitive::I8 => "i8",
+            TypeDefPrimitive::I16 => "i16",
+            TypeDefPrimitive::I32 => "i32",
+            TypeDefPrimitive::I64 => "i64",
+            TypeDefPrimitive::I128 => "i128",
+            TypeDefPrimitive::I256 => "i256",
+        }
+        .to_owned(),
+        TypeDef::Composite(_) | TypeDef::Variant(_) => format!("<type {id}>"),
+    }
+}
+
+/// The encoded default value of some storage entry, as given in the metadata. This is what
+/// reading the entry returns if nothing is stored at it.
+pub fn storage_default_bytes(
+    pallet_name: &str,
+    storage_entry: &str,
+    metadata: &RuntimeMetadata,
+) -> anyhow::Result<Vec<u8>> {
+    use super::decoded;
+
+    macro_rules! legacy_default {
+        ($m:ident) => {
+            decoded(&$m.modules)
+                .iter()
+                .find(|module| decoded(&module.name) == pallet_name)
+                .and_then(|module| module.storage.as_ref())
+                .and_then(|storage| {
+                    decoded(&decoded(storage).entries)
+                        .iter()
+                        .find(|entry| decoded(&entry.name) == storage_entry)
+                })
+                .map(|entry| decoded(&entry.default).clone())
+        };
+    }
+    macro_rules! modern_default {
+        ($m:ident) => {
+            $m.pallets
+                .iter()
+                .find(|pallet| pallet.name == pallet_name)
+                .and_then(|pallet| pallet.storage.as_ref())
+                .and_then(|storage| storage.entries.iter().find(|e| e.name == storage_entry))
+                .map(|entry| entry.default.clone())
+        };
+    }
+
+    let default = match metadata {
+        RuntimeMetadata::V8(m) => legacy_default!(m),
+        RuntimeMetadata::V9(m) => legacy_default!(m),
+        RuntimeMetadata::V10(m) => legacy_default!(m),
+        RuntimeMetadata::V11(m) => legacy_default!(m),
+        RuntimeMetadata::V12(m) => legacy_default!(m),
+        RuntimeMetadata::V13(m) => legacy_default!(m),
+        RuntimeMetadata::V14(m) => modern_default!(m),
+        RuntimeMetadata::V15(m) => modern_default!(m),
+        RuntimeMetadata::V16(m) => modern_default!(m),
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    };
+    default.ok_or_else(|| {
+        ErrorCategory::MissingData.error(format!(
+            "Storage entry {pallet_name}.{storage_entry} not found"
+        ))
+    })
+}
+
+/// Encode the full storage key (including the hashed pallet name and storage entry) for some
+/// storage entry, given a value for each of its keys.
+pub fn encode_storage_key(
+    pallet_name: &str,
+    storage_entry: &str,
+    keys: &[scale_value::Value],
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet,
+) -> anyhow::Result<Vec<u8>> {
+    encode_storage_key_any(
+        pallet_name,
+        storage_entry,
+        keys,
+        false,
+        metadata,
+        historic_types,
+    )
+}
+
+/// Encode the prefix of the storage key shared by every item in some storage map whose first
+/// keys are the ones given. Fewer values than the entry has keys can be given.
+pub fn encode_storage_key_prefix(
+    pallet_name: &str,
+    storage_entry: &str,
+    keys: &[scale_value::Value],
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet,
+) -> anyhow::Result<Vec<u8>> {
+    encode_storage_key_any(
+        pallet_name,
+        storage_entry,
+        keys,
+        true,
+        metadata,
+        historic_types,
+    )
+}
+
+fn encode_storage_key_any(
+    pallet_name: &str,
+    storage_entry: &str,
+    keys: &[scale_value::Value],
+    partial: bool,
+    metadata: &RuntimeMetadata,
+    historic_types: &TypeRegistrySet,
+) -> anyhow::Result<Vec<u8>> {
+    match metadata {
+        RuntimeMetadata::V8(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, historic_types)
+        }
+        RuntimeMetadata::V9(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, historic_types)
+        }
+        RuntimeMetadata::V10(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, historic_types)
+        }
+        RuntimeMetadata::V11(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, historic_types)
+        }
+        RuntimeMetadata::V12(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, historic_types)
+        }
+        RuntimeMetadata::V13(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, historic_types)
+        }
+        RuntimeMetadata::V14(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, &m.types)
+        }
+        RuntimeMetadata::V15(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, &m.types)
+        }
+        RuntimeMetadata::V16(m) => {
+            encode_storage_key_inner(pallet_name, storage_entry, keys, partial, m, &m.types)
+        }
+        _ => bail!("Only metadata V8 - V16 is supported"),
+    }
+}
+
+fn encode_storage_key_inner<Info, Resolver>(
+    pallet_name: &str,
+    storage_entry: &str,
+    keys: &[scale_value::Value],
+    partial: bool,
+    info: &Info,
+    type_resolver: &Resolver,
+) -> anyhow::Result<Vec<u8>>
+where
+    Info: frame_decode::storage::StorageTypeInfo,
+    Info::TypeId: Clone + core::fmt::Debug,
+    Resolver: TypeResolver<TypeId = Info::TypeId>,
+{
+    let storage_info = info
+        .get_storage_info(pallet_name, storage_entry)
+        .map_err(|e| ErrorCategory::TypeResolution.error(e))?;
+    let too_many = keys.len() > storage_info.keys.len();
+    if too_many || (!partial && storage_info.keys.len() != keys.len()) {
+        bail!(
+            "{pallet_name}.{storage_entry} has {} keys, but {} were given",
+            storage_info.keys.len(),
+            keys.len()
+        );
+    }
+
+    let mut storage_key = Vec::new();
+    storage_key.extend(sp_crypto_hashing::twox_128(pallet_name.as_bytes()));
+    storage_key.extend(sp_crypto_hashing::twox_128(storage_entry.as_bytes()));
+    for (idx, (key, key_info)) in keys.iter().zip(&storage_info.keys).enumerate() {
+        let mut key_bytes = Vec::new();
+        scale_value::scale::encode_as_type(
+            key,
+            key_info.key_id.clone(),
+            type_resolver,
+            &mut key_bytes,
+        )
+        .map_err(|e| anyhow::anyhow!("Could not encode key {idx} ({key}): {e}"))?;
+        storage_key.extend(hash_storage_key(key_info.hasher, &key_bytes));
+    }
+    Ok(storage_key)
+}
+
+/// Hash some encoded storage key with the given hasher.
+pub fn hash_storage_key(hasher: StorageHasher, key_bytes: &[u8]) -> Vec<u8> {
+    use sp_crypto_hashing::{blake2_128, blake2_256, twox_128, twox_256, twox_64};
+    match hasher {
+        StorageHasher::Blake2_128 => blake2_128(key_bytes).to_vec(),
+        StorageHasher::Blake2_256 => blake2_256(key_bytes).to_vec(),
+        StorageHasher::Blake2_128Concat => [&blake2_128(key_bytes)[..], key_bytes].concat(),
+        StorageHasher::Twox128 => twox_128(key_bytes).to_vec(),
+        StorageHasher::Twox256 => twox_256(key_bytes).to_vec(),
+        StorageHasher::Twox64Concat => [&twox_64(key_bytes)[..], key_bytes].concat(),
+        StorageHasher::Identity => key_bytes.to_vec(),
+    }
+}
+
+/// For the concat hashers, does the hash in some part of a storage key match the value after
+/// it? Other hashers don't give us a value to check the hash against.
+fn key_hash_matches(hasher: StorageHasher, hash: &[u8], value_bytes: &[u8]) -> bool {
+    match hasher {
+        StorageHasher::Blake2_128Concat | StorageHasher::Twox64Concat => {
+            hash_storage_key(hasher, value_bytes) == [hash, value_bytes].concat()
+        }
+        _ => true,
+    }
+}
+
 fn decode_storage_value_inner<Info, Resolver>(
     pallet_name: &str,
     storage_entry: &str,
     bytes: &[u8],
     info: &Info,
     type_resolver: &Resolver,
+    opts: &DecodeOptions,
 ) -> anyhow::Result<StorageValue>
 where
     Info: frame_decode::storage::StorageTypeInfo,
     Info::TypeId: Clone + core::fmt::Display + core::fmt::Debug + Send + Sync + 'static,
     Resolver: TypeResolver<TypeId = Info::TypeId>,
 {
-    let cursor = &mut &*bytes;
-    let value = frame_decode::storage::decode_storage_value(
-        pallet_name,
-        storage_entry,
-        cursor,
-        info,
-        type_resolver,
-        scale_value::scale::ValueVisitor::new(),
-    )?
-    .map_context(|id| id.to_string());
+    let decode = || {
+        let cursor = &mut &*bytes;
+        let value_id = info
+            .get_storage_info(pallet_name, storage_entry)
+            .map_err(|e| {
+                ErrorCategory::TypeResolution.error(format!("Cannot get storage info:\n\n{e}"))
+            })?
+            .value_id;
+        // We don't use frame_decode to decode the value, because it decodes values again to
+        // explain any errors, and that could use any amount of memory.
+        let value = decode_value(cursor, value_id.clone(), type_resolver, opts)
+            .with_context(|| format!("Cannot decode value with type ID {value_id:?}"))?;
 
-    if !cursor.is_empty() {
-        let mut value_string = String::new();
-        crate::utils::write_value_fmt(&mut value_string, &value)?;
-        bail!(
-            "{} leftover bytes decoding storage value: {cursor:?}. decoded:\n\n{value_string}",
-            cursor.len()
-        );
-    }
+        if !cursor.is_empty() {
+            let mut value_string = String::new();
+            crate::utils::write_value_fmt(&mut value_string, &value, opts)?;
+            return Err(ErrorCategory::LeftoverBytes.error(format!(
+                "{} leftover bytes decoding storage value: {cursor:?}. decoded:\n\n{value_string}",
+                cursor.len()
+            )));
+        }
+        Ok(value)
+    };
 
-    Ok(value)
+    // If decoding failed in a way that a common layout change explains, say so. Values that
+    // were too large to decode won't decode any better in other ways.
+    decode().map_err(|e| {
+        if limit_exceeded_of(&e).is_some() {
+            return e;
+        }
+        let hint = info
+            .get_storage_info(pallet_name, storage_entry)
+            .ok()
+            .and_then(|storage_info| {
+                layout_hint(bytes, storage_info.value_id, type_resolver, opts)
+            });
+        match hint {
+            Some(hint) => e.context(hint),
+            None => e,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checks_concat_key_hashes() {
+        let value = 1234u32.to_le_bytes();
+        for hasher in [StorageHasher::Blake2_128Concat, StorageHasher::Twox64Concat] {
+            let key = hash_storage_key(hasher, &value);
+            let (hash, value_bytes) = key.split_at(key.len() - value.len());
+            assert!(key_hash_matches(hasher, hash, value_bytes));
+            assert!(!key_hash_matches(hasher, hash, &1235u32.to_le_bytes()));
+        }
+        assert!(key_hash_matches(StorageHasher::Identity, &[], &value));
+        assert!(key_hash_matches(StorageHasher::Blake2_128, &[0; 16], &[]));
+    }
 }
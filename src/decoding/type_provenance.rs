@@ -0,0 +1,342 @@
+use super::builtin_types::builtin_types_from_metadata;
+use super::historic_types::SpecRange;
+use frame_metadata::RuntimeMetadata;
+use scale_info::PortableRegistry;
+use scale_info_legacy::{LookupName, TypeRegistry};
+use scale_value::{Value, ValueDef};
+use std::collections::{HashMap, HashSet};
+
+/// Where the definition of some type that was used to decode a value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeSource {
+    /// An entry in the types file. `layer` is the part of the file (`global` or some
+    /// `forSpec` range) and `definition` is what was given there.
+    TypesFile { layer: String, definition: String },
+    /// One of the basic Rust types (`u32`, `Vec<T>` and so on) that are always available.
+    Basic,
+    /// A type that we derive from pre-V14 metadata, like `builtin::Call`.
+    Metadata,
+    /// A type from the type registry in V14+ metadata.
+    Portable { id: u32, path: String },
+    /// The type could not be found anywhere.
+    NotFound,
+}
+
+impl std::fmt::Display for TypeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeSource::TypesFile { layer, definition } => {
+                write!(f, "types file ({layer}): {definition}")
+            }
+            TypeSource::Basic => write!(f, "basic Rust type"),
+            TypeSource::Metadata => write!(f, "derived from metadata"),
+            TypeSource::Portable { id, path } => {
+                write!(f, "metadata type registry (#{id}): {path}")
+            }
+            TypeSource::NotFound => write!(f, "not found"),
+        }
+    }
+}
+
+/// Work out which type registry provided each type used in decoding some value. Types are
+/// looked up in the types file entries for the spec version (later `forSpec` entries first,
+/// then `global`), then the basic Rust types, then the types derived from the metadata. The
+/// first of these to define some type wins, so this helps to find out why some type was
+/// shadowed by another.
+pub struct TypeExplainer {
+    /// The parts of the types file that apply at this spec version, highest precedence first.
+    layers: Vec<TypesFileLayer>,
+    basics: TypeRegistry,
+    builtins: TypeRegistry,
+    portable: Option<PortableRegistry>,
+}
+
+struct TypesFileLayer {
+    name: String,
+    /// The definition of each type, keyed by pallet, name and number of generic params
+    /// (which is how the type registry looks them up).
+    types: HashMap<(Option<String>, String, usize), String>,
+}
+
+impl TypeExplainer {
+    /// Explain the types used to decode things at the given spec version, given the types file
+    /// (see [`super::historic_types::load_historic_types_yaml`]) and the metadata.
+    pub fn new(
+        types_file: &serde_yaml::Value,
+        spec_version: u64,
+        metadata: &RuntimeMetadata,
+    ) -> anyhow::Result<Self> {
+        let portable = match metadata {
+            RuntimeMetadata::V14(m) => Some(m.types.clone()),
+            RuntimeMetadata::V15(m) => Some(m.types.clone()),
+            RuntimeMetadata::V16(m) => Some(m.types.clone()),
+            _ => None,
+        };
+
+        Ok(TypeExplainer {
+            builtins: builtin_types_from_metadata(metadata)?,
+            portable,
+            ..TypeExplainer::from_types_file(types_file, spec_version)
+        })
+    }
+
+    /// Like [`TypeExplainer::new`], but without any of the types that we'd derive from metadata.
+    pub fn from_types_file(types_file: &serde_yaml::Value, spec_version: u64) -> Self {
+        TypeExplainer {
+            layers: types_file_layers(types_file, spec_version),
+            basics: TypeRegistry::basic(),
+            builtins: TypeRegistry::empty(),
+            portable: None,
+        }
+    }
+
+    /// Where the given type came from when looked up in the given pallet. Returns `None` for
+    /// types like tuples and arrays, which are built from other types rather than defined.
+    pub fn source(&self, pallet: Option<&str>, type_name: &str) -> Option<TypeSource> {
+        if let Some(portable) = &self.portable {
+            let id: u32 = type_name.parse().ok()?;
+            let path = match portable.resolve(id) {
+                Some(ty) if ty.path.segments.is_empty() => "(no path)".to_owned(),
+                Some(ty) => ty.path.segments.join("::"),
+                None => return Some(TypeSource::NotFound),
+            };
+            return Some(TypeSource::Portable { id, path });
+        }
+
+        let (name, params) = named_type(type_name)?;
+
+        // Types scoped to the pallet are looked for in every layer before global ones.
+        let scopes = pallet.map(ToOwned::to_owned).into_iter().map(Some);
+        for scope in scopes.chain([None]) {
+            let key = (scope, name.clone(), params);
+            for layer in &self.layers {
+                if let Some(definition) = layer.types.get(&key) {
+                    let layer = match &key.0 {
+                        Some(pallet) => format!("{}, {pallet} pallet", layer.name),
+                        None => layer.name.clone(),
+                    };
+                    return Some(TypeSource::TypesFile {
+                        layer,
+                        definition: definition.clone(),
+                    });
+                }
+            }
+        }
+
+        if is_defined(&self.basics, &name, params) {
+            Some(TypeSource::Basic)
+        } else if is_defined(&self.builtins, &name, params) {
+            Some(TypeSource::Metadata)
+        } else {
+            Some(TypeSource::NotFound)
+        }
+    }
+
+    /// Explain each distinct type used to decode some value, in the order that they appear.
+    /// The value should have been decoded within the given pallet.
+    pub fn explain(
+        &self,
+        pallet: Option<&str>,
+        value: &Value<String>,
+    ) -> Vec<(String, TypeSource)> {
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        self.explain_inner(pallet, value, &mut seen, &mut out);
+        out
+    }
+
+    fn explain_inner(
+        &self,
+        pallet: Option<&str>,
+        value: &Value<String>,
+        seen: &mut HashSet<(Option<String>, String)>,
+        out: &mut Vec<(String, TypeSource)>,
+    ) {
+        let type_name = &value.context;
+        if seen.insert((pallet.map(ToOwned::to_owned), type_name.clone())) {
+            if let Some(source) = self.source(pallet, type_name) {
+                out.push((type_name.clone(), source));
+            }
+        }
+
+        // The arguments of calls and events that we derive from the metadata are looked up
+        // in the pallet that the call or event belongs to.
+        let pallet = type_name
+            .strip_prefix("builtin::module::call::")
+            .or_else(|| type_name.strip_prefix("builtin::module::event::"))
+            .or(pallet);
+
+        match &value.value {
+            ValueDef::Composite(composite) => {
+                for value in composite.values() {
+                    self.explain_inner(pallet, value, seen, out);
+                }
+            }
+            ValueDef::Variant(variant) => {
+                for value in variant.values.values() {
+                    self.explain_inner(pallet, value, seen, out);
+                }
+            }
+            ValueDef::BitSequence(_) | ValueDef::Primitive(_) => {}
+        }
+    }
+}
+
+impl TypesFileLayer {
+    /// Gather the `types` and `palletTypes` given in some section of the types file.
+    fn new(name: String, section: &serde_yaml::Value) -> Self {
+        let mut types = HashMap::new();
+        let mut add = |pallet: Option<&str>, defs: &serde_yaml::Value| {
+            let Some(defs) = defs.as_mapping() else {
+                return;
+            };
+            for (key, def) in defs {
+                let Some((name, params)) = key.as_str().and_then(named_type) else {
+                    continue;
+                };
+                let definition = match def.as_str() {
+                    Some(def) => def.to_owned(),
+                    None => serde_json::to_string(def).unwrap_or_default(),
+                };
+                types.insert((pallet.map(ToOwned::to_owned), name, params), definition);
+            }
+        };
+
+        if let Some(defs) = section.get("types") {
+            add(None, defs);
+        }
+        if let Some(pallets) = section.get("palletTypes").and_then(|p| p.as_mapping()) {
+            for (pallet, defs) in pallets {
+                if let Some(pallet) = pallet.as_str() {
+                    add(Some(pallet), defs);
+                }
+            }
+        }
+        TypesFileLayer { name, types }
+    }
+}
+
+/// The parts of the types file that apply at some spec version, highest precedence first.
+fn types_file_layers(types_file: &serde_yaml::Value, spec_version: u64) -> Vec<TypesFileLayer> {
+    let mut layers = Vec::new();
+    if let Some(global) = types_file.get("global") {
+        layers.push(TypesFileLayer::new("global".to_owned(), global));
+    }
+    for entry in types_file
+        .get("forSpec")
+        .and_then(|f| f.as_sequence())
+        .into_iter()
+        .flatten()
+    {
+        let Some(range) = entry
+            .get("range")
+            .and_then(|r| serde_yaml::from_value::<SpecRange>(r.clone()).ok())
+        else {
+            continue;
+        };
+        let (min, max) = (range.0.unwrap_or(0), range.1.unwrap_or(u64::MAX));
+        if spec_version < min || spec_version > max {
+            continue;
+        }
+        let bound = |b: Option<u64>| b.map_or("null".to_owned(), |b| b.to_string());
+        let name = format!("forSpec [{}, {}]", bound(range.0), bound(range.1));
+        layers.push(TypesFileLayer::new(name, entry));
+    }
+    // Later entries take precedence over earlier ones.
+    layers.reverse();
+    layers
+}
+
+/// The name and number of generic parameters of a named type like `Foo<A, B>`. Tuples and
+/// arrays aren't named types.
+fn named_type(type_name: &str) -> Option<(String, usize)> {
+    let type_name = type_name.trim();
+    if type_name.starts_with(['(', '[']) || LookupName::parse(type_name).is_err() {
+        return None;
+    }
+    let Some((name, params)) = type_name.split_once('<') else {
+        return Some((type_name.to_owned(), 0));
+    };
+
+    let mut depth = 0;
+    let mut count = 1;
+    for c in params.strip_suffix('>')?.chars() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => count += 1,
+            _ => {}
+        }
+    }
+    Some((name.trim().to_owned(), count))
+}
+
+/// Is a type with this name and number of generic parameters defined in the given registry?
+fn is_defined(types: &TypeRegistry, name: &str, params: usize) -> bool {
+    // Fill in any generic params with a concrete type so that aliases like `Box<T>` resolve.
+    let type_name = match params {
+        0 => name.to_owned(),
+        n => format!("{name}<{}>", vec!["u8"; n].join(",")),
+    };
+    let visitor = scale_type_resolver::visitor::new::<_, LookupName, _, _>((), |_, _| true)
+        .visit_not_found(|_| false);
+    types.resolve_type_str(&type_name, visitor).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn later_types_file_entries_take_precedence() {
+        let types_file: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+            global:
+              types:
+                Balance: u64
+                Foo: Balance
+              palletTypes:
+                Balances:
+                  Balance: u32
+            forSpec:
+              - range: [10, null]
+                types:
+                  Balance: u128
+            "#,
+        )
+        .unwrap();
+        let explainer = |spec_version| TypeExplainer::from_types_file(&types_file, spec_version);
+        let types_file_source = |layer: &str, definition: &str| {
+            Some(TypeSource::TypesFile {
+                layer: layer.to_owned(),
+                definition: definition.to_owned(),
+            })
+        };
+
+        let old = explainer(5);
+        assert_eq!(
+            old.source(None, "Balance"),
+            types_file_source("global", "u64")
+        );
+        assert_eq!(
+            old.source(Some("Balances"), "Balance"),
+            types_file_source("global, Balances pallet", "u32")
+        );
+
+        // Pallet scoped types are preferred even over later global ones.
+        let new = explainer(10);
+        assert_eq!(
+            new.source(None, "Balance"),
+            types_file_source("forSpec [10, null]", "u128")
+        );
+        assert_eq!(
+            new.source(Some("Balances"), "Balance"),
+            types_file_source("global, Balances pallet", "u32")
+        );
+
+        assert_eq!(new.source(None, "Vec<Foo>"), Some(TypeSource::Basic));
+        assert_eq!(new.source(None, "Box<Foo>"), Some(TypeSource::Basic));
+        assert_eq!(new.source(None, "(u8, u16)"), None);
+        assert_eq!(new.source(None, "Bar"), Some(TypeSource::NotFound));
+    }
+}
@@ -0,0 +1,149 @@
+//! Writing weights (eg in `System.BlockWeight` and the `DispatchInfo` of `System.ExtrinsicSuccess`
+//! events) with their parts labelled in text output. Weights began as a single integer, the time
+//! that something takes to execute in picoseconds, and later became a struct of that `ref_time`
+//! and the `proof_size` (in bytes) that it adds to the proof of validity of a parachain block.
+//! Which of these a `Weight` is at some spec version is up to the types file (or metadata).
+//!
+//! Weights are recognised by the type that they were decoded as (see
+//! [`super::named_types`]). In V14+ metadata, weights that are a single integer are just a
+//! `u64` (rather than a named type), so only those decoded with the historic types are labelled.
+
+use super::named_types::base_name;
+use scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// The names that weights have had, including `OldWeight`, the single integer weight that some
+/// calls kept taking after weights gained a proof size.
+const WEIGHTS: &[&str] = &["Weight", "WeightV1", "WeightV2", "OldWeight"];
+
+/// How many picoseconds of ref time there are in a second.
+const REF_TIME_PER_SECOND: u128 = 1_000_000_000_000;
+
+/// Is this the type name of a weight?
+pub fn is_weight(type_name: &str) -> bool {
+    WEIGHTS.contains(&base_name(type_name))
+}
+
+/// A custom formatter (for `scale_value::stringify`) which writes weights with each of their
+/// parts labelled, eg `{ ref_time: 1500000000 (1.50ms), proof_size: 3593 bytes }`, or
+/// `1500000000 (legacy weight, 1.50ms)` for those that are a single integer.
+pub fn format_weight<T: std::fmt::Display, W: std::fmt::Write>(
+    value: &Value<T>,
+    w: &mut W,
+) -> Option<std::fmt::Result> {
+    if !is_weight(&value.context.to_string()) {
+        return None;
+    }
+    match &value.value {
+        ValueDef::Primitive(Primitive::U128(ref_time)) => Some(write!(
+            w,
+            "{ref_time} (legacy weight, {})",
+            duration(*ref_time)
+        )),
+        ValueDef::Composite(Composite::Unnamed(vals)) => {
+            let [val] = vals.as_slice() else {
+                return None;
+            };
+            let ref_time = val.as_u128()?;
+            Some(write!(
+                w,
+                "{ref_time} (legacy weight, {})",
+                duration(ref_time)
+            ))
+        }
+        ValueDef::Composite(Composite::Named(vals)) => {
+            // The historic types may use the camel case names that polkadot.js does.
+            let field = |names: [&str; 2]| {
+                vals.iter()
+                    .find(|(name, _)| names.contains(&name.as_str()))
+                    .and_then(|(_, v)| v.as_u128())
+            };
+            let ref_time = field(["ref_time", "refTime"])?;
+            let proof_size = field(["proof_size", "proofSize"]);
+            Some((|| {
+                write!(w, "{{ ref_time: {ref_time} ({})", duration(ref_time))?;
+                if let Some(proof_size) = proof_size {
+                    write!(w, ", proof_size: {proof_size} bytes")?;
+                }
+                write!(w, " }}")
+            })())
+        }
+        _ => None,
+    }
+}
+
+/// Some ref time as a duration, eg `1.50ms`.
+fn duration(ref_time: u128) -> String {
+    let secs = ref_time as f64 / REF_TIME_PER_SECOND as f64;
+    if secs >= 1.0 {
+        format!("{secs:.2}s")
+    } else if secs >= 1e-3 {
+        format!("{:.2}ms", secs * 1e3)
+    } else if secs >= 1e-6 {
+        format!("{:.2}µs", secs * 1e6)
+    } else {
+        format!("{:.2}ns", secs * 1e9)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decoding::decode_limits::decode_value;
+    use crate::decoding::decode_options::DecodeOptions;
+    use parity_scale_codec::{Compact, Encode};
+    use scale_info_legacy::{ChainTypeRegistry, LookupName};
+
+    #[test]
+    fn writes_durations() {
+        assert_eq!(duration(1_500_000_000), "1.50ms");
+        assert_eq!(duration(2_000_000_000_000), "2.00s");
+        assert_eq!(duration(125_000_000), "125.00µs");
+        assert_eq!(duration(999), "1.00ns");
+    }
+
+    #[test]
+    fn labels_weights_from_each_spec_version() {
+        let types: ChainTypeRegistry = serde_yaml::from_str(
+            r#"
+            global:
+              types:
+                Weight: u64
+                PerDispatchClass:
+                  normal: Weight
+                  operational: Weight
+                  mandatory: Weight
+            forSpec:
+              - range: [10, null]
+                types:
+                  Weight:
+                    refTime: Compact<u64>
+                    proofSize: Compact<u64>
+            "#,
+        )
+        .unwrap();
+        let write = |spec_version, bytes: Vec<u8>| {
+            let types = types.for_spec_version(spec_version);
+            let ty = LookupName::parse("PerDispatchClass").unwrap();
+            let value = decode_value(&mut &*bytes, ty, &types, &DecodeOptions::default()).unwrap();
+            let mut out = String::new();
+            crate::utils::write_value_fmt(&mut out, &value, &DecodeOptions::default()).unwrap();
+            out
+        };
+
+        let old = write(5, (1_500_000_000u64, 0u64, 7u64).encode());
+        // Text output gives the type of each value before it.
+        assert!(old.contains("normal: <Weight> 1500000000 (legacy weight, 1.50ms)"));
+
+        let weight = |ref_time: u64, proof_size: u64| (Compact(ref_time), Compact(proof_size));
+        let new = write(
+            10,
+            (weight(1_500_000_000, 3593), weight(0, 0), weight(0, 0)).encode(),
+        );
+        assert!(new.contains("<Weight> { ref_time: 1500000000 (1.50ms), proof_size: 3593 bytes }"));
+
+        // Other integers are left alone.
+        let mut out = String::new();
+        let value = Value::u128(1).map_context(|_| "u64");
+        assert!(format_weight(&value, &mut out).is_none());
+    }
+}
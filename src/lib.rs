@@ -0,0 +1,36 @@
+//! Decoding historic Substrate (and in particular Polkadot) blocks and storage, using
+//! type definitions for the runtimes before V14 metadata described their own types. The
+//! `substrate-block-decoder` binary is built on this, and other projects can use it to decode
+//! historic data without shelling out to the binary.
+//!
+//! The most useful parts are re-exported here:
+//!
+//! - [`load_historic_types`] loads a types file (eg `types/polkadot_types.yaml`).
+//! - [`DecodeOptions`] are the options to decode with (eg limits on the size of values, or how
+//!   to write out account IDs). [`DecodeOptions::default`] matches the binary's defaults.
+//! - [`prepare_decoding_context`] combines those types with the metadata at some spec version,
+//!   and the types derived from it (see [`extend_with_metadata_info`]), into the
+//!   [`DecodingContext`] to decode things at that spec version with. A
+//!   [`DecodingContextCache`] keeps these around to share between tasks.
+//! - [`decode_extrinsic`], [`decode_storage_keys`] and [`decode_storage_value`] decode things
+//!   given the metadata, types and options from such a context.
+//! - [`Runner`] fetches and decodes items (eg blocks) in parallel, and hands back the results in
+//!   order, along with any errors as [`RunnerItem::Error`]s.
+//!
+//! Everything else is available from the [`decoding`] and [`utils`] modules, though these
+//! change as the binary needs them to.
+
+pub mod decoding;
+pub mod utils;
+
+pub use decoding::builtin_types::extend_with_metadata_info;
+pub use decoding::decode_options::DecodeOptions;
+pub use decoding::decoding_context::{
+    prepare_decoding_context, DecodingContext, DecodingContextCache,
+};
+pub use decoding::extrinsic_decoder::{decode_extrinsic, Extrinsic, ExtrinsicCallData};
+pub use decoding::historic_types::load_historic_types;
+pub use decoding::storage_decoder::{
+    decode_storage_keys, decode_storage_value, StorageKey, StorageKeys, StorageValue,
+};
+pub use utils::runner::{Runner, RunnerError, RunnerItem};
@@ -1,32 +1,255 @@
 mod commands;
-mod decoding;
-mod utils;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use decoding::decode_limits::DecodeLimits;
+use decoding::decode_options::DecodeOptions;
+use std::process::ExitCode;
+use std::sync::Arc;
+use substrate_block_decoder::{decoding, utils};
+use utils::exit_code::{self, ErrorKind};
 
 #[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Run in CI mode. When finished, a machine readable JSON summary is printed to stderr
+    /// instead of any error message. The exit code is 0 on success, 2 if decode errors occurred,
+    /// 3 if the configuration was invalid and 4 if we gave up fetching data from the network.
+    #[arg(long, global = true)]
+    ci: bool,
+
+    /// The most memory (in MiB) that decoding any single value can use. Values which would
+    /// need more than this (usually because they are being decoded with the wrong type) fail
+    /// to decode with a "suspiciously large decode" error instead.
+    #[arg(long, global = true, default_value_t = decoding::decode_limits::DEFAULT_MAX_DECODE_MEMORY_MIB)]
+    max_decode_memory: usize,
+
+    /// How deeply values can be nested inside other values when decoding any single value.
+    /// A type which (wrongly) refers to itself would otherwise decode until the stack
+    /// overflows.
+    #[arg(long, global = true, default_value_t = decoding::decode_limits::DEFAULT_MAX_DECODE_DEPTH)]
+    max_decode_depth: usize,
+
+    /// The most values (counting every field, sequence item and so on) that decoding any
+    /// single value can produce.
+    #[arg(long, global = true, default_value_t = decoding::decode_limits::DEFAULT_MAX_DECODE_NODES)]
+    max_decode_nodes: usize,
+
+    /// Fail to decode values which decode but aren't encoded the way that we'd encode them
+    /// (a sign that they're being decoded with the wrong type), rather than accepting them.
+    #[arg(long, global = true)]
+    strict_encoding: bool,
+
+    /// Decode as much of each extrinsic as we can: call arguments which fail to decode are
+    /// given as hex, along with why they failed, rather than failing the whole extrinsic.
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    /// How to write account IDs, both as signer addresses and wherever they appear in call
+    /// arguments and storage. By default, signer addresses are given in SS58 format and other
+    /// account IDs are left as bytes.
+    #[arg(long, global = true, value_enum)]
+    address_format: Option<decoding::addresses::AddressFormat>,
+
+    /// The SS58 prefix of the chain, used when writing SS58 addresses (eg 0 for Polkadot and 2
+    /// for Kusama).
+    #[arg(long, global = true, default_value_t = decoding::addresses::DEFAULT_SS58_PREFIX)]
+    ss58_prefix: u16,
+
+    /// Decode blocks and storage with the types from the types file for this spec version,
+    /// whatever the spec version of the block being decoded. This is for deliberately decoding
+    /// with mismatched types, eg to diagnose issues around the blocks where upgrades are enacted.
+    #[arg(long, global = true)]
+    force_spec_version: Option<u32>,
+
+    /// Decode blocks and storage with the metadata in this file (the output of
+    /// `fetch-metadata --binary`, or hex encoded metadata), whatever the block being decoded.
+    #[arg(long, global = true)]
+    force_metadata: Option<std::path::PathBuf>,
+
+    /// When finished, print how long was spent downloading the metadata for each spec version,
+    /// building types from it and merging those with the historic types (or in CI mode, add
+    /// these to the JSON summary).
+    #[arg(long, global = true)]
+    profile: bool,
+
+    /// Write bit sequences (eg parachain availability bitfields) out in full. By default, those
+    /// longer than 32 bits are written with runs of the same bit shortened (eg `1×200`), along
+    /// with how many bits are set. JSON output always has every bit.
+    #[arg(long, global = true)]
+    expand_bits: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
 enum Commands {
+    /// Compare metadata files from consecutive runtimes, warning about any call indexes which
+    /// refer to a different call after an upgrade than before it.
+    CheckCallIndices(commands::check_call_indices::Opts),
+    /// Decode blocks from a file, using metadata from a file, without connecting to any node.
+    DecodeBlockFile(commands::decode_block_file::Opts),
     /// Decode blocks, printing the decoded output.
     DecodeBlocks(commands::decode_blocks::Opts),
+    /// Decode the events emitted in blocks, printing the decoded output.
+    DecodeEvents(commands::decode_events::Opts),
+    /// Decode a single extrinsic given as hex, using metadata from a file, without connecting to
+    /// any node.
+    DecodeExtrinsic(commands::decode_extrinsic::Opts),
+    /// Decode a single storage key and value given as hex, using metadata from a file, without
+    /// connecting to any node.
+    DecodeStorageItem(commands::decode_storage_item::Opts),
     /// Decode storage items, printing the decoded output.
     DecodeStorageItems(commands::decode_storage_items::Opts),
+    /// Decode the blocks and storage entries recorded in a file with two different types files,
+    /// reporting anything which decodes differently with each.
+    DiffTypes(commands::diff_types::Opts),
+    /// Print the types that we generate from the (pre-V14) metadata at a given block as YAML.
+    DumpBuiltinTypes(commands::dump_builtin_types::Opts),
+    /// Count the events emitted in a range of blocks by pallet, event and phase.
+    EventsStats(commands::events_stats::Opts),
     /// Fetch the metadata at a given block as JSON.
     FetchMetadata(commands::fetch_metadata::Opts),
     /// Find the block numbers where spec version changes happen.
     /// This is where the metadata/node API may have changed.
     FindSpecChanges(commands::find_spec_changes::Opts),
+    /// Fetch and decode a single item, printing it in a form that's easy to use in scripts.
+    Get(commands::get::Opts),
+    /// List every type name referenced by the calls, events and storage entries in some
+    /// (pre-V14) metadata, and whether each can be resolved using the types file.
+    ListTypes(commands::list_types::Opts),
+    /// Find the blocks containing some call, using a call index written by `decode-blocks`
+    /// with `--sink postgres://...?mode=index`.
+    Query(commands::query::Opts),
+    /// Print what some type in the types file resolves to at a given spec version, and where
+    /// it and the types it refers to were defined.
+    ResolveType(commands::resolve_type::Opts),
+    /// Decode blocks and storage on several chains at once, as given in a config file, and
+    /// report on how each went.
+    RunMatrix(commands::run_matrix::Opts),
+    /// Print the JSON Schemas for the output of `--format json`.
+    Schema(commands::schema::Opts),
+    /// Decode a small built-in set of extrinsics and storage items with metadata of several
+    /// versions, to check that this build decodes things as expected.
+    SelfTest(commands::self_test::Opts),
+    /// Serve JSON-RPC requests to decode blocks and storage and fetch metadata at any block.
+    ServeRpc(commands::serve_rpc::Opts),
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let cmd = Commands::parse();
+async fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            // Help and version output goes to stdout and isn't an error.
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitCode::from(ErrorKind::Config.exit_code())
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+
+    let started = std::time::Instant::now();
+    let res = match decode_options(&args) {
+        Ok(options) => run(args.command, Arc::new(options)).await,
+        Err(e) => Err(e),
+    };
+
+    let (status, code) = match &res {
+        Ok(()) => ("success", 0),
+        Err(e) => match exit_code::error_kind(e) {
+            Some(kind) => (kind.name(), kind.exit_code()),
+            None => ("error", exit_code::GENERIC_ERROR_EXIT_CODE),
+        },
+    };
 
+    let network_usage = utils::usage::summary();
+    let profile = args.profile.then(utils::profile::summary);
+    if args.ci {
+        let mut summary = serde_json::json!({
+            "status": status,
+            "exit_code": code,
+            "error": res.as_ref().err().map(|e| format!("{e:#}")),
+            "elapsed_secs": started.elapsed().as_secs_f64(),
+            "network": network_usage,
+            "errors": utils::error_category::counts(),
+        });
+        if let Some(profile) = &profile {
+            summary["profile"] = serde_json::json!(profile);
+        }
+        eprintln!("{summary}");
+    } else {
+        if network_usage.total.requests > 0 {
+            let _ = utils::usage::write_summary(std::io::stderr().lock(), &network_usage);
+        }
+        let _ = utils::error_category::write_summary(std::io::stderr().lock());
+        if let Some(profile) = &profile {
+            let _ = utils::profile::write_summary(std::io::stderr().lock(), profile);
+        }
+        if let Err(e) = &res {
+            eprintln!("Error: {e:?}");
+        }
+    }
+
+    ExitCode::from(code)
+}
+
+/// The options to decode everything with, as given by the global arguments.
+fn decode_options(args: &Args) -> anyhow::Result<DecodeOptions> {
+    let pinned_metadata = args
+        .force_metadata
+        .as_deref()
+        .map(commands::decode_block_file::load_metadata)
+        .transpose()?;
+    Ok(DecodeOptions {
+        limits: DecodeLimits {
+            max_memory: args.max_decode_memory.saturating_mul(1 << 20),
+            max_depth: args.max_decode_depth,
+            max_nodes: args.max_decode_nodes,
+        },
+        strict_encoding: args.strict_encoding,
+        lenient: args.lenient,
+        address_format: args.address_format,
+        ss58_prefix: args.ss58_prefix,
+        expand_bits: args.expand_bits,
+        pinned_spec_version: args.force_spec_version,
+        pinned_metadata: pinned_metadata.map(Arc::new),
+    })
+}
+
+async fn run(cmd: Commands, options: Arc<DecodeOptions>) -> anyhow::Result<()> {
     match cmd {
+        Commands::CheckCallIndices(opts) => {
+            commands::check_call_indices::run(opts).await?;
+        }
+        Commands::DecodeBlockFile(opts) => {
+            commands::decode_block_file::run(opts, options).await?;
+        }
         Commands::DecodeBlocks(opts) => {
-            commands::decode_blocks::run(opts).await?;
+            commands::decode_blocks::run(opts, options).await?;
+        }
+        Commands::DecodeEvents(opts) => {
+            commands::decode_events::run(opts, options).await?;
+        }
+        Commands::DecodeExtrinsic(opts) => {
+            commands::decode_extrinsic::run(opts, options).await?;
+        }
+        Commands::DecodeStorageItem(opts) => {
+            commands::decode_storage_item::run(opts, options).await?;
         }
         Commands::DecodeStorageItems(opts) => {
-            commands::decode_storage_items::run(opts).await?;
+            commands::decode_storage_items::run(opts, options).await?;
+        }
+        Commands::DiffTypes(opts) => {
+            commands::diff_types::run(opts, options).await?;
+        }
+        Commands::DumpBuiltinTypes(opts) => {
+            commands::dump_builtin_types::run(opts).await?;
+        }
+        Commands::EventsStats(opts) => {
+            commands::events_stats::run(opts, options).await?;
         }
         Commands::FetchMetadata(opts) => {
             commands::fetch_metadata::run(opts).await?;
@@ -34,6 +257,30 @@ async fn main() -> anyhow::Result<()> {
         Commands::FindSpecChanges(opts) => {
             commands::find_spec_changes::run(opts).await?;
         }
+        Commands::Get(opts) => {
+            commands::get::run(opts, options).await?;
+        }
+        Commands::ListTypes(opts) => {
+            commands::list_types::run(opts).await?;
+        }
+        Commands::Query(opts) => {
+            commands::query::run(opts).await?;
+        }
+        Commands::ResolveType(opts) => {
+            commands::resolve_type::run(opts).await?;
+        }
+        Commands::RunMatrix(opts) => {
+            commands::run_matrix::run(opts).await?;
+        }
+        Commands::Schema(opts) => {
+            commands::schema::run(opts).await?;
+        }
+        Commands::SelfTest(opts) => {
+            commands::self_test::run(opts).await?;
+        }
+        Commands::ServeRpc(opts) => {
+            commands::serve_rpc::run(opts, options).await?;
+        }
     }
 
     Ok(())
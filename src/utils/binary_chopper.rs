@@ -102,7 +102,7 @@ mod test {
             let mut chopper = BinaryChopper::new((start, versions[start]), (end, versions[end]));
 
             while let Next::NeedsState(n) = chopper.next_value() {
-                chopper.set_state_for_next_value(versions[n as usize]);
+                chopper.set_state_for_next_value(versions[n]);
             }
 
             let finished = chopper.next_value().unwrap_finished();
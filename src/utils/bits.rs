@@ -0,0 +1,84 @@
+//! Writing bit sequences (eg parachain availability bitfields, which have a bit per validator)
+//! compactly. Long runs of the same bit are shortened to eg `1×200`, and the number of set bits
+//! is given after them, so `<1111111111110110>` is written as `<1×12 0110> (14 of 16 set)`.
+
+use scale_value::{BitSequence, Value, ValueDef};
+
+/// Bit sequences no longer than this are written out in full anyway.
+const MAX_EXPANDED_LEN: usize = 32;
+
+/// Runs of the same bit at least this long are shortened.
+const MIN_RUN_LEN: usize = 8;
+
+/// A custom formatter (for `scale_value::stringify`) which writes long bit sequences compactly,
+/// unless `expand` is given to always write them out in full.
+pub fn format_bits<T, W: std::fmt::Write>(
+    value: &Value<T>,
+    w: &mut W,
+    expand: bool,
+) -> Option<std::fmt::Result> {
+    let ValueDef::BitSequence(bits) = &value.value else {
+        return None;
+    };
+    if bits.len() <= MAX_EXPANDED_LEN || expand {
+        return None;
+    }
+    let set = bits.iter().filter(|&bit| bit).count();
+    Some(write!(
+        w,
+        "{} ({set} of {} set)",
+        compact_bits(bits),
+        bits.len()
+    ))
+}
+
+fn compact_bits(bits: &BitSequence) -> String {
+    let mut segments: Vec<String> = vec![];
+    // Literal bits waiting to be added as a segment.
+    let mut literal = String::new();
+    let mut bits = bits.iter().peekable();
+    while let Some(bit) = bits.next() {
+        let mut run_len = 1;
+        while bits.next_if_eq(&bit).is_some() {
+            run_len += 1;
+        }
+        let c = if bit { '1' } else { '0' };
+        if run_len >= MIN_RUN_LEN {
+            if !literal.is_empty() {
+                segments.push(std::mem::take(&mut literal));
+            }
+            segments.push(format!("{c}×{run_len}"));
+        } else {
+            literal.extend(std::iter::repeat_n(c, run_len));
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(literal);
+    }
+    format!("<{}>", segments.join(" "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bits(s: &str) -> BitSequence {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn writes_bits_compactly() {
+        assert_eq!(compact_bits(&bits("1111111111110110")), "<1×12 0110>");
+        assert_eq!(compact_bits(&bits("0110")), "<0110>");
+        assert_eq!(compact_bits(&bits("01000000001111111111")), "<01 0×8 1×10>");
+
+        let mut out = String::new();
+        let long = Value::bit_sequence(bits(&"1".repeat(40)));
+        assert!(format_bits(&long, &mut out, false).is_some());
+        assert_eq!(out, "<1×40> (40 of 40 set)");
+        assert!(format_bits(&long, &mut out, true).is_none());
+
+        let short = Value::bit_sequence(bits("0110"));
+        assert!(format_bits(&short, &mut out, false).is_none());
+    }
+}
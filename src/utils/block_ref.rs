@@ -0,0 +1,115 @@
+//! Blocks given on the command line, eg `--at 1000000`. These can be `latest`, `finalized`,
+//! a block number or a (0x prefixed) block hash, and are resolved to a block number and hash
+//! on the canonical chain.
+
+use super::exit_code::ErrorKind;
+use anyhow::{anyhow, Context};
+use subxt::backend::legacy::{rpc_methods::NumberOrHex, LegacyRpcMethods};
+use subxt::utils::{to_hex, H256};
+use subxt::PolkadotConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRef {
+    /// The best block that the node knows about.
+    Latest,
+    /// The latest finalized block.
+    Finalized,
+    Number(u64),
+    /// A block on the canonical chain, given by its hash.
+    Hash(H256),
+}
+
+impl BlockRef {
+    /// Find the number and hash of the block. Blocks given by hash must be on the canonical
+    /// chain, since we look up blocks by number.
+    pub async fn resolve(
+        self,
+        rpcs: &LegacyRpcMethods<PolkadotConfig>,
+    ) -> anyhow::Result<(u64, H256)> {
+        let hash = match self {
+            BlockRef::Number(number) => {
+                let hash = rpcs
+                    .chain_get_block_hash(Some(NumberOrHex::Number(number)))
+                    .await
+                    .with_context(|| "Could not fetch block hash")?
+                    .ok_or_else(|| anyhow!("Couldn't find block {number}"))?;
+                return Ok((number, hash));
+            }
+            BlockRef::Latest => rpcs
+                .chain_get_block_hash(None)
+                .await
+                .with_context(|| "Could not fetch the latest block hash")?
+                .ok_or_else(|| anyhow!("Couldn't find the latest block"))?,
+            BlockRef::Finalized => rpcs
+                .chain_get_finalized_head()
+                .await
+                .with_context(|| "Could not fetch the finalized block hash")?,
+            BlockRef::Hash(hash) => hash,
+        };
+
+        let header = rpcs
+            .chain_get_header(Some(hash))
+            .await
+            .with_context(|| "Could not fetch block header")?
+            .ok_or_else(|| anyhow!("Couldn't find block {}", to_hex(hash)))
+            .context(ErrorKind::Config)?;
+        let number = header.number as u64;
+        if let BlockRef::Hash(hash) = self {
+            let canonical_hash = rpcs
+                .chain_get_block_hash(Some(NumberOrHex::Number(number)))
+                .await
+                .with_context(|| "Could not fetch block hash")?;
+            if canonical_hash != Some(hash) {
+                return Err(anyhow!(
+                    "Block {} (number {number}) is not on the canonical chain",
+                    to_hex(hash)
+                ))
+                .context(ErrorKind::Config);
+            }
+        }
+        Ok((number, hash))
+    }
+}
+
+impl std::str::FromStr for BlockRef {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s {
+            "latest" => Ok(BlockRef::Latest),
+            "finalized" | "finalised" => Ok(BlockRef::Finalized),
+            _ if s.starts_with("0x") => s
+                .parse()
+                .map(BlockRef::Hash)
+                .map_err(|_| anyhow!("block hash should be 32 bytes of hex, eg 0x91b1..")),
+            _ => s.parse().map(BlockRef::Number).map_err(|_| {
+                anyhow!("block should be 'latest', 'finalized', a block number or a block hash")
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_block_refs() {
+        assert_eq!("latest".parse::<BlockRef>().unwrap(), BlockRef::Latest);
+        assert_eq!(
+            "finalized".parse::<BlockRef>().unwrap(),
+            BlockRef::Finalized
+        );
+        assert_eq!("1234".parse::<BlockRef>().unwrap(), BlockRef::Number(1234));
+
+        let hash = "0x91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3";
+        assert_eq!(
+            hash.parse::<BlockRef>().unwrap(),
+            BlockRef::Hash(super::super::polkadot_genesis_hash())
+        );
+
+        assert!("0x1234".parse::<BlockRef>().is_err());
+        assert!("best".parse::<BlockRef>().is_err());
+        assert!("-1".parse::<BlockRef>().is_err());
+    }
+}
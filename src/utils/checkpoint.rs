@@ -0,0 +1,93 @@
+//! A checkpoint file records how far some run has got, so that it can be resumed from there
+//! later. It contains a single number: the last block (or, for storage, the last `number`)
+//! whose output has been completely written.
+
+use super::exit_code::ErrorKind;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct Checkpoint {
+    path: PathBuf,
+    /// The first number that failed, which the checkpoint never moves past.
+    first_failed: Mutex<Option<u64>>,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path) -> Self {
+        Checkpoint {
+            path: path.to_owned(),
+            first_failed: Mutex::new(None),
+        }
+    }
+
+    /// Note that the given number failed and has no output. Later saves won't move the
+    /// checkpoint past the number before it, so that a resumed run tries it again.
+    pub fn failed(&self, number: u64) {
+        let mut first_failed = self.first_failed.lock().unwrap();
+        *first_failed = Some(first_failed.map_or(number, |n| n.min(number)));
+    }
+
+    /// The last number saved to the checkpoint file, or None if it doesn't exist yet.
+    pub fn load(&self) -> anyhow::Result<Option<u64>> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Could not read checkpoint {}", self.path.display()))
+                    .context(ErrorKind::Config)
+            }
+        };
+        let number = contents
+            .trim()
+            .parse()
+            .with_context(|| format!("Could not parse checkpoint {}", self.path.display()))
+            .context(ErrorKind::Config)?;
+        Ok(Some(number))
+    }
+
+    /// Save a number to the checkpoint file. The file is replaced in one go, so that it's never
+    /// left half written if we're stopped part way through.
+    pub fn save(&self, number: u64) -> anyhow::Result<()> {
+        let number = match *self.first_failed.lock().unwrap() {
+            Some(failed) if number >= failed => match failed.checked_sub(1) {
+                Some(number) => number,
+                // Nothing before the failure has been saved, so there's nothing to save.
+                None => return Ok(()),
+            },
+            _ => number,
+        };
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        std::fs::write(&tmp_path, format!("{number}\n"))
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .with_context(|| format!("Could not write checkpoint {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn failures_stop_the_checkpoint_advancing() {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}", std::process::id()));
+        let checkpoint = Checkpoint::new(&path);
+
+        checkpoint.save(10).unwrap();
+        assert_eq!(checkpoint.load().unwrap(), Some(10));
+
+        checkpoint.failed(12);
+        checkpoint.save(11).unwrap();
+        assert_eq!(checkpoint.load().unwrap(), Some(11));
+        checkpoint.save(15).unwrap();
+        assert_eq!(checkpoint.load().unwrap(), Some(11));
+
+        // Going back (eg after a reorg) is still allowed.
+        checkpoint.save(5).unwrap();
+        assert_eq!(checkpoint.load().unwrap(), Some(5));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
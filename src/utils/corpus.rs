@@ -0,0 +1,207 @@
+//! A corpus is a file recording the raw bytes that we fetched during some run (via `--record`),
+//! so that the same items can be decoded again later without any RPC connection (via `--from-file`).
+//!
+//! It is written as one JSON [`Record`] per line. The first line is a [`Record::Header`], and the
+//! metadata for each spec version is recorded once, before the first block or storage entry which
+//! needs it.
+
+use super::error_category::ErrorCategory;
+use super::exit_code::ErrorKind;
+use crate::decoding::decoding_context::decode_metadata;
+use anyhow::Context;
+use frame_metadata::{RuntimeMetadata, META_RESERVED};
+use parity_scale_codec::Encode;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use subxt::backend::legacy::rpc_methods::Bytes;
+use subxt::utils::H256;
+
+/// The version of the corpus format that we write. This should be bumped if the format
+/// changes in a way that older versions can't read.
+pub const CORPUS_VERSION: u32 = 1;
+
+/// A single line in a corpus file.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Record {
+    /// The first record in every corpus.
+    Header { version: u32 },
+    /// The SCALE encoded metadata (including the magic number prefix) for some spec version.
+    Metadata { spec_version: u32, metadata: Bytes },
+    /// The extrinsics in some block.
+    Block(BlockRecord),
+    /// The keys and values fetched for some storage entry at some block.
+    Storage(StorageRecord),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlockRecord {
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub spec_version: u32,
+    pub extrinsics: Vec<Bytes>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StorageRecord {
+    /// The number used to pick the block (see `decode-storage-items --starting-number`).
+    pub number: usize,
+    pub block_number: u32,
+    pub block_hash: H256,
+    pub spec_version: u32,
+    pub pallet: String,
+    pub entry: String,
+    pub keyvals: Vec<(Bytes, Bytes)>,
+}
+
+/// Write records to a corpus file.
+pub struct Recorder {
+    inner: Mutex<RecorderInner>,
+}
+
+struct RecorderInner {
+    file: BufWriter<std::fs::File>,
+    recorded_spec_versions: HashSet<u32>,
+}
+
+impl Recorder {
+    /// Create a new corpus file at the given path, overwriting any existing file.
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Could not create corpus file {}", path.display()))
+            .context(ErrorKind::Config)?;
+        let recorder = Recorder {
+            inner: Mutex::new(RecorderInner {
+                file: BufWriter::new(file),
+                recorded_spec_versions: HashSet::new(),
+            }),
+        };
+        recorder.write(&Record::Header {
+            version: CORPUS_VERSION,
+        })?;
+        Ok(recorder)
+    }
+
+    /// Record the metadata for some spec version, if it hasn't already been recorded.
+    pub fn record_metadata(
+        &self,
+        spec_version: u32,
+        metadata: &RuntimeMetadata,
+    ) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.recorded_spec_versions.insert(spec_version) {
+            return Ok(());
+        }
+        // This is the same encoding as a RuntimeMetadataPrefixed.
+        let metadata = (META_RESERVED, metadata).encode();
+        write_record(
+            &mut inner.file,
+            &Record::Metadata {
+                spec_version,
+                metadata: Bytes(metadata),
+            },
+        )
+    }
+
+    /// Write a record to the corpus.
+    pub fn write(&self, record: &Record) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        write_record(&mut inner.file, record)
+    }
+}
+
+fn write_record<W: Write>(mut w: W, record: &Record) -> anyhow::Result<()> {
+    super::output::write_json_line(&mut w, record)
+        .with_context(|| "Could not write to corpus file")?;
+    w.flush()?;
+    Ok(())
+}
+
+/// The contents of a corpus file.
+pub struct Corpus {
+    /// The metadata for each spec version.
+    pub metadata: HashMap<u32, Arc<RuntimeMetadata>>,
+    pub blocks: Vec<BlockRecord>,
+    pub storage: Vec<StorageRecord>,
+}
+
+impl Corpus {
+    /// Load a corpus from the given path.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        load_corpus(path)
+            .with_context(|| format!("Could not load corpus file {}", path.display()))
+            .context(ErrorKind::Config)
+    }
+
+    /// The metadata for some spec version.
+    pub fn metadata(&self, spec_version: u32) -> anyhow::Result<&Arc<RuntimeMetadata>> {
+        self.metadata.get(&spec_version).ok_or_else(|| {
+            ErrorCategory::MissingData.error(format!(
+                "No metadata recorded for spec version {spec_version}"
+            ))
+        })
+    }
+}
+
+fn load_corpus(path: &Path) -> anyhow::Result<Corpus> {
+    let corpus_str = std::fs::read_to_string(path)?;
+    let mut corpus = Corpus {
+        metadata: HashMap::new(),
+        blocks: Vec::new(),
+        storage: Vec::new(),
+    };
+
+    for (idx, line) in corpus_str.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(line)
+            .with_context(|| format!("Could not parse line {}", idx + 1))?;
+        match record {
+            Record::Header { version } => {
+                if version > CORPUS_VERSION {
+                    anyhow::bail!(
+                        "Corpus version {version} is newer than the supported version {CORPUS_VERSION}"
+                    );
+                }
+            }
+            Record::Metadata {
+                spec_version,
+                metadata,
+            } => {
+                let metadata = decode_metadata(&metadata.0).with_context(|| {
+                    format!("Could not decode metadata for spec version {spec_version}")
+                })?;
+                corpus.metadata.insert(spec_version, Arc::new(metadata));
+            }
+            Record::Block(block) => corpus.blocks.push(block),
+            Record::Storage(storage) => corpus.storage.push(storage),
+        }
+    }
+    Ok(corpus)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records_round_trip() {
+        let record = Record::Block(BlockRecord {
+            block_number: 1,
+            block_hash: H256::repeat_byte(1),
+            spec_version: 0,
+            extrinsics: vec![Bytes(vec![1, 2, 3])],
+        });
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.starts_with(r#"{"kind":"block","block_number":1,"#));
+
+        let Record::Block(block) = serde_json::from_str(&json).unwrap() else {
+            panic!("expected a block record");
+        };
+        assert_eq!(block.extrinsics[0].0, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,138 @@
+//! A coverage file records which calls and storage entries have been seen to decode at each
+//! spec version, so that later runs can skip them and focus on what hasn't been tested yet.
+
+use super::exit_code::ErrorKind;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct Coverage {
+    path: PathBuf,
+    covered: Mutex<Covered>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Covered {
+    /// `Pallet.call` names, by spec version.
+    #[serde(default)]
+    calls: BTreeMap<u32, BTreeSet<String>>,
+    /// `Pallet.Entry` names, by spec version.
+    #[serde(default)]
+    storage: BTreeMap<u32, BTreeSet<String>>,
+    /// Whether anything has been added since we last saved.
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl Coverage {
+    /// Load the coverage file at this path, or start with nothing covered if it doesn't exist.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let covered = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Could not parse coverage file {}", path.display()))
+                .context(ErrorKind::Config)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Covered::default(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Could not read coverage file {}", path.display()))
+                    .context(ErrorKind::Config)
+            }
+        };
+        Ok(Coverage {
+            path: path.to_owned(),
+            covered: Mutex::new(covered),
+        })
+    }
+
+    /// Has this call decoded at this spec version before?
+    pub fn has_call(&self, spec_version: u32, pallet: &str, call: &str) -> bool {
+        let covered = self.covered.lock().unwrap();
+        is_covered(&covered.calls, spec_version, pallet, call)
+    }
+
+    /// Has this storage entry decoded at this spec version before?
+    pub fn has_storage_entry(&self, spec_version: u32, pallet: &str, entry: &str) -> bool {
+        let covered = self.covered.lock().unwrap();
+        is_covered(&covered.storage, spec_version, pallet, entry)
+    }
+
+    /// Note that this call decoded at this spec version.
+    pub fn add_call(&self, spec_version: u32, pallet: &str, call: &str) {
+        let mut covered = self.covered.lock().unwrap();
+        let Covered { calls, changed, .. } = &mut *covered;
+        *changed |= add(calls, spec_version, pallet, call);
+    }
+
+    /// Note that this storage entry decoded at this spec version.
+    pub fn add_storage_entry(&self, spec_version: u32, pallet: &str, entry: &str) {
+        let mut covered = self.covered.lock().unwrap();
+        let Covered {
+            storage, changed, ..
+        } = &mut *covered;
+        *changed |= add(storage, spec_version, pallet, entry);
+    }
+
+    /// Save the coverage file if anything has been added to it. Like checkpoints, the file is
+    /// replaced in one go, so that it's never left half written.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let mut covered = self.covered.lock().unwrap();
+        if !covered.changed {
+            return Ok(());
+        }
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let contents = serde_json::to_string_pretty(&*covered)?;
+        std::fs::write(&tmp_path, contents)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .with_context(|| format!("Could not write coverage file {}", self.path.display()))?;
+        covered.changed = false;
+        Ok(())
+    }
+}
+
+fn is_covered(
+    covered: &BTreeMap<u32, BTreeSet<String>>,
+    spec_version: u32,
+    pallet: &str,
+    item: &str,
+) -> bool {
+    covered
+        .get(&spec_version)
+        .is_some_and(|items| items.contains(&format!("{pallet}.{item}")))
+}
+
+fn add(
+    covered: &mut BTreeMap<u32, BTreeSet<String>>,
+    spec_version: u32,
+    pallet: &str,
+    item: &str,
+) -> bool {
+    covered
+        .entry(spec_version)
+        .or_default()
+        .insert(format!("{pallet}.{item}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn coverage_round_trips() {
+        let path = std::env::temp_dir().join(format!("coverage-{}.json", std::process::id()));
+        let coverage = Coverage::load(&path).unwrap();
+        coverage.add_call(1000, "Balances", "transfer");
+        coverage.add_storage_entry(1000, "System", "Account");
+        coverage.save().unwrap();
+
+        let coverage = Coverage::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(coverage.has_call(1000, "Balances", "transfer"));
+        assert!(!coverage.has_call(1001, "Balances", "transfer"));
+        assert!(!coverage.has_call(1000, "System", "Account"));
+        assert!(coverage.has_storage_entry(1000, "System", "Account"));
+    }
+}
@@ -0,0 +1,201 @@
+//! What kind of problem an error is, so that (for instance) failing to fetch some data can be
+//! told apart from failing to decode it. Each error in the output is given a category, and the
+//! errors written out in each category are counted for the summary at the end of a run.
+
+use crate::decoding::canonical::NonCanonical;
+use crate::decoding::decode_limits::LimitExceeded;
+use crate::decoding::extrinsic_decoder::UnsupportedVersion;
+use frame_decode::extrinsics::ExtrinsicDecodeError;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Fetching data from an RPC node failed.
+    NetworkError,
+    /// Some data that we needed wasn't there, like a block, or a storage entry in the metadata.
+    MissingData,
+    /// The types needed to decode something couldn't be found or resolved, or it's in a
+    /// format (like a pre-v4 extrinsic) that we don't know how to decode.
+    TypeResolution,
+    /// A value decoded without using all of its bytes.
+    LeftoverBytes,
+    /// The bytes couldn't be decoded as the type that they should be (or, with
+    /// `--strict-encoding`, decoded but weren't canonically encoded).
+    Corrupt,
+    /// A value was too large to fetch, or to decode within our limits.
+    TooLarge,
+}
+
+impl ErrorCategory {
+    /// The category of some error. Errors whose type doesn't say, and which weren't given a
+    /// category with [`ErrorCategory::error`] or [`ErrorCategory::context`], are
+    /// [`ErrorCategory::Corrupt`].
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(err) = find::<CategorisedError>(err) {
+            return err.category;
+        }
+        if find::<LimitExceeded>(err).is_some() {
+            return ErrorCategory::TooLarge;
+        }
+        if find::<UnsupportedVersion>(err).is_some() {
+            return ErrorCategory::TypeResolution;
+        }
+        if find::<NonCanonical>(err).is_some() {
+            return ErrorCategory::Corrupt;
+        }
+        if let Some(err) = find::<subxt::Error>(err) {
+            return match err {
+                subxt::Error::Rpc(subxt::error::RpcError::ClientError(e))
+                    if is_too_large_message(&e.to_string()) =>
+                {
+                    ErrorCategory::TooLarge
+                }
+                _ => ErrorCategory::NetworkError,
+            };
+        }
+        if find::<subxt::error::RpcError>(err).is_some()
+            || find::<jsonrpsee::core::client::Error>(err).is_some()
+        {
+            return ErrorCategory::NetworkError;
+        }
+        if let Some(err) = find::<scale_decode::visitor::DecodeError>(err) {
+            return match err {
+                scale_decode::visitor::DecodeError::TypeIdNotFound(_)
+                | scale_decode::visitor::DecodeError::TypeResolvingError(_) => {
+                    ErrorCategory::TypeResolution
+                }
+                _ => ErrorCategory::Corrupt,
+            };
+        }
+        if let Some(ExtrinsicDecodeError::CannotGetInfo(_)) = find::<ExtrinsicDecodeError>(err) {
+            return ErrorCategory::TypeResolution;
+        }
+        ErrorCategory::Corrupt
+    }
+
+    /// An error with the given message in this category.
+    pub fn error(self, message: impl std::fmt::Display) -> anyhow::Error {
+        CategorisedError {
+            category: self,
+            message: message.to_string(),
+        }
+        .into()
+    }
+
+    /// Add a message to some error, putting it in this category whatever its type says.
+    pub fn context(
+        self,
+        err: impl Into<anyhow::Error>,
+        message: impl std::fmt::Display,
+    ) -> anyhow::Error {
+        err.into().context(CategorisedError {
+            category: self,
+            message: message.to_string(),
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ErrorCategory::NetworkError => "network_error",
+            ErrorCategory::MissingData => "missing_data",
+            ErrorCategory::TypeResolution => "type_resolution",
+            ErrorCategory::LeftoverBytes => "leftover_bytes",
+            ErrorCategory::Corrupt => "corrupt",
+            ErrorCategory::TooLarge => "too_large",
+        }
+    }
+}
+
+/// Does an RPC error message say that the response was too large for the client?
+pub fn is_too_large_message(message: &str) -> bool {
+    message.contains("message too large") || message.contains("Response is too big")
+}
+
+/// An error whose type doesn't say what category it's in, so that we say instead.
+#[derive(Debug)]
+struct CategorisedError {
+    category: ErrorCategory,
+    message: String,
+}
+
+impl std::fmt::Display for CategorisedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CategorisedError {}
+
+/// Find an error of some type in an error or anything that it was caused by.
+fn find<T: std::error::Error + Send + Sync + 'static>(err: &anyhow::Error) -> Option<&T> {
+    err.downcast_ref::<T>()
+        .or_else(|| err.chain().find_map(|cause| cause.downcast_ref::<T>()))
+}
+
+static COUNTS: Mutex<BTreeMap<ErrorCategory, u64>> = Mutex::new(BTreeMap::new());
+
+/// Count an error that's been written out, for the summary at the end of the run.
+pub fn count(err: &anyhow::Error) {
+    *COUNTS
+        .lock()
+        .unwrap()
+        .entry(ErrorCategory::of(err))
+        .or_default() += 1;
+}
+
+/// How many errors in each category have been written out so far, by category name.
+pub fn counts() -> BTreeMap<&'static str, u64> {
+    COUNTS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(category, n)| (category.name(), *n))
+        .collect()
+}
+
+/// Write a human readable summary of the errors that have been written out, if there were any.
+pub fn write_summary<W: std::io::Write>(mut w: W) -> std::io::Result<()> {
+    let counts = counts();
+    if counts.is_empty() {
+        return Ok(());
+    }
+    let counts: Vec<_> = counts
+        .iter()
+        .map(|(name, n)| format!("{n} {name}"))
+        .collect();
+    writeln!(w, "Errors: {}", counts.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn categorises_errors() {
+        let leftover: anyhow::Result<()> =
+            Err(ErrorCategory::LeftoverBytes.error("2 leftover bytes"));
+        let leftover = leftover.context("Failed to decode").unwrap_err();
+        assert_eq!(ErrorCategory::of(&leftover), ErrorCategory::LeftoverBytes);
+        assert_eq!(
+            format!("{leftover:#}"),
+            "Failed to decode: 2 leftover bytes"
+        );
+
+        let not_found = anyhow::Error::from(scale_decode::visitor::DecodeError::TypeIdNotFound(
+            "Foo".to_owned(),
+        ));
+        assert_eq!(ErrorCategory::of(&not_found), ErrorCategory::TypeResolution);
+
+        let truncated = anyhow::Error::from(scale_decode::visitor::DecodeError::NotEnoughInput);
+        assert_eq!(ErrorCategory::of(&truncated), ErrorCategory::Corrupt);
+        assert_eq!(
+            ErrorCategory::of(&anyhow::anyhow!("something else")),
+            ErrorCategory::Corrupt
+        );
+    }
+}
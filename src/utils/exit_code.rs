@@ -0,0 +1,75 @@
+/// Errors can be tagged with one of these (via [`anyhow::Context`]) to determine
+/// the exit code of the process if they cause it to end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Some items could not be decoded.
+    DecodeErrors,
+    /// The configuration (arguments, types files and so on) is invalid.
+    Config,
+    /// We gave up after repeatedly failing to fetch data from the RPC nodes.
+    NetworkExhausted,
+}
+
+impl ErrorKind {
+    /// The exit code associated with this kind of error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorKind::DecodeErrors => 2,
+            ErrorKind::Config => 3,
+            ErrorKind::NetworkExhausted => 4,
+        }
+    }
+
+    /// The kind of error that a process exited with, given its exit code.
+    pub fn from_exit_code(code: i32) -> Option<Self> {
+        match code {
+            2 => Some(ErrorKind::DecodeErrors),
+            3 => Some(ErrorKind::Config),
+            4 => Some(ErrorKind::NetworkExhausted),
+            _ => None,
+        }
+    }
+
+    /// A short machine readable name for this kind of error.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::DecodeErrors => "decode_errors",
+            ErrorKind::Config => "config_error",
+            ErrorKind::NetworkExhausted => "network_exhausted",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::DecodeErrors => write!(f, "Errors occurred while decoding"),
+            ErrorKind::Config => write!(f, "Invalid configuration"),
+            ErrorKind::NetworkExhausted => write!(f, "Gave up fetching data from the network"),
+        }
+    }
+}
+
+/// The exit code for a generic error which hasn't been tagged with an [`ErrorKind`].
+pub const GENERIC_ERROR_EXIT_CODE: u8 = 1;
+
+/// Find the [`ErrorKind`] that an error has been tagged with, if any.
+pub fn error_kind(err: &anyhow::Error) -> Option<ErrorKind> {
+    err.downcast_ref::<ErrorKind>().copied()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn finds_error_kind_beneath_other_context() {
+        let err: anyhow::Result<()> = Err(anyhow::anyhow!("inner"));
+        let err = err.context(ErrorKind::Config).context("outer").unwrap_err();
+        assert_eq!(error_kind(&err), Some(ErrorKind::Config));
+
+        let err = anyhow::anyhow!("untagged");
+        assert_eq!(error_kind(&err), None);
+    }
+}
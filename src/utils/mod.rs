@@ -1,7 +1,34 @@
 pub mod binary_chopper;
+pub mod bits;
+pub mod block_ref;
+pub mod checkpoint;
+pub mod corpus;
+pub mod coverage;
+pub mod error_category;
+pub mod exit_code;
+pub mod output;
+pub mod previous_failures;
+pub mod profile;
+pub mod read_proof;
+pub mod report;
+pub mod rpc;
+pub mod run_config;
 pub mod runner;
+pub mod shard;
+pub mod sink;
+pub mod template;
+pub mod usage;
 
+use crate::decoding::decode_options::DecodeOptions;
+use anyhow::Context;
+use exit_code::ErrorKind;
 use scale_value::{Composite, Value, ValueDef};
+use std::cell::Cell;
+use subxt::backend::legacy::{rpc_methods::NumberOrHex, LegacyRpcMethods};
+use subxt::{utils::H256, PolkadotConfig};
+
+/// How many items to print when showing what a dry run would do.
+pub const DRY_RUN_PLAN_LEN: u64 = 10;
 
 /// Our stdout lock is io::Write but we need fmt::Write for scale_value writing.
 /// Ideally we'd change scale_value, but io::Write is std-only among other things,
@@ -28,21 +55,28 @@ impl<W: std::io::Write> std::io::Write for ToFmtWrite<W> {
 pub fn write_value<W: std::io::Write, T: std::fmt::Display>(
     w: W,
     value: &Value<T>,
+    opts: &DecodeOptions,
 ) -> core::fmt::Result {
-    write_value_fmt(ToFmtWrite(w), value)
+    write_value_fmt(ToFmtWrite(w), value, opts)
 }
 
 /// Write out a pretty Value using `std::fmt::Write`.
 pub fn write_value_fmt<W: std::fmt::Write, T: std::fmt::Display>(
     w: W,
     value: &Value<T>,
+    opts: &DecodeOptions,
 ) -> core::fmt::Result {
+    let expand_bits = opts.expand_bits;
     scale_value::stringify::to_writer_custom()
         .pretty()
         .format_context(|type_id, w: &mut W| write!(w, "{type_id}"))
+        .add_custom_formatter(|v, w: &mut W| crate::decoding::ethereum::format_ethereum(v, w))
         .add_custom_formatter(|v, w: &mut W| {
             scale_value::stringify::custom_formatters::format_hex(v, w)
         })
+        .add_custom_formatter(move |v, w: &mut W| bits::format_bits(v, w, expand_bits))
+        .add_custom_formatter(|v, w: &mut W| crate::decoding::per_things::format_per_thing(v, w))
+        .add_custom_formatter(|v, w: &mut W| crate::decoding::weights::format_weight(v, w))
         .add_custom_formatter(|v, w: &mut W| {
             // don't space unnamed composites over multiple lines if lots of primitive values.
             if let ValueDef::Composite(Composite::Unnamed(vals)) = &v.value {
@@ -55,7 +89,7 @@ pub fn write_value_fmt<W: std::fmt::Write, T: std::fmt::Display>(
             }
             None
         })
-        .write(&value, w)
+        .write(value, w)
 }
 
 // pub fn write_compact_value<W: std::io::Write>(writer: W, value: &Value<String>) -> anyhow::Result<()> {
@@ -65,13 +99,19 @@ pub fn write_value_fmt<W: std::fmt::Write, T: std::fmt::Display>(
 pub fn write_compact_value_fmt<W: std::fmt::Write>(
     writer: W,
     value: &Value<String>,
+    opts: &DecodeOptions,
 ) -> anyhow::Result<()> {
+    let expand_bits = opts.expand_bits;
     scale_value::stringify::to_writer_custom()
         .compact()
         .format_context(|type_id, w: &mut W| write!(w, "{type_id}"))
+        .add_custom_formatter(|v, w: &mut W| crate::decoding::ethereum::format_ethereum(v, w))
         .add_custom_formatter(|v, w: &mut W| {
             scale_value::stringify::custom_formatters::format_hex(v, w)
         })
+        .add_custom_formatter(move |v, w: &mut W| bits::format_bits(v, w, expand_bits))
+        .add_custom_formatter(|v, w: &mut W| crate::decoding::per_things::format_per_thing(v, w))
+        .add_custom_formatter(|v, w: &mut W| crate::decoding::weights::format_weight(v, w))
         .add_custom_formatter(|v, w: &mut W| {
             // don't space unnamed composites over multiple lines if lots of primitive values.
             if let ValueDef::Composite(Composite::Unnamed(vals)) = &v.value {
@@ -88,19 +128,221 @@ pub fn write_compact_value_fmt<W: std::fmt::Write>(
     Ok(())
 }
 
+/// If the value is a sequence of bytes (possibly wrapped in a newtype, eg `H256([u8; 32])`),
+/// return them.
+pub fn value_to_bytes<T>(value: &Value<T>) -> Option<Vec<u8>> {
+    let ValueDef::Composite(composite) = &value.value else {
+        return None;
+    };
+    let mut vals = composite.values();
+    if let (1, Some(inner)) = (vals.len(), vals.next()) {
+        if let ValueDef::Composite(_) = &inner.value {
+            return value_to_bytes(inner);
+        }
+    }
+
+    composite
+        .values()
+        .map(|v| v.as_u128().and_then(|n| u8::try_from(n).ok()))
+        .collect()
+}
+
 /// Unwrap the given URl string, returning default Polkadot RPC nodes if not given.
-pub fn url_or_polkadot_rpc_nodes(url: Option<&str>) -> Vec<String> {
+pub fn url_or_polkadot_rpc_nodes(url: Option<&str>) -> anyhow::Result<Vec<String>> {
     // Use our default or built-inPolkadot RPC URLs if not provided.
-    let urls = url
-        .as_ref()
-        .map(|urls| {
-            urls.split(',')
-                .map(|url| url.to_owned())
-                .collect::<Vec<String>>()
-        })
-        .unwrap_or_else(|| RPC_NODE_URLS.iter().map(|url| url.to_string()).collect());
+    let urls = match url {
+        Some(urls) => urls.split(',').map(|url| url.to_owned()).collect(),
+        None => default_polkadot_rpc_nodes()?,
+    };
+
+    Ok(urls)
+}
+
+/// The environment variable that additional default RPC URLs can be provided in (comma separated).
+const RPC_NODE_URLS_ENV_VAR: &str = "POLKADOT_DECODER_URLS";
+
+/// The default Polkadot RPC URLs to use. These are the built-in URLs merged with any given
+/// in the [`RPC_NODE_URLS_ENV_VAR`] environment variable or in the user's endpoints config file,
+/// so that dead public nodes can be replaced without rebuilding.
+fn default_polkadot_rpc_nodes() -> anyhow::Result<Vec<String>> {
+    let mut urls = vec![];
+    let mut replace_builtin = false;
+
+    if let Ok(env_urls) = std::env::var(RPC_NODE_URLS_ENV_VAR) {
+        urls.extend(
+            env_urls
+                .split(',')
+                .map(|url| url.trim())
+                .filter(|url| !url.is_empty())
+                .map(|url| url.to_owned()),
+        );
+    }
+
+    if let Some(path) = endpoints_config_path().filter(|path| path.is_file()) {
+        let config_str = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not load endpoints from {}", path.display()))?;
+        let config: EndpointsConfig = toml::from_str(&config_str)
+            .with_context(|| format!("Could not parse endpoints from {}", path.display()))?;
+        urls.extend(config.urls);
+        replace_builtin = config.replace;
+    }
+
+    if !replace_builtin {
+        urls.extend(RPC_NODE_URLS.iter().map(|url| url.to_string()));
+    }
+
+    // Keep the first instance of each URL.
+    let mut seen = std::collections::HashSet::new();
+    urls.retain(|url| seen.insert(url.clone()));
+
+    if urls.is_empty() {
+        anyhow::bail!("No default RPC URLs are configured; provide one with --url");
+    }
+    Ok(urls)
+}
+
+/// Where the user's endpoints config lives, ie `~/.config/polkadot-decoder/endpoints.toml`.
+fn endpoints_config_path() -> Option<std::path::PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(config_dir.join("polkadot-decoder").join("endpoints.toml"))
+}
+
+/// The format of the user's endpoints config file.
+#[derive(serde::Deserialize)]
+struct EndpointsConfig {
+    /// RPC URLs to use in addition to the built-in ones.
+    #[serde(default)]
+    urls: Vec<String>,
+    /// Use only the URLs given here and in the environment, ignoring the built-in ones.
+    #[serde(default)]
+    replace: bool,
+}
 
-    urls
+/// Like [`url_or_polkadot_rpc_nodes`], but also checks that every URL is connected to the same chain
+/// (see [`check_genesis_hashes`]) before handing them back. URLs are checked against the genesis hash
+/// given in `--expected-genesis`, or Polkadot's if we're using our built-in URLs. This also
+/// initialises the given [`rpc::RpcOpts`], so it should be called before connecting to anything.
+pub async fn validated_url_or_polkadot_rpc_nodes(
+    url: Option<&str>,
+    rpc_opts: &rpc::RpcOpts,
+) -> anyhow::Result<Vec<String>> {
+    rpc_opts.init().context(ErrorKind::Config)?;
+    let urls = url_or_polkadot_rpc_nodes(url).context(ErrorKind::Config)?;
+    let expected = rpc_opts
+        .expected_genesis
+        .or_else(|| url.is_none().then(polkadot_genesis_hash));
+    check_genesis_hashes(&urls, expected, rpc_opts)
+        .await
+        .context(ErrorKind::Config)
+}
+
+/// Check that each of the given RPC URLs is connected to the same chain, by comparing their
+/// genesis hashes. If an expected genesis hash is given, every URL must also match it. URLs whose
+/// genesis hash we can't fetch are warned about and dropped, since we can't tell which chain they
+/// are connected to. The URLs that passed the check are returned, failing if there are none.
+pub async fn check_genesis_hashes(
+    urls: &[String],
+    expected: Option<H256>,
+    rpc_opts: &rpc::RpcOpts,
+) -> anyhow::Result<Vec<String>> {
+    let mut tasks = tokio::task::JoinSet::new();
+    for url in urls {
+        let url = url.clone();
+        let rpc_opts = rpc_opts.clone();
+        tasks.spawn(async move {
+            let res = fetch_genesis_hash(&url, &rpc_opts).await;
+            (url, res)
+        });
+    }
+
+    let mut genesis_hashes = std::collections::HashMap::new();
+    while let Some(res) = tasks.join_next().await {
+        match res? {
+            (url, Ok(hash)) => {
+                genesis_hashes.insert(url, hash);
+            }
+            (url, Err(e)) => {
+                eprintln!("Couldn't fetch genesis hash from {url}; not using it: {e}")
+            }
+        }
+    }
+
+    // Keep the URLs in the order given, so the first one is still the first one used.
+    let genesis_hashes: Vec<_> = urls
+        .iter()
+        .filter_map(|url| genesis_hashes.get(url).map(|hash| (url, *hash)))
+        .collect();
+    let Some(expected) = expected.or_else(|| genesis_hashes.first().map(|(_, hash)| *hash)) else {
+        anyhow::bail!("Couldn't fetch the genesis hash from any of the RPC URLs");
+    };
+    let mismatched: Vec<_> = genesis_hashes
+        .iter()
+        .filter(|(_, hash)| *hash != expected)
+        .map(|(url, hash)| format!("{url} ({})", subxt::utils::to_hex(hash)))
+        .collect();
+    if !mismatched.is_empty() {
+        anyhow::bail!(
+            "Some RPC URLs do not have the expected genesis hash {}: {}",
+            subxt::utils::to_hex(expected),
+            mismatched.join(", ")
+        );
+    }
+
+    Ok(genesis_hashes
+        .into_iter()
+        .map(|(url, _)| url.clone())
+        .collect())
+}
+
+/// Connect to each of the given URLs and print their genesis hashes, failing if any of them can't
+/// be reached. This is used to check the endpoints we'll use before a run.
+pub async fn check_endpoints<W: std::io::Write>(
+    mut w: W,
+    urls: &[String],
+    rpc_opts: &rpc::RpcOpts,
+) -> anyhow::Result<()> {
+    let mut unreachable = 0;
+    writeln!(w, "Endpoints:")?;
+    for url in urls {
+        match fetch_genesis_hash(url, rpc_opts).await {
+            Ok(hash) => writeln!(w, "  {url} (genesis {})", subxt::utils::to_hex(hash))?,
+            Err(e) => {
+                writeln!(w, "  {url} (unreachable: {e})")?;
+                unreachable += 1;
+            }
+        }
+    }
+    if unreachable > 0 {
+        return Err(anyhow::anyhow!(
+            "{unreachable} of {} endpoints could not be reached",
+            urls.len()
+        ))
+        .context(ErrorKind::NetworkExhausted);
+    }
+    Ok(())
+}
+
+async fn fetch_genesis_hash(url: &str, rpc_opts: &rpc::RpcOpts) -> anyhow::Result<H256> {
+    let rpc_client = rpc_opts.connect(url).await?;
+    let rpcs = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client);
+    let hash = rpcs
+        .chain_get_block_hash(Some(NumberOrHex::Number(0)))
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No genesis block found"))?;
+    Ok(hash)
+}
+
+/// The genesis hash of Polkadot, which our built-in RPC URLs should all agree on.
+pub fn polkadot_genesis_hash() -> H256 {
+    let bytes = hex::decode("91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3")
+        .expect("valid hex")
+        .try_into()
+        .expect("32 bytes");
+    H256(bytes)
 }
 
 const RPC_NODE_URLS: [&str; 7] = [
@@ -125,7 +367,7 @@ impl<const U: usize, W: std::io::Write> std::io::Write for IndentedWriter<U, W>
             self.0.write(&[byte])?;
             if byte == b'\n' {
                 for _ in 0..U {
-                    self.0.write(&[b' '])?;
+                    self.0.write(b" ")?;
                 }
             }
         }
@@ -135,3 +377,64 @@ impl<const U: usize, W: std::io::Write> std::io::Write for IndentedWriter<U, W>
         self.0.flush()
     }
 }
+
+thread_local! {
+    /// Whether panics on this thread are being caught by [`catch_panic`].
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run some decode function, turning any panic that occurs into a normal error.
+/// Some historic items are malformed in ways that can trip panics deep in the decoding
+/// libraries, and we'd rather report these than bring down a long running process.
+pub fn catch_panic<T, F: FnOnce() -> anyhow::Result<T>>(f: F) -> anyhow::Result<T> {
+    // The panic is reported as the returned error, so don't let the panic hook print it too.
+    static QUIET_HOOK: std::sync::Once = std::sync::Once::new();
+    QUIET_HOOK.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !CATCHING_PANICS.with(Cell::get) {
+                default_hook(info);
+            }
+        }));
+    });
+
+    let was_catching = CATCHING_PANICS.with(|catching| catching.replace(true));
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    CATCHING_PANICS.with(|catching| catching.set(was_catching));
+
+    match res {
+        Ok(res) => res,
+        Err(payload) => {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic payload".to_string());
+            Err(anyhow::anyhow!("Panic during decoding: {msg}"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn catch_panic_turns_panics_into_errors() {
+        let res: anyhow::Result<()> = catch_panic(|| panic!("bad bytes"));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Panic during decoding: bad bytes"
+        );
+
+        let res: anyhow::Result<u32> = catch_panic(|| panic!("{} bad bytes", 2));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Panic during decoding: 2 bad bytes"
+        );
+
+        assert_eq!(catch_panic(|| Ok(1)).unwrap(), 1);
+        // Panics outside of catch_panic are left to the default hook again.
+        assert!(!CATCHING_PANICS.with(Cell::get));
+    }
+}
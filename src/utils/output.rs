@@ -0,0 +1,736 @@
+use crate::decoding::canonical::{non_canonical_of, NonCanonical};
+use crate::decoding::decode_limits::{limit_exceeded_of, LimitExceeded};
+use crate::decoding::events::Event;
+use crate::decoding::extrinsic_decoder::{
+    unsupported_version_of, Extrinsic, ExtrinsicCallData, Mortality, UnsupportedVersion,
+};
+use crate::decoding::layout_hints::{layout_hint_of, LayoutHint};
+use crate::decoding::storage_decoder::{self, StorageKey};
+use crate::utils::error_category::ErrorCategory;
+use crate::utils::runner::{RunnerError, RunnerStage};
+use scale_value::{Composite, Primitive, ValueDef};
+use schemars::JsonSchema;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::cell::Cell;
+
+/// The format that decoded output is written in. When JSON, each decoded item is written
+/// as a single line of JSON, and the JSON Schemas for these can be printed with the `schema` command.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+    /// One JSON object per line, with decoded values written as typed JSON, which keeps the
+    /// type of each value and can be turned back into the original value.
+    TypedJson,
+}
+
+impl OutputFormat {
+    /// Is this one of the JSON formats?
+    pub fn is_json(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::TypedJson)
+    }
+}
+
+/// A decoded value. See the `scale-value` crate for details of the JSON representation, and
+/// [`TypedValue`] for the representation used with `--format typed-json`.
+pub type JsonValue = scale_value::Value<String>;
+
+thread_local! {
+    /// Whether decoded values are currently being written as typed JSON.
+    static TYPED_VALUES: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Serialize a decoded value in an output item, as typed JSON if the item is being written
+/// with [`write_output_line`] in the typed JSON format.
+pub fn serialize_value<S: Serializer>(value: &JsonValue, serializer: S) -> Result<S::Ok, S::Error> {
+    if TYPED_VALUES.with(Cell::get) {
+        TypedValue(value).serialize(serializer)
+    } else {
+        value.serialize(serializer)
+    }
+}
+
+/// Like [`serialize_value`], for optional values.
+pub fn serialize_option_value<S: Serializer>(
+    value: &Option<JsonValue>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_some(&OutputValue(value)),
+        None => serializer.serialize_none(),
+    }
+}
+
+struct OutputValue<'a>(&'a JsonValue);
+
+impl Serialize for OutputValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_value(self.0, serializer)
+    }
+}
+
+/// A decoded value written as typed JSON. Unlike the plain JSON representation, enum variants
+/// can't be mistaken for structs, numbers keep their exact size and sign, and each value has
+/// the `type` that it was decoded as (a type name, or a type ID in V14+ metadata, except for
+/// proportions like `Perbill` and weights, which are named) if known:
+///
+/// - structs: `{"type": "AccountInfo", "named": {"nonce": .., "data": ..}}`
+/// - tuples and sequences: `{"type": "Vec<u8>", "unnamed": [..]}`
+/// - enum variants: `{"type": "Option<u32>", "variant": "Some", "unnamed": [..]}`
+/// - primitives: `{"type": "u128", "u128": "1000"}` (as are `i128`, `u256`, `i256`, `bool`,
+///   `char` and `string`), with large numbers as strings so that no precision is lost
+/// - bit sequences: `{"bits": "0110"}`
+pub struct TypedValue<'a>(pub &'a JsonValue);
+
+impl Serialize for TypedValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if !self.0.context.is_empty() {
+            map.serialize_entry("type", &self.0.context)?;
+        }
+        match &self.0.value {
+            ValueDef::Composite(composite) => serialize_composite(&mut map, composite)?,
+            ValueDef::Variant(variant) => {
+                map.serialize_entry("variant", &variant.name)?;
+                serialize_composite(&mut map, &variant.values)?;
+            }
+            ValueDef::Primitive(primitive) => match primitive {
+                Primitive::Bool(b) => map.serialize_entry("bool", b)?,
+                Primitive::Char(c) => map.serialize_entry("char", c)?,
+                Primitive::String(s) => map.serialize_entry("string", s)?,
+                Primitive::U128(n) => map.serialize_entry("u128", &n.to_string())?,
+                Primitive::I128(n) => map.serialize_entry("i128", &n.to_string())?,
+                Primitive::U256(n) => map.serialize_entry("u256", &subxt::utils::to_hex(n))?,
+                Primitive::I256(n) => map.serialize_entry("i256", &subxt::utils::to_hex(n))?,
+            },
+            ValueDef::BitSequence(bits) => {
+                let bits: String = bits.iter().map(|b| if b { '1' } else { '0' }).collect();
+                map.serialize_entry("bits", &bits)?;
+            }
+        }
+        map.end()
+    }
+}
+
+fn serialize_composite<M: SerializeMap>(
+    map: &mut M,
+    composite: &Composite<String>,
+) -> Result<(), M::Error> {
+    struct Named<'a>(&'a [(String, JsonValue)]);
+    impl Serialize for Named<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(name, value)| (name, TypedValue(value))))
+        }
+    }
+    struct Unnamed<'a>(&'a [JsonValue]);
+    impl Serialize for Unnamed<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter().map(TypedValue))
+        }
+    }
+
+    match composite {
+        Composite::Named(fields) => map.serialize_entry("named", &Named(fields)),
+        Composite::Unnamed(values) => map.serialize_entry("unnamed", &Unnamed(values)),
+    }
+}
+
+/// A decoded block.
+#[derive(Serialize, JsonSchema)]
+pub struct BlockOutput {
+    pub block_number: u64,
+    /// Hex encoded block hash.
+    pub block_hash: String,
+    pub spec_version: u32,
+    /// The URL of the node that the block was fetched from, if it was fetched from one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    pub extrinsics: Vec<ExtrinsicOutput>,
+}
+
+/// A single extrinsic in a block.
+#[derive(Serialize, JsonSchema)]
+pub struct ExtrinsicOutput {
+    /// The index of the extrinsic in the block.
+    pub index: usize,
+    /// Hex encoded extrinsic bytes, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<String>,
+    /// The decoded extrinsic, if it was decoded successfully.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<DecodedExtrinsicOutput>,
+    /// The reason we failed to decode this extrinsic, if we did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorOutput>,
+}
+
+/// The decoded details of an extrinsic.
+#[derive(Serialize, JsonSchema)]
+pub struct DecodedExtrinsicOutput {
+    pub pallet: String,
+    pub call: String,
+    /// The address, for signed extrinsics.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Hex encoded signature, for signed extrinsics.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// The signed extensions, for signed or general extrinsics.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed_exts: Option<Vec<NamedValueOutput>>,
+    /// The blocks that the extrinsic was valid in, if it has an era.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mortality: Option<MortalityOutput>,
+    pub args: Vec<NamedValueOutput>,
+    /// Whether the extrinsic succeeded, if `--outcomes` was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+    /// The first line of the call's docs, if `--docs` was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+}
+
+/// The validity window of an extrinsic: `"immortal"`, or `{"mortal": {..}}` with the first
+/// block that it was valid in (`birth`) and the first block that it no longer was (`death`).
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MortalityOutput {
+    Immortal,
+    Mortal {
+        period: u64,
+        phase: u64,
+        birth: u64,
+        death: u64,
+    },
+}
+
+impl From<Mortality> for MortalityOutput {
+    fn from(mortality: Mortality) -> Self {
+        match mortality {
+            Mortality::Immortal => MortalityOutput::Immortal,
+            Mortality::Mortal {
+                period,
+                phase,
+                birth,
+                death,
+            } => MortalityOutput::Mortal {
+                period,
+                phase,
+                birth,
+                death,
+            },
+        }
+    }
+}
+
+impl From<Extrinsic> for DecodedExtrinsicOutput {
+    fn from(ext: Extrinsic) -> Self {
+        let (address, signature, signed_exts, call_data) = match ext {
+            Extrinsic::Unsigned { call_data } => (None, None, None, call_data),
+            Extrinsic::Signed {
+                address,
+                signature,
+                signed_exts,
+                call_data,
+            } => (Some(address), Some(signature), Some(signed_exts), call_data),
+            Extrinsic::General {
+                signed_exts,
+                call_data,
+            } => (None, None, Some(signed_exts), call_data),
+        };
+        let ExtrinsicCallData {
+            pallet_name,
+            call_name,
+            args,
+        } = call_data;
+
+        DecodedExtrinsicOutput {
+            pallet: pallet_name,
+            call: call_name,
+            address,
+            signature,
+            signed_exts: signed_exts.map(NamedValueOutput::from_pairs),
+            mortality: None,
+            args: NamedValueOutput::from_pairs(args),
+            success: None,
+            docs: None,
+        }
+    }
+}
+
+/// The events emitted in a block.
+#[derive(Serialize, JsonSchema)]
+pub struct BlockEventsOutput {
+    pub block_number: u64,
+    /// Hex encoded block hash.
+    pub block_hash: String,
+    pub spec_version: u32,
+    /// The URL of the node that the events were fetched from.
+    pub endpoint: String,
+    pub events: Vec<EventOutput>,
+    /// The reason we failed to decode the block's events, if we did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorOutput>,
+}
+
+/// A single decoded event.
+#[derive(Serialize, JsonSchema)]
+pub struct EventOutput {
+    /// The index of the event in the block.
+    pub index: usize,
+    /// When in the block the event was emitted, eg `{"name": "ApplyExtrinsic", "values": [1]}`.
+    #[serde(serialize_with = "serialize_value")]
+    #[schemars(with = "serde_json::Value")]
+    pub phase: JsonValue,
+    pub pallet: String,
+    pub event: String,
+    /// The event's fields, which are unnamed before V14 metadata.
+    #[serde(serialize_with = "serialize_value")]
+    #[schemars(with = "serde_json::Value")]
+    pub fields: JsonValue,
+    #[serde(serialize_with = "serialize_value")]
+    #[schemars(with = "serde_json::Value")]
+    pub topics: JsonValue,
+}
+
+impl EventOutput {
+    pub fn new(index: usize, event: Event) -> Self {
+        EventOutput {
+            index,
+            phase: event.phase,
+            pallet: event.pallet,
+            event: event.name,
+            fields: JsonValue {
+                value: ValueDef::Composite(event.fields),
+                context: String::new(),
+            },
+            topics: event.topics,
+        }
+    }
+}
+
+/// How often each event was emitted over a range of blocks.
+#[derive(Serialize, JsonSchema)]
+pub struct EventStatsOutput {
+    pub starting_block: u64,
+    /// The last block that was scanned.
+    pub ending_block: Option<u64>,
+    /// The number of blocks whose events were scanned, including those that failed to decode.
+    pub blocks: u64,
+    /// The blocks whose events failed to decode, and so aren't counted.
+    pub failed_blocks: Vec<u64>,
+    /// Each event that was emitted, rarest first.
+    pub events: Vec<EventCountOutput>,
+    /// The number of events emitted in each kind of phase (eg `ApplyExtrinsic`).
+    pub phases: Vec<PhaseCountOutput>,
+}
+
+/// How often some event was emitted, and the first and last blocks that it was emitted in.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct EventCountOutput {
+    pub pallet: String,
+    pub event: String,
+    pub count: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+}
+
+/// The number of events emitted in some kind of phase.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct PhaseCountOutput {
+    pub phase: String,
+    pub count: u64,
+}
+
+/// A call found in a call index.
+#[derive(Serialize, JsonSchema, Debug, PartialEq)]
+pub struct IndexedCallOutput {
+    pub block_number: u64,
+    pub extrinsic_index: usize,
+    pub pallet: String,
+    pub call: String,
+    /// The address of the signer, for signed extrinsics.
+    pub signer: Option<String>,
+    /// Whether the extrinsic succeeded, if this was known when it was indexed.
+    pub success: Option<bool>,
+}
+
+/// A named, decoded value (eg a call argument).
+#[derive(Serialize, JsonSchema)]
+pub struct NamedValueOutput {
+    pub name: String,
+    #[serde(serialize_with = "serialize_value")]
+    #[schemars(with = "serde_json::Value")]
+    pub value: JsonValue,
+}
+
+impl NamedValueOutput {
+    fn from_pairs(pairs: Vec<(String, JsonValue)>) -> Vec<Self> {
+        pairs
+            .into_iter()
+            .map(|(name, value)| NamedValueOutput { name, value })
+            .collect()
+    }
+}
+
+/// The decoded storage items at some storage entry.
+#[derive(Serialize, JsonSchema)]
+pub struct StorageEntryOutput {
+    /// The number used to pick the block that this entry was decoded at.
+    pub number: usize,
+    pub block_number: u32,
+    /// Hex encoded block hash.
+    pub block_hash: String,
+    pub spec_version: u32,
+    pub pallet: String,
+    pub entry: String,
+    /// What the metadata says about the entry, if `--show-entry-info` was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<StorageEntryInfoOutput>,
+    /// The URLs of the nodes that the keys and values were fetched from, if they were fetched
+    /// from any. This is usually just one, unless fetching failed partway through and carried
+    /// on using another.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<String>,
+    /// The era and session that a set of validators was decoded in, if `--session-context`
+    /// was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub era_context: Option<EraContextOutput>,
+    /// The decoded default value of the entry, if `--show-defaults` was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_value")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub default: Option<JsonValue>,
+    /// The reason we failed to decode the default value, if we did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_error: Option<ErrorOutput>,
+    pub keyvals: Vec<StorageKeyValOutput>,
+}
+
+/// The docs, modifier and type names that the metadata gives for some storage entry.
+#[derive(Serialize, JsonSchema)]
+pub struct StorageEntryInfoOutput {
+    pub docs: Vec<String>,
+    /// `Optional` or `Default`.
+    pub modifier: String,
+    pub key_types: Vec<String>,
+    pub value_type: String,
+}
+
+/// A single storage key and value.
+#[derive(Serialize, JsonSchema)]
+pub struct StorageKeyValOutput {
+    /// Hex encoded key bytes, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_bytes: Option<String>,
+    /// Hex encoded value bytes, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_bytes: Option<String>,
+    /// The length and hash of the key bytes, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_digest: Option<BytesDigestOutput>,
+    /// The length and hash of the value bytes, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_digest: Option<BytesDigestOutput>,
+    /// The decoded key parts, if the key was decoded successfully.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<Vec<StorageKeyPartOutput>>,
+    /// The reason we failed to decode the key, if we did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_error: Option<ErrorOutput>,
+    /// The decoded value, if it was decoded successfully.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_value")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub value: Option<JsonValue>,
+    /// The reason we failed to decode the value, if we did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_error: Option<ErrorOutput>,
+    /// Why we didn't decode this key and value, if we skipped them. Neither the key nor the
+    /// value are given for skipped entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<SkipReason>,
+    /// Calls that the value refers to by hash, if `--resolve-call-hashes` was given.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resolved_calls: Vec<ResolvedCallOutput>,
+}
+
+/// The length and hash of some bytes, to identify them without giving the bytes themselves.
+#[derive(Serialize, JsonSchema)]
+pub struct BytesDigestOutput {
+    /// The number of bytes.
+    pub len: usize,
+    /// The hex encoded blake2_256 hash of the bytes.
+    pub blake2_256: String,
+}
+
+impl BytesDigestOutput {
+    pub fn new(bytes: &[u8]) -> Self {
+        BytesDigestOutput {
+            len: bytes.len(),
+            blake2_256: subxt::utils::to_hex(sp_crypto_hashing::blake2_256(bytes)),
+        }
+    }
+}
+
+/// Why a storage key and value weren't decoded.
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The value was too large to fetch.
+    TooLarge,
+    /// The entry is known to be corrupt at this spec version.
+    Corrupt,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::TooLarge => f.write_str("it is too large"),
+            SkipReason::Corrupt => f.write_str("it is corrupt"),
+        }
+    }
+}
+
+/// The era and session at some block, from `Session.CurrentIndex`, `Staking.ActiveEra` and
+/// `Staking.CurrentEra`. Each is missing if it doesn't exist at the block.
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EraContextOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_index: Option<u32>,
+    /// The era that validators are being rewarded in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_era: Option<u32>,
+    /// The era that validators were last elected for, which can be ahead of the active era.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_era: Option<u32>,
+}
+
+impl std::fmt::Display for EraContextOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts = [
+            ("session", self.session_index),
+            ("active era", self.active_era),
+            ("current era", self.current_era),
+        ];
+        let parts: Vec<String> = parts
+            .into_iter()
+            .filter_map(|(name, index)| Some(format!("{name} {}", index?)))
+            .collect();
+        match parts.as_slice() {
+            [] => write!(f, "unknown"),
+            parts => write!(f, "{}", parts.join(", ")),
+        }
+    }
+}
+
+/// A call that some storage value refers to by hash.
+#[derive(Serialize, JsonSchema)]
+pub struct ResolvedCallOutput {
+    /// Hex encoded call hash.
+    pub call_hash: String,
+    /// The block that we looked for the call in.
+    pub block_number: u32,
+    /// The decoded call, if we found it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_value")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub call: Option<JsonValue>,
+}
+
+/// One part of a storage key. The key bytes after the hashed pallet and entry names are the
+/// `hash` followed by the `value_bytes` (if any) of each part in turn, so the key can be
+/// rebuilt from these.
+#[derive(Serialize, JsonSchema)]
+pub struct StorageKeyPartOutput {
+    /// The name given to this part of the key in the types file, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The hasher used for this part of the key.
+    pub hasher: String,
+    /// Hex encoded hash. Empty for the identity hasher.
+    pub hash: String,
+    /// The decoded value, if the hasher is a concat or identity hasher.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_value")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub value: Option<JsonValue>,
+    /// The hex encoded SCALE bytes of the value, if the hasher is a concat or identity hasher.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_bytes: Option<String>,
+}
+
+impl From<&StorageKey> for StorageKeyPartOutput {
+    fn from(key: &StorageKey) -> Self {
+        StorageKeyPartOutput {
+            name: key.name.clone(),
+            hasher: storage_decoder::hasher_name(key.hasher).to_owned(),
+            hash: subxt::utils::to_hex(&key.hash),
+            value: key.value.clone(),
+            value_bytes: key
+                .value
+                .is_some()
+                .then(|| subxt::utils::to_hex(&key.value_bytes)),
+        }
+    }
+}
+
+/// An error.
+#[derive(Serialize, JsonSchema)]
+pub struct ErrorOutput {
+    /// The error message, including any underlying causes.
+    pub message: String,
+    /// What kind of problem this is, eg whether we failed to fetch something or to decode it.
+    pub category: ErrorCategory,
+    /// A guess at how the layout of the value differs from the type it was decoded as, if
+    /// the value failed to decode in a way that a common historic layout change explains.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<LayoutHint>,
+    /// The limit that was exceeded, if decoding was abandoned because the value was getting
+    /// suspiciously large.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_exceeded: Option<LimitExceeded>,
+    /// With `--strict-encoding`, the details of a value which decoded but wasn't canonically
+    /// encoded, if that's why it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub non_canonical: Option<NonCanonical>,
+    /// The version of an extrinsic given in a format that we can't decode, if that's why it
+    /// failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsupported_version: Option<UnsupportedVersion>,
+}
+
+impl ErrorOutput {
+    pub fn new(err: &anyhow::Error) -> Self {
+        ErrorOutput {
+            message: format!("{err:#}"),
+            category: ErrorCategory::of(err),
+            hint: layout_hint_of(err),
+            limit_exceeded: limit_exceeded_of(err),
+            non_canonical: non_canonical_of(err),
+            unsupported_version: unsupported_version_of(err),
+        }
+    }
+}
+
+/// An error fetching something, which we either retried after or gave up on.
+#[derive(Serialize, JsonSchema)]
+pub struct RunnerErrorOutput {
+    /// The task being worked on; the block number, or for storage, the index of the entry
+    /// (or the number of the block being sampled, if we couldn't fetch its details).
+    pub task_number: u64,
+    /// Whether this happened while connecting to a node or while fetching the item.
+    pub stage: RunnerStage,
+    /// Whether we gave up on the task, in which case it has no other output. Otherwise we
+    /// tried again.
+    pub gave_up: bool,
+    pub error: ErrorOutput,
+}
+
+impl RunnerErrorOutput {
+    pub fn new(err: &RunnerError) -> Self {
+        RunnerErrorOutput {
+            task_number: err.task_number,
+            stage: err.stage,
+            gave_up: err.gave_up,
+            error: ErrorOutput::new(&err.error),
+        }
+    }
+}
+
+/// Write out an error from the runner in the given format, so that it ends up alongside
+/// whatever else we're outputting.
+pub fn write_runner_error<W: std::io::Write>(
+    format: OutputFormat,
+    mut w: W,
+    err: &RunnerErrorOutput,
+) -> anyhow::Result<()> {
+    if format.is_json() {
+        return write_output_line(format, w, err);
+    }
+    let what = match err.stage {
+        RunnerStage::Init => "connecting",
+        RunnerStage::Task => "fetching",
+    };
+    let outcome = if err.gave_up {
+        "giving up on it"
+    } else {
+        "retrying"
+    };
+    writeln!(
+        w,
+        "Error {what} for task {} ({outcome}): {}",
+        err.task_number, err.error.message
+    )?;
+    Ok(())
+}
+
+/// Write an output item as a single line of JSON.
+pub fn write_json_line<W: std::io::Write, T: Serialize>(mut w: W, item: &T) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut w, item)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Write an output item as a single line of JSON in the given JSON format, so that any
+/// decoded values in it are written as typed JSON if asked for.
+pub fn write_output_line<W: std::io::Write, T: Serialize>(
+    format: OutputFormat,
+    w: W,
+    item: &T,
+) -> anyhow::Result<()> {
+    TYPED_VALUES.with(|typed| typed.set(format == OutputFormat::TypedJson));
+    let res = write_json_line(w, item);
+    TYPED_VALUES.with(|typed| typed.set(false));
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scale_value::Value;
+    use serde_json::json;
+
+    #[test]
+    fn writes_typed_values() {
+        let free = Value::u128(u128::MAX).map_context(|_| "u128".to_owned());
+        let reserved =
+            Value::variant("None", Composite::unnamed([])).map_context(|_| String::new());
+        let value = Value {
+            value: ValueDef::Composite(Composite::Named(vec![
+                ("free".to_owned(), free),
+                ("reserved".to_owned(), reserved),
+            ])),
+            context: "AccountData".to_owned(),
+        };
+
+        let output = NamedValueOutput {
+            name: "data".to_owned(),
+            value,
+        };
+        let mut typed = Vec::new();
+        write_output_line(OutputFormat::TypedJson, &mut typed, &output).unwrap();
+        let typed: serde_json::Value = serde_json::from_slice(&typed).unwrap();
+        assert_eq!(
+            typed,
+            json!({
+                "name": "data",
+                "value": {
+                    "type": "AccountData",
+                    "named": {
+                        "free": { "type": "u128", "u128": u128::MAX.to_string() },
+                        "reserved": { "variant": "None", "unnamed": [] },
+                    },
+                },
+            })
+        );
+
+        // Values are written as plain JSON otherwise.
+        let plain = serde_json::to_string(&output).unwrap();
+        assert!(plain.contains(r#""reserved":{"name":"None","values":[]}"#));
+    }
+}
@@ -0,0 +1,103 @@
+//! The failures found by previous runs, read back from their JSON output (as written with
+//! `--format json`), so that we can sample more heavily around them.
+
+use super::exit_code::ErrorKind;
+use anyhow::Context;
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// How many times more heavily we sample things that failed before.
+pub const FAILURE_WEIGHT: usize = 4;
+
+#[derive(Default)]
+pub struct PreviousFailures {
+    /// The spec versions that any blocks or storage entries failed to decode at.
+    spec_versions: HashSet<u32>,
+    /// The (lowercased) pallet and entry names of storage entries that failed to decode.
+    storage_entries: HashSet<(String, String)>,
+}
+
+impl PreviousFailures {
+    /// Load the failures from the JSON output of some previous runs. Lines that aren't decoded
+    /// blocks or storage entries (like warnings) are ignored.
+    pub fn load(paths: &[impl AsRef<Path>]) -> anyhow::Result<Self> {
+        let mut failures = PreviousFailures::default();
+        for path in paths {
+            let path = path.as_ref();
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("Could not read previous output {}", path.display()))
+                .context(ErrorKind::Config)?;
+            for line in contents.lines() {
+                failures.add_line(line);
+            }
+        }
+        Ok(failures)
+    }
+
+    fn add_line(&mut self, line: &str) {
+        let Ok(item) = serde_json::from_str::<JsonValue>(line) else {
+            return;
+        };
+        let Some(spec_version) = item["spec_version"].as_u64() else {
+            return;
+        };
+        if !has_error(&item) {
+            return;
+        }
+
+        self.spec_versions.insert(spec_version as u32);
+        if let (Some(pallet), Some(entry)) = (item["pallet"].as_str(), item["entry"].as_str()) {
+            self.storage_entries
+                .insert((pallet.to_ascii_lowercase(), entry.to_ascii_lowercase()));
+        }
+    }
+
+    /// Did anything fail to decode at this spec version?
+    pub fn has_spec_version(&self, spec_version: u32) -> bool {
+        self.spec_versions.contains(&spec_version)
+    }
+
+    /// Did this storage entry fail to decode (at any spec version)?
+    pub fn has_storage_entry(&self, pallet: &str, entry: &str) -> bool {
+        self.storage_entries
+            .contains(&(pallet.to_ascii_lowercase(), entry.to_ascii_lowercase()))
+    }
+}
+
+/// Does a decoded block or storage entry contain any errors?
+fn has_error(item: &JsonValue) -> bool {
+    let items = |name| item[name].as_array().into_iter().flatten();
+
+    !item["default_error"].is_null()
+        || items("extrinsics").any(|ext| !ext["error"].is_null())
+        || items("keyvals").any(|kv| !kv["key_error"].is_null() || !kv["value_error"].is_null())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_failures_in_output() {
+        let mut failures = PreviousFailures::default();
+        for line in [
+            r#"{"warning":"no_types_for_spec_version","block_number":1,"spec_version":0}"#,
+            r#"{"block_number":1,"spec_version":1,"extrinsics":[{"index":0,"decoded":{}}]}"#,
+            r#"{"block_number":2,"spec_version":2,"extrinsics":[{"index":0,"error":{"message":"bad"}}]}"#,
+            r#"{"number":0,"spec_version":3,"pallet":"System","entry":"Account","keyvals":[{"value_error":{"message":"bad"}}]}"#,
+            r#"{"number":1,"spec_version":4,"pallet":"System","entry":"Number","keyvals":[{"value":1}]}"#,
+            "not json",
+        ] {
+            failures.add_line(line);
+        }
+
+        assert!(!failures.has_spec_version(0));
+        assert!(!failures.has_spec_version(1));
+        assert!(failures.has_spec_version(2));
+        assert!(failures.has_spec_version(3));
+        assert!(!failures.has_spec_version(4));
+        assert!(failures.has_storage_entry("system", "account"));
+        assert!(!failures.has_storage_entry("System", "Number"));
+    }
+}
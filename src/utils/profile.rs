@@ -0,0 +1,139 @@
+//! Timing the fixed costs of preparing to decode each spec version: downloading its metadata,
+//! building a type registry from that metadata and merging it with the historic types. These
+//! dominate short runs, and are printed at the end of the run with `--profile`.
+
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Something that we time for each spec version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Fetching the metadata from a node.
+    MetadataDownload,
+    /// Building the types that we derive from the metadata (see
+    /// [`crate::decoding::builtin_types::builtin_types_from_metadata`]).
+    TypeRegistryFromMetadata,
+    /// Picking the historic types for the spec version and adding those derived from the
+    /// metadata to them.
+    RegistryMerging,
+}
+
+/// The total time spent on each stage for some spec version. A stage can happen more than
+/// once if several tasks prepare the same spec version at the same time.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    #[serde(rename = "metadata_download_secs", serialize_with = "secs")]
+    pub metadata_download: Duration,
+    #[serde(rename = "type_registry_from_metadata_secs", serialize_with = "secs")]
+    pub type_registry_from_metadata: Duration,
+    #[serde(rename = "registry_merging_secs", serialize_with = "secs")]
+    pub registry_merging: Duration,
+}
+
+impl Timings {
+    fn total(&self) -> Duration {
+        self.metadata_download + self.type_registry_from_metadata + self.registry_merging
+    }
+}
+
+fn secs<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+static TIMINGS: Mutex<BTreeMap<u32, Timings>> = Mutex::new(BTreeMap::new());
+
+/// Record that some stage took the given time for some spec version.
+pub fn record(spec_version: u32, stage: Stage, elapsed: Duration) {
+    let mut timings = TIMINGS.lock().unwrap();
+    let timings = timings.entry(spec_version).or_default();
+    let total = match stage {
+        Stage::MetadataDownload => &mut timings.metadata_download,
+        Stage::TypeRegistryFromMetadata => &mut timings.type_registry_from_metadata,
+        Stage::RegistryMerging => &mut timings.registry_merging,
+    };
+    *total += elapsed;
+}
+
+/// Run the given future, recording how long it took as some stage for some spec version.
+pub async fn timed<F: Future>(spec_version: u32, stage: Stage, f: F) -> F::Output {
+    let started = Instant::now();
+    let output = f.await;
+    record(spec_version, stage, started.elapsed());
+    output
+}
+
+/// The timings recorded so far, by spec version.
+pub fn summary() -> BTreeMap<u32, Timings> {
+    TIMINGS.lock().unwrap().clone()
+}
+
+/// Write a human readable table of the timings for each spec version, and their totals.
+pub fn write_summary<W: std::io::Write>(
+    mut w: W,
+    summary: &BTreeMap<u32, Timings>,
+) -> std::io::Result<()> {
+    let ms = |duration: Duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.0);
+    writeln!(
+        w,
+        "Preparation time by spec version (metadata download, registry from metadata, registry merging):"
+    )?;
+    let mut totals = Timings::default();
+    for (spec_version, timings) in summary {
+        writeln!(
+            w,
+            "  {spec_version}: {}, {}, {} (total {})",
+            ms(timings.metadata_download),
+            ms(timings.type_registry_from_metadata),
+            ms(timings.registry_merging),
+            ms(timings.total())
+        )?;
+        totals.metadata_download += timings.metadata_download;
+        totals.type_registry_from_metadata += timings.type_registry_from_metadata;
+        totals.registry_merging += timings.registry_merging;
+    }
+    writeln!(
+        w,
+        "  Total: {}, {}, {} (total {})",
+        ms(totals.metadata_download),
+        ms(totals.type_registry_from_metadata),
+        ms(totals.registry_merging),
+        ms(totals.total())
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_timings_by_spec_version() {
+        // Other tests prepare decoding contexts too, so use a spec version that they won't.
+        let spec_version = u32::MAX;
+        record(
+            spec_version,
+            Stage::RegistryMerging,
+            Duration::from_millis(2),
+        );
+        record(
+            spec_version,
+            Stage::RegistryMerging,
+            Duration::from_millis(3),
+        );
+        timed(spec_version, Stage::MetadataDownload, async {}).await;
+
+        let summary = summary();
+        let timings = summary[&spec_version];
+        assert_eq!(timings.registry_merging, Duration::from_millis(5));
+        assert_eq!(timings.type_registry_from_metadata, Duration::ZERO);
+
+        let mut out = Vec::new();
+        let only_this = BTreeMap::from([(spec_version, timings)]);
+        write_summary(&mut out, &only_this).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(&format!("  {spec_version}: ")));
+        assert!(out.contains(", 5.0ms ("));
+    }
+}
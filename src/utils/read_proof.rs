@@ -0,0 +1,217 @@
+//! Finding storage values in the proofs returned by `state_getReadProof`. A proof is the set of
+//! trie nodes on the path from the state root to each key asked for, so by walking these nodes
+//! we can find a value without fetching it with `state_getStorage`. Some providers allow
+//! larger responses to this than to `state_getStorage`, which lets us fetch values that would
+//! otherwise be too large.
+
+use anyhow::{anyhow, bail};
+use parity_scale_codec::{Compact, Decode};
+use std::collections::HashMap;
+use subxt::utils::H256;
+
+/// Find the value stored at some key in a read proof for it, given the state root of the block
+/// that the proof was made at. Returns `None` if the proof shows that nothing is stored there.
+pub fn value_from_proof(
+    state_root: H256,
+    key: &[u8],
+    proof: &[Vec<u8>],
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let nodes: HashMap<[u8; 32], &[u8]> = proof
+        .iter()
+        .map(|node| (sp_crypto_hashing::blake2_256(node), node.as_slice()))
+        .collect();
+    let node_by_hash = |hash: &[u8]| {
+        nodes.get(hash).copied().ok_or_else(|| {
+            anyhow!(
+                "The proof is missing the node with hash 0x{}",
+                hex::encode(hash)
+            )
+        })
+    };
+
+    let key: Vec<u8> = key.iter().flat_map(|b| [b >> 4, b & 0xf]).collect();
+    let mut key = key.as_slice();
+    let mut node_bytes = node_by_hash(&state_root.0)?;
+    loop {
+        let node = Node::decode(node_bytes)?;
+        let Some(rest) = key.strip_prefix(node.partial_key.as_slice()) else {
+            return Ok(None);
+        };
+        key = rest;
+
+        let Some((&nibble, rest)) = key.split_first() else {
+            return match node.value {
+                None => Ok(None),
+                Some(NodeValue::Inline(value)) => Ok(Some(value.to_vec())),
+                Some(NodeValue::Hash(hash)) => Ok(Some(node_by_hash(hash)?.to_vec())),
+            };
+        };
+        key = rest;
+        node_bytes = match node.children[nibble as usize] {
+            None => return Ok(None),
+            Some(NodeHandle::Inline(child)) => child,
+            Some(NodeHandle::Hash(hash)) => node_by_hash(hash)?,
+        };
+    }
+}
+
+/// A decoded trie node. Leaves are nodes without children.
+struct Node<'a> {
+    /// The nibbles of the key between this node and its parent.
+    partial_key: Vec<u8>,
+    value: Option<NodeValue<'a>>,
+    children: [Option<NodeHandle<'a>>; 16],
+}
+
+enum NodeValue<'a> {
+    Inline(&'a [u8]),
+    /// The hash of a node in the proof which is the value (for large values in state version 1).
+    Hash(&'a [u8]),
+}
+
+enum NodeHandle<'a> {
+    /// A child node which is small enough to be stored in its parent.
+    Inline(&'a [u8]),
+    Hash(&'a [u8]),
+}
+
+impl<'a> Node<'a> {
+    /// Decode a node, encoded as Substrate's trie codec does.
+    fn decode(bytes: &'a [u8]) -> anyhow::Result<Self> {
+        let input = &mut &*bytes;
+        let mut node = Node {
+            partial_key: Vec::new(),
+            value: None,
+            children: Default::default(),
+        };
+
+        let header = take(input, 1)?[0];
+        if header == 0 {
+            return Ok(node);
+        }
+        // The top bits say what kind of node this is, and the rest are the partial key length.
+        let (is_branch, value_kind, prefix_bits) = match header >> 6 {
+            0b01 => (false, Some(ValueKind::Inline), 2),
+            0b10 => (true, None, 2),
+            0b11 => (true, Some(ValueKind::Inline), 2),
+            _ if header >> 5 == 0b001 => (false, Some(ValueKind::Hash), 3),
+            _ if header >> 4 == 0b0001 => (true, Some(ValueKind::Hash), 4),
+            _ => bail!("Invalid trie node header {header:#010b}"),
+        };
+        let nibble_count = partial_key_len(header, prefix_bits, input)?;
+
+        // Odd numbers of nibbles are padded with a leading zero nibble.
+        let partial_key = take(input, nibble_count.div_ceil(2))?;
+        node.partial_key = partial_key
+            .iter()
+            .flat_map(|b| [b >> 4, b & 0xf])
+            .skip(nibble_count % 2)
+            .collect();
+
+        let bitmap = if is_branch {
+            u16::from_le_bytes(take(input, 2)?.try_into().unwrap())
+        } else {
+            0
+        };
+        node.value = match value_kind {
+            None => None,
+            Some(ValueKind::Inline) => {
+                let len = Compact::<u32>::decode(input)?.0 as usize;
+                Some(NodeValue::Inline(take(input, len)?))
+            }
+            Some(ValueKind::Hash) => Some(NodeValue::Hash(take(input, 32)?)),
+        };
+        for (idx, child) in node.children.iter_mut().enumerate() {
+            if bitmap & (1 << idx) == 0 {
+                continue;
+            }
+            let len = Compact::<u32>::decode(input)?.0 as usize;
+            let handle = take(input, len)?;
+            *child = Some(if len == 32 {
+                NodeHandle::Hash(handle)
+            } else {
+                NodeHandle::Inline(handle)
+            });
+        }
+        Ok(node)
+    }
+}
+
+enum ValueKind {
+    Inline,
+    Hash,
+}
+
+/// The number of nibbles in a node's partial key, which is given by the header bits after the
+/// node kind, followed by further bytes if it doesn't fit there.
+fn partial_key_len(header: u8, prefix_bits: u32, input: &mut &[u8]) -> anyhow::Result<usize> {
+    let max = (0xff >> prefix_bits) as usize;
+    let mut len = header as usize & max;
+    if len < max {
+        return Ok(len);
+    }
+    loop {
+        let n = take(input, 1)?[0] as usize;
+        len += n;
+        if n < 255 {
+            return Ok(len);
+        }
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> anyhow::Result<&'a [u8]> {
+    if input.len() < n {
+        bail!("Unexpected end of trie node");
+    }
+    let (taken, rest) = input.split_at(n);
+    *input = rest;
+    Ok(taken)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parity_scale_codec::Encode;
+
+    #[test]
+    fn finds_values_in_proofs() {
+        // A large value, stored as its own node (as in state version 1).
+        let large_value = vec![7u8; 40];
+        // Below the root (partial key nibble "1") and its child at nibble "2", a leaf for the
+        // key 0x12ab with partial key nibbles "ab" and the hash of the large value.
+        let large_leaf = [
+            &[0b0010_0010, 0xab][..],
+            &sp_crypto_hashing::blake2_256(&large_value),
+        ]
+        .concat();
+        // An inline leaf for the key 0x1c at the root's child at nibble "c", with an inline value.
+        let small_leaf = [&[0b0100_0000][..], &vec![1u8, 2, 3].encode()].concat();
+        let bitmap: u16 = (1 << 2) | (1 << 0xc);
+        let root = [
+            &[0b1000_0001, 0x01][..],
+            &bitmap.to_le_bytes(),
+            &Compact(32u32).encode(),
+            &sp_crypto_hashing::blake2_256(&large_leaf),
+            &Compact(small_leaf.len() as u32).encode(),
+            &small_leaf,
+        ]
+        .concat();
+        let state_root = H256(sp_crypto_hashing::blake2_256(&root));
+        let proof = vec![root, large_leaf, large_value.clone()];
+
+        assert_eq!(
+            value_from_proof(state_root, &[0x12, 0xab], &proof).unwrap(),
+            Some(large_value)
+        );
+        assert_eq!(
+            value_from_proof(state_root, &[0x1c], &proof).unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(value_from_proof(state_root, &[0x13], &proof).unwrap(), None);
+        assert_eq!(
+            value_from_proof(state_root, &[0x12, 0xac], &proof).unwrap(),
+            None
+        );
+        assert!(value_from_proof(state_root, &[0x12, 0xab], &proof[..1]).is_err());
+    }
+}
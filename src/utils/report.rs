@@ -0,0 +1,307 @@
+//! An HTML report of a run, for sharing results with people who'd rather not read the CLI
+//! output. Blocks (or, for storage, each `number`) are sections, in which calls or storage
+//! entries are grouped by pallet. Each item can be expanded to show the JSON that
+//! `--format json` would write for it, and anything that failed to decode is highlighted.
+
+use super::exit_code::ErrorKind;
+use super::output::{BlockOutput, RunnerErrorOutput, StorageEntryOutput};
+use super::run_config::RunConfig;
+use anyhow::Context;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+const HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Decoding report</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+details { margin: 0.2em 0 0.2em 1.5em; }
+summary { cursor: pointer; }
+.error > summary { color: #b00020; font-weight: bold; }
+.error-message { color: #b00020; white-space: pre-wrap; }
+pre { background: #f4f4f4; padding: 0.5em; overflow-x: auto; }
+</style>
+</head>
+<body>
+<h1>Decoding report</h1>
+"#;
+
+pub struct Report {
+    inner: Mutex<ReportInner>,
+}
+
+struct ReportInner {
+    file: BufWriter<File>,
+    /// Storage entries for each number that hasn't been finished yet.
+    pending: BTreeMap<usize, Vec<JsonValue>>,
+    sections: usize,
+    sections_with_errors: usize,
+}
+
+impl Report {
+    /// Create the report file, overwriting it if it exists already.
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Could not create report {}", path.display()))
+            .context(ErrorKind::Config)?;
+        let mut file = BufWriter::new(file);
+        file.write_all(HEADER.as_bytes())?;
+        Ok(Report {
+            inner: Mutex::new(ReportInner {
+                file,
+                pending: BTreeMap::new(),
+                sections: 0,
+                sections_with_errors: 0,
+            }),
+        })
+    }
+
+    /// Add the configuration of the run to the report, so that it's clear what produced it.
+    pub fn add_config(&self, config: &RunConfig) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(config)?;
+        let mut inner = self.inner.lock().unwrap();
+        writeln!(
+            inner.file,
+            "<details><summary>Configuration</summary>\n<pre>{}</pre>\n</details>",
+            escape(&json)
+        )?;
+        Ok(())
+    }
+
+    /// Add a decoded block to the report.
+    pub fn add_block(&self, block: &BlockOutput) -> anyhow::Result<()> {
+        let block = serde_json::to_value(block)?;
+        let mut items = Vec::new();
+        for ext in block["extrinsics"].as_array().into_iter().flatten() {
+            let index = &ext["index"];
+            let item = match ext["error"]["message"].as_str() {
+                Some(message) => Item {
+                    pallet: "Failed to decode".to_owned(),
+                    name: format!("#{index}"),
+                    error: Some(message.to_owned()),
+                    json: ext,
+                },
+                None => Item {
+                    pallet: ext["decoded"]["pallet"].as_str().unwrap_or("?").to_owned(),
+                    name: format!(
+                        "#{index} {}.{}",
+                        ext["decoded"]["pallet"].as_str().unwrap_or("?"),
+                        ext["decoded"]["call"].as_str().unwrap_or("?")
+                    ),
+                    error: None,
+                    json: ext,
+                },
+            };
+            items.push(item);
+        }
+
+        let title = format!(
+            "Block {} (spec version {})",
+            block["block_number"], block["spec_version"]
+        );
+        self.inner.lock().unwrap().write_section(&title, &items)
+    }
+
+    /// Add a decoded storage entry to the report. It's written once [`Report::finish_number`]
+    /// is called for its number.
+    pub fn add_storage_entry(&self, entry: &StorageEntryOutput) -> anyhow::Result<()> {
+        let entry = serde_json::to_value(entry)?;
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .pending
+            .entry(entry["number"].as_u64().unwrap_or_default() as usize)
+            .or_default()
+            .push(entry);
+        Ok(())
+    }
+
+    /// Write out the storage entries for some number, now that they've all been added.
+    pub fn finish_number(&self, number: usize) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(entries) = inner.pending.remove(&number) else {
+            return Ok(());
+        };
+
+        let items: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let errors: Vec<_> = std::iter::once(&entry["default_error"])
+                    .chain(
+                        entry["keyvals"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .flat_map(|kv| [&kv["key_error"], &kv["value_error"]]),
+                    )
+                    .filter_map(|e| e["message"].as_str())
+                    .collect();
+                let skipped = entry["keyvals"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|kv| !kv["skipped"].is_null())
+                    .count();
+                let pallet = entry["pallet"].as_str().unwrap_or("?");
+                let mut name = format!("{pallet}.{}", entry["entry"].as_str().unwrap_or("?"));
+                if skipped > 0 {
+                    name.push_str(&format!(" ({skipped} skipped)"));
+                }
+                Item {
+                    pallet: pallet.to_owned(),
+                    name,
+                    error: (!errors.is_empty()).then(|| errors.join("\n")),
+                    json: entry,
+                }
+            })
+            .collect();
+
+        let first = &entries[0];
+        let title = format!(
+            "Number {number}: block {} (spec version {})",
+            first["block_number"], first["spec_version"]
+        );
+        inner.write_section(&title, &items)
+    }
+
+    /// Add an error fetching something to the report, as a section of its own.
+    pub fn add_runner_error(&self, err: &RunnerErrorOutput) -> anyhow::Result<()> {
+        let json = serde_json::to_value(err)?;
+        let title = if err.gave_up {
+            format!("Task {}: gave up", err.task_number)
+        } else {
+            format!("Task {}: retried", err.task_number)
+        };
+        let item = Item {
+            pallet: "Failed to fetch".to_owned(),
+            name: format!("Task {}", err.task_number),
+            error: Some(err.error.message.clone()),
+            json: &json,
+        };
+        self.inner.lock().unwrap().write_section(&title, &[item])
+    }
+
+    /// Write out anything still pending and end the report.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        let numbers: Vec<_> = self.inner.lock().unwrap().pending.keys().copied().collect();
+        for number in numbers {
+            self.finish_number(number)?;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let summary = format!(
+            "<p>{} sections, {} with errors.</p>\n</body>\n</html>\n",
+            inner.sections, inner.sections_with_errors
+        );
+        inner.file.write_all(summary.as_bytes())?;
+        inner.file.flush()?;
+        Ok(())
+    }
+}
+
+/// One call or storage entry in the report.
+struct Item<'a> {
+    pallet: String,
+    name: String,
+    error: Option<String>,
+    json: &'a JsonValue,
+}
+
+impl ReportInner {
+    fn write_section(&mut self, title: &str, items: &[Item]) -> anyhow::Result<()> {
+        let has_errors = items.iter().any(|item| item.error.is_some());
+        self.sections += 1;
+        self.sections_with_errors += has_errors as usize;
+
+        // Group items by pallet, keeping them in the order that each pallet first appears.
+        let mut pallets: Vec<(&str, Vec<&Item>)> = Vec::new();
+        for item in items {
+            match pallets
+                .iter_mut()
+                .find(|(pallet, _)| *pallet == item.pallet)
+            {
+                Some((_, pallet_items)) => pallet_items.push(item),
+                None => pallets.push((&item.pallet, vec![item])),
+            }
+        }
+
+        let f = &mut self.file;
+        writeln!(
+            f,
+            "<details{}><summary>{}</summary>",
+            details_attrs(has_errors),
+            escape(title)
+        )?;
+        for (pallet, pallet_items) in pallets {
+            let pallet_has_errors = pallet_items.iter().any(|item| item.error.is_some());
+            writeln!(
+                f,
+                "<details{}><summary>{} ({})</summary>",
+                details_attrs(pallet_has_errors),
+                escape(pallet),
+                pallet_items.len()
+            )?;
+            for item in pallet_items {
+                writeln!(
+                    f,
+                    "<details{}><summary>{}</summary>",
+                    if item.error.is_some() {
+                        r#" class="error""#
+                    } else {
+                        ""
+                    },
+                    escape(&item.name)
+                )?;
+                if let Some(error) = &item.error {
+                    writeln!(f, r#"<div class="error-message">{}</div>"#, escape(error))?;
+                }
+                let json = serde_json::to_string_pretty(item.json)?;
+                writeln!(f, "<pre>{}</pre>\n</details>", escape(&json))?;
+            }
+            writeln!(f, "</details>")?;
+        }
+        writeln!(f, "</details>")?;
+        Ok(())
+    }
+}
+
+/// Sections with errors in are highlighted and open to begin with.
+fn details_attrs(has_errors: bool) -> &'static str {
+    if has_errors {
+        r#" class="error" open"#
+    } else {
+        ""
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape(r#"<T::Moment as "x"> & co"#),
+            "&lt;T::Moment as &quot;x&quot;&gt; &amp; co"
+        );
+    }
+}
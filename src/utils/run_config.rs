@@ -0,0 +1,122 @@
+//! The effective configuration of a run: the endpoints that passed their checks, the input
+//! files that were used (and hashes of their contents), and anything limiting what was
+//! decoded. This is printed when a run starts and written to its report, so that results can
+//! be attributed to exactly what produced them later.
+
+use super::exit_code::ErrorKind;
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RunConfig {
+    /// The subcommand that was run, eg `decode-blocks`.
+    pub command: &'static str,
+    /// The URLs that we're decoding from, once their genesis hashes have been checked.
+    pub endpoints: Vec<String>,
+    pub types: InputFile,
+    pub spec_versions: Option<InputFile>,
+    /// The options limiting which blocks or storage entries are decoded (or output), by name,
+    /// for those that were given.
+    pub filters: BTreeMap<&'static str, String>,
+    /// The seed that storage sampling was shifted by, if there's any sampling.
+    pub seed: Option<u64>,
+}
+
+/// An input file, along with the hex encoded twox_64 hash of its contents (as printed when
+/// sampling with spec versions).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct InputFile {
+    pub path: String,
+    pub hash: String,
+}
+
+impl InputFile {
+    /// Hash the contents of the file at the given path.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read(path)
+            .with_context(|| format!("Could not read {}", path.display()))
+            .context(ErrorKind::Config)?;
+        Ok(InputFile {
+            path: path.display().to_string(),
+            hash: subxt::utils::to_hex(sp_crypto_hashing::twox_64(&contents)),
+        })
+    }
+}
+
+impl RunConfig {
+    /// A configuration with no spec versions, filters or seed.
+    pub fn new(command: &'static str, endpoints: &[String], types: &Path) -> anyhow::Result<Self> {
+        Ok(RunConfig {
+            command,
+            endpoints: endpoints.to_vec(),
+            types: InputFile::read(types)?,
+            spec_versions: None,
+            filters: BTreeMap::new(),
+            seed: None,
+        })
+    }
+
+    /// Add a filter that was given, by its option name (eg `ending-block`).
+    pub fn filter(&mut self, name: &'static str, value: Option<impl ToString>) {
+        if let Some(value) = value {
+            self.filters.insert(name, value.to_string());
+        }
+    }
+
+    /// Add a flag that filters things if it was set (eg `errors-only`).
+    pub fn flag(&mut self, name: &'static str, set: bool) {
+        self.filter(name, set.then_some(true));
+    }
+}
+
+impl std::fmt::Display for RunConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Configuration ({}):", self.command)?;
+        writeln!(f, "  Endpoints: {}", self.endpoints.join(", "))?;
+        writeln!(f, "  Types: {} ({})", self.types.path, self.types.hash)?;
+        if let Some(spec_versions) = &self.spec_versions {
+            writeln!(
+                f,
+                "  Spec versions: {} ({})",
+                spec_versions.path, spec_versions.hash
+            )?;
+        }
+        if let Some(seed) = self.seed {
+            writeln!(f, "  Seed: {seed}")?;
+        }
+        if self.filters.is_empty() {
+            write!(f, "  Filters: none")
+        } else {
+            let filters: Vec<_> = self
+                .filters
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            write!(f, "  Filters: {}", filters.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn describes_configuration() {
+        let types = std::env::temp_dir().join("run_config_test_types.yaml");
+        std::fs::write(&types, "global: {}\n").unwrap();
+        let mut config =
+            RunConfig::new("decode-blocks", &["wss://a.example.com".to_owned()], &types).unwrap();
+        config.filter("ending-block", Some(100));
+        config.filter("shard", None::<String>);
+        config.flag("errors-only", true);
+        config.flag("only-uncovered", false);
+
+        assert_eq!(config.types.hash.len(), 2 + 16);
+        let described = config.to_string();
+        assert!(described.contains("  Endpoints: wss://a.example.com\n"));
+        assert!(described.ends_with("  Filters: ending-block=100, errors-only=true"));
+    }
+}
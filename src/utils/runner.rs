@@ -1,13 +1,50 @@
+use super::exit_code::ErrorKind;
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::future::Future;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Something handed to the output function of a [`Runner`].
+pub enum RunnerItem<Output> {
+    /// The output of a task. These are handed over in order of task number.
+    Output(Output),
+    /// Something went wrong. Errors that we go on to retry after are handed over as they
+    /// happen, while task numbers that we give up on are handed over in order, in place of
+    /// their output.
+    Error(RunnerError),
+}
+
+/// An error initialising a workload or running a task.
+#[derive(Debug)]
+pub struct RunnerError {
+    /// The task number that was being worked on.
+    pub task_number: u64,
+    pub stage: RunnerStage,
+    /// Whether we gave up on this task number, in which case it'll have no output.
+    pub gave_up: bool,
+    pub error: anyhow::Error,
+}
+
+/// Where in the [`Runner`] an error happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RunnerStage {
+    /// Initialising a workload, eg connecting to a node.
+    Init,
+    /// Running a task with some workload.
+    Task,
+}
 
 pub struct Runner<State, InitFn, TaskFn, OutputFn> {
     initial_state: Arc<State>,
     init_fn: Arc<InitFn>,
     task_fn: Arc<TaskFn>,
     output_fn: OutputFn,
+    step: u64,
 }
 
 impl<State, InitFn, TaskFn, OutputFn, WorkloadFut, Workload, OutputFut, Output>
@@ -19,7 +56,7 @@ where
     Workload: Send,
     TaskFn: Send + Sync + 'static + Fn(u64, &Workload) -> OutputFut,
     OutputFut: Send + Future<Output = anyhow::Result<Option<Output>>>,
-    OutputFn: Send + Sync + 'static + FnMut(Output) -> anyhow::Result<()>,
+    OutputFn: Send + Sync + 'static + FnMut(RunnerItem<Output>) -> anyhow::Result<()>,
     Output: Send + 'static,
 {
     pub fn new(state: State, init_fn: InitFn, task_fn: TaskFn, output_fn: OutputFn) -> Self {
@@ -28,13 +65,29 @@ where
             init_fn: Arc::new(init_fn),
             task_fn: Arc::new(task_fn),
             output_fn,
+            step: 1,
         }
     }
 
+    /// Only run every `step`th task number counting from the starting one, so that several
+    /// runners (eg on different machines) can share out the task numbers between them.
+    pub fn every(mut self, step: u64) -> Self {
+        self.step = step.max(1);
+        self
+    }
+
     pub async fn run(mut self, num_tasks: usize, starting_task_number: u64) -> anyhow::Result<()> {
         const MAX_RETRIES: usize = 5;
+        // How many times in a row a task can fail to initialise a workload or to
+        // successfully run before we give up entirely.
+        const MAX_RESTARTS: u32 = 10;
+        // How many fresh workloads a single task number can fail on before we give up on
+        // just that task number and move on to the next one.
+        const MAX_TASK_RESTARTS: u32 = 2;
 
         let next_task_num = Arc::new(AtomicU64::new(starting_task_number));
+        let gave_up = Arc::new(AtomicBool::new(false));
+        let step = self.step;
         let (output_tx, mut output_rx) = tokio::sync::mpsc::channel(10);
 
         // Kick off all of the tasks.
@@ -44,15 +97,28 @@ where
             let task_fn = self.task_fn.clone();
             let next_task_num = next_task_num.clone();
             let output_tx = output_tx.clone();
+            let gave_up = gave_up.clone();
 
             tokio::spawn(async move {
-                let mut current_task_num = next_task_num.fetch_add(1, Ordering::Relaxed);
+                let mut current_task_num = next_task_num.fetch_add(step, Ordering::Relaxed);
+                let mut restarts = 0u32;
+                let mut task_restarts = 0u32;
 
                 'outer: loop {
-                    // Don't bothr doing any more if the output chan is closed.
-                    if output_tx.is_closed() {
+                    // Don't bothr doing any more if the output chan is closed or some task gave up.
+                    if output_tx.is_closed() || gave_up.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    // If we keep failing, give up. Outputs are emitted in order, so nothing
+                    // after this task number can be output anyway, and so all tasks will stop.
+                    if restarts > MAX_RESTARTS {
+                        gave_up.store(true, Ordering::Relaxed);
                         return;
                     }
+                    if restarts > 0 {
+                        tokio::time::sleep(Duration::from_millis(250 * restarts as u64)).await;
+                    }
 
                     // Initialise new workload. This is passed to each task.
                     let workload = match init_fn(task_idx, &state).await {
@@ -61,8 +127,21 @@ where
                             // None indicates nothing left to do in this runner.
                             return;
                         }
-                        Err(_e) => {
-                            // eprintln!("Error instantiating workload for task {task_idx} (running {current_task_num}): {e}");
+                        Err(error) => {
+                            let error = RunnerError {
+                                task_number: current_task_num,
+                                stage: RunnerStage::Init,
+                                gave_up: false,
+                                error,
+                            };
+                            if output_tx
+                                .send((current_task_num, RunnerItem::Error(error)))
+                                .await
+                                .is_err()
+                            {
+                                return;
+                            }
+                            restarts += 1;
                             continue;
                         }
                     };
@@ -70,20 +149,49 @@ where
                     // Now, loop running tasks and outputting the results until something goes wrong.
                     let mut task_retries = 0usize;
                     'inner: loop {
+                        if gave_up.load(Ordering::Relaxed) {
+                            return;
+                        }
                         let output = match task_fn(current_task_num, &workload).await {
                             Ok(Some(output)) => {
                                 task_retries = 0;
-                                output
+                                restarts = 0;
+                                task_restarts = 0;
+                                RunnerItem::Output(output)
                             }
                             Ok(None) => {
                                 // None indicates nothing left to do in this runner.
                                 return;
                             }
-                            Err(e) => {
+                            Err(error) => {
                                 task_retries += 1;
-                                if task_retries > MAX_RETRIES {
+                                let mut error = RunnerError {
+                                    task_number: current_task_num,
+                                    stage: RunnerStage::Task,
+                                    gave_up: false,
+                                    error,
+                                };
+                                if task_retries > MAX_RETRIES && task_restarts >= MAX_TASK_RESTARTS
+                                {
+                                    // This task number keeps failing however we run it, so give
+                                    // up on it and emit the failure in its place; otherwise the
+                                    // outputs after it could never be emitted in order.
+                                    task_retries = 0;
+                                    task_restarts = 0;
+                                    restarts += 1;
+                                    error.gave_up = true;
+                                    RunnerItem::Error(error)
+                                } else if task_retries > MAX_RETRIES {
                                     // task went wrong a few times; re-initialize everything.
-                                    eprintln!("Error running task {current_task_num}: {e:?}");
+                                    if output_tx
+                                        .send((current_task_num, RunnerItem::Error(error)))
+                                        .await
+                                        .is_err()
+                                    {
+                                        return;
+                                    }
+                                    restarts += 1;
+                                    task_restarts += 1;
                                     continue 'outer;
                                 } else {
                                     // Try task again.
@@ -93,11 +201,11 @@ where
                         };
 
                         // Task done; pull the next task ID to run the next task.
-                        if let Err(_) = output_tx.send((current_task_num, output)).await {
+                        if output_tx.send((current_task_num, output)).await.is_err() {
                             return;
                         }
 
-                        current_task_num = next_task_num.fetch_add(1, Ordering::Relaxed);
+                        current_task_num = next_task_num.fetch_add(step, Ordering::Relaxed);
                     }
                 }
             });
@@ -108,24 +216,55 @@ where
         drop(output_tx);
 
         // Here, we wait to gather outputs and run the output fn in order for each output,
-        // buffering up any that are received out of order.
+        // buffering up any that are received out of order. Task numbers that we gave up on
+        // are reported in their place.
         let mut output_task_number = starting_task_number;
         let mut outputs = HashMap::new();
+        let mut failed_task_numbers = Vec::new();
+        let mut emit = |item: RunnerItem<Output>| {
+            if let RunnerItem::Error(e) = &item {
+                if e.gave_up {
+                    failed_task_numbers.push(e.task_number);
+                }
+            }
+            (self.output_fn)(item)
+        };
         while let Some((task_num, output)) = output_rx.recv().await {
+            // Errors that we retried after aren't in place of any output, so hand them over
+            // straight away.
+            if matches!(&output, RunnerItem::Error(e) if !e.gave_up) {
+                emit(output)?;
+                continue;
+            }
             if task_num == output_task_number {
-                (self.output_fn)(output)?;
-                output_task_number += 1;
+                emit(output)?;
+                output_task_number += step;
                 // Once we see the output we're looking for, we also check to find as
                 // many subsequent outputs we might already have been sent.
                 while let Some(output) = outputs.remove(&output_task_number) {
-                    (self.output_fn)(output)?;
-                    output_task_number += 1;
+                    emit(output)?;
+                    output_task_number += step;
                 }
             } else {
                 outputs.insert(task_num, output);
             }
         }
 
+        if gave_up.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!(
+                "Stopped at task {output_task_number} after repeated failures"
+            ))
+            .context(ErrorKind::NetworkExhausted);
+        }
+        if !failed_task_numbers.is_empty() {
+            let failed: Vec<_> = failed_task_numbers.iter().map(|n| n.to_string()).collect();
+            return Err(anyhow::anyhow!(
+                "Gave up on tasks {} after repeated failures",
+                failed.join(", ")
+            ))
+            .context(ErrorKind::NetworkExhausted);
+        }
+
         Ok(())
     }
 }
@@ -145,9 +284,89 @@ impl<T> RoundRobin<T> {
             idx: Arc::new(AtomicUsize::new(0)),
         }
     }
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
     pub fn get(&self) -> &T {
         let idx = self.idx.fetch_add(1, Ordering::Relaxed);
         let n = idx % self.items.len();
         &self.items[n]
     }
 }
+
+/// A pool which runs CPU heavy work (ie decoding) on blocking threads, allowing at most
+/// some fixed number of these to run at once. This allows decoding parallelism to be tuned
+/// separately from the number of tasks fetching data.
+#[derive(Debug, Clone)]
+pub struct DecodePool {
+    permits: Arc<tokio::sync::Semaphore>,
+}
+
+impl DecodePool {
+    /// Create a new pool. If no number of threads is given, we default to the
+    /// number of CPUs available.
+    pub fn new(threads: Option<usize>) -> Self {
+        let threads = threads
+            .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+            .unwrap_or(1)
+            .max(1);
+        DecodePool {
+            permits: Arc::new(tokio::sync::Semaphore::new(threads)),
+        }
+    }
+
+    /// Run some function on the pool, waiting for a free thread if necessary.
+    pub async fn run<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let _permit = self.permits.acquire().await?;
+        let res = tokio::task::spawn_blocking(f).await?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn failed_tasks_are_output_in_order() {
+        let outputs = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let task_outputs = outputs.clone();
+        let res = Runner::new(
+            (),
+            |_n, _state| async { Ok(Some(())) },
+            |task_num, _workload| async move {
+                match task_num {
+                    2 => Err(anyhow::anyhow!("Task 2 always fails")),
+                    n if n > 4 => Ok(None),
+                    n => Ok(Some(n)),
+                }
+            },
+            move |item| {
+                match item {
+                    RunnerItem::Output(output) => task_outputs.lock().unwrap().push(Ok(output)),
+                    RunnerItem::Error(e) if e.gave_up => {
+                        task_outputs.lock().unwrap().push(Err(e.task_number))
+                    }
+                    RunnerItem::Error(e) => assert_eq!(e.task_number, 2),
+                }
+                Ok(())
+            },
+        )
+        .run(2, 0)
+        .await;
+
+        assert_eq!(
+            *outputs.lock().unwrap(),
+            vec![Ok(0), Ok(1), Err(2), Ok(3), Ok(4)]
+        );
+        let err = res.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ErrorKind>(),
+            Some(&ErrorKind::NetworkExhausted)
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Splitting the blocks (or, for storage, the `number`s) of a run between several machines.
+//! Shard `i/n` takes every `n`th item, starting from the `i`th, so the shards never overlap
+//! and together cover everything.
+
+use anyhow::anyhow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    /// Which shard this is, from 0 to `count - 1`.
+    pub index: u64,
+    /// How many shards there are.
+    pub count: u64,
+}
+
+impl Default for Shard {
+    /// A single shard containing everything.
+    fn default() -> Self {
+        Shard { index: 0, count: 1 }
+    }
+}
+
+impl Shard {
+    /// Is this item in the shard?
+    pub fn contains(&self, n: u64) -> bool {
+        n % self.count == self.index
+    }
+
+    /// The first item in the shard at or after `n`.
+    pub fn first_from(&self, n: u64) -> u64 {
+        let offset = (self.index + self.count - n % self.count) % self.count;
+        n + offset
+    }
+}
+
+impl std::str::FromStr for Shard {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("shard should take the form $index/$count, eg 0/4"))?;
+        let index: u64 = index.trim().parse()?;
+        let count: u64 = count.trim().parse()?;
+        if index >= count {
+            anyhow::bail!("shard index should be less than the number of shards ({count})");
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shards_cover_everything_once() {
+        let shards: Vec<Shard> = ["0/3", "1/3", "2/3"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        for n in 10..40 {
+            assert_eq!(shards.iter().filter(|s| s.contains(n)).count(), 1);
+        }
+        assert_eq!(shards[0].first_from(10), 12);
+        assert_eq!(shards[1].first_from(10), 10);
+        assert_eq!(shards[2].first_from(10), 11);
+        assert_eq!(Shard::default().first_from(10), 10);
+
+        assert!("3/3".parse::<Shard>().is_err());
+        assert!("1".parse::<Shard>().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Publish messages to Kafka. Messages are produced with `acks=all` and idempotence enabled,
+//! and each is only counted as published once its delivery has been reported, so that
+//! checkpoints are never saved past messages that might have been lost.
+
+use anyhow::{anyhow, Context};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
+use rdkafka::ClientConfig;
+use std::time::Duration;
+
+/// How many messages we'll produce before waiting for them to be delivered.
+const MAX_PENDING_DELIVERIES: usize = 1024;
+
+/// How long to wait for the brokers when connecting.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic_prefix: String,
+    pending: Vec<DeliveryFuture>,
+}
+
+impl KafkaSink {
+    /// Connect to the Kafka brokers in the given URL, eg `kafka://b1:9092,b2:9092/historic`.
+    /// The path gives the prefix of the topics that messages are published to.
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let (brokers, topic_prefix) = parse_url(url)?;
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", &brokers)
+            .set("acks", "all")
+            .set("enable.idempotence", "true")
+            .create()
+            .context("Could not create a Kafka producer")?;
+
+        // Creating a producer doesn't connect to anything, so check that the brokers can be
+        // reached before we start decoding.
+        let client = producer.clone();
+        tokio::task::spawn_blocking(move || client.client().fetch_metadata(None, CONNECT_TIMEOUT))
+            .await?
+            .with_context(|| format!("Could not reach the Kafka brokers {brokers}"))?;
+
+        Ok(KafkaSink {
+            producer,
+            topic_prefix,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Publish a message. `kind` is appended to our topic prefix, eg to publish to
+    /// the topic `historic.blocks`.
+    pub async fn publish(&mut self, kind: &str, payload: &[u8]) -> anyhow::Result<()> {
+        let topic = format!("{}.{kind}", self.topic_prefix);
+        loop {
+            let record = FutureRecord::<(), _>::to(&topic).payload(payload);
+            match self.producer.send_result(record) {
+                Ok(delivery) => {
+                    self.pending.push(delivery);
+                    break;
+                }
+                // The producer's queue is full, so wait for what's in it to be delivered.
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                    self.confirm().await?;
+                }
+                Err((e, _)) => return Err(e).context("Could not produce a Kafka message"),
+            }
+        }
+        if self.pending.len() >= MAX_PENDING_DELIVERIES {
+            self.confirm().await?;
+        }
+        Ok(())
+    }
+
+    /// Wait until everything that we've published so far has been delivered.
+    pub async fn confirm(&mut self) -> anyhow::Result<()> {
+        for delivery in self.pending.drain(..) {
+            delivery
+                .await
+                .map_err(|_| anyhow!("The Kafka producer stopped before delivering a message"))?
+                .map_err(|(e, _)| e)
+                .context("Kafka did not accept a message")?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a sink URL into the brokers to connect to and the prefix of the topics to publish to,
+/// which defaults to `historic`.
+fn parse_url(url: &str) -> anyhow::Result<(String, String)> {
+    let rest = url
+        .strip_prefix("kafka://")
+        .ok_or_else(|| anyhow!("Kafka sink URLs should begin with kafka://"))?;
+    let (brokers, path) = rest.split_once('/').unwrap_or((rest, ""));
+    if brokers.is_empty() {
+        anyhow::bail!("Sink URL has no brokers");
+    }
+    let topic_prefix = match path.trim_matches('/') {
+        "" => "historic".to_string(),
+        path => path.replace('/', "."),
+    };
+    Ok((brokers.to_owned(), topic_prefix))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_urls() {
+        assert_eq!(
+            parse_url("kafka://b1:9092,b2:9092/historic/polkadot").unwrap(),
+            ("b1:9092,b2:9092".to_owned(), "historic.polkadot".to_owned())
+        );
+        assert_eq!(
+            parse_url("kafka://127.0.0.1:9092").unwrap(),
+            ("127.0.0.1:9092".to_owned(), "historic".to_owned())
+        );
+        assert!(parse_url("kafka:///historic").is_err());
+    }
+}
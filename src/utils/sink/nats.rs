@@ -0,0 +1,92 @@
+//! Publish messages to NATS JetStream. Each message is only counted as published once
+//! JetStream has acknowledged storing it, so that checkpoints are never saved past messages
+//! that might have been lost.
+
+use anyhow::{anyhow, Context};
+use async_nats::jetstream::{self, context::PublishAckFuture};
+
+/// How many messages we'll publish before waiting for them to be acknowledged.
+const MAX_PENDING_ACKS: usize = 1024;
+
+pub struct NatsSink {
+    jetstream: jetstream::Context,
+    subject: String,
+    pending: Vec<PublishAckFuture>,
+}
+
+impl NatsSink {
+    /// Connect to the NATS server in the given URL (eg `nats://127.0.0.1:4222/historic`). The
+    /// path gives the subject that messages are published under. If no JetStream stream
+    /// captures this subject yet, one is created with the subject as its name.
+    pub async fn connect(uri: &http::Uri) -> anyhow::Result<Self> {
+        let authority = uri
+            .authority()
+            .ok_or_else(|| anyhow!("Sink URL has no host"))?;
+        let port = if authority.port().is_some() { "" } else { ":4222" };
+        let subject = subject_from_path(uri.path());
+
+        let client = async_nats::connect(format!("nats://{authority}{port}")).await?;
+        let jetstream = jetstream::new(client);
+
+        let wildcard = format!("{subject}.>");
+        if jetstream.stream_by_subject(wildcard.clone()).await.is_err() {
+            jetstream
+                .get_or_create_stream(jetstream::stream::Config {
+                    name: subject.replace('.', "_"),
+                    subjects: vec![wildcard],
+                    ..Default::default()
+                })
+                .await
+                .with_context(|| format!("Could not create a JetStream stream for {subject}"))?;
+        }
+
+        Ok(NatsSink {
+            jetstream,
+            subject,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Publish a message. `kind` is appended to our subject, eg to publish to `historic.blocks`.
+    pub async fn publish(&mut self, kind: &str, payload: &[u8]) -> anyhow::Result<()> {
+        let ack = self
+            .jetstream
+            .publish(format!("{}.{kind}", self.subject), payload.to_vec().into())
+            .await?;
+        self.pending.push(ack);
+        if self.pending.len() >= MAX_PENDING_ACKS {
+            self.confirm().await?;
+        }
+        Ok(())
+    }
+
+    /// Wait until JetStream has acknowledged everything that we've published so far.
+    pub async fn confirm(&mut self) -> anyhow::Result<()> {
+        for ack in self.pending.drain(..) {
+            ack.await.context("JetStream did not acknowledge a message")?;
+        }
+        Ok(())
+    }
+}
+
+/// The subject to publish under, given the path of a sink URL. Slashes are treated like dots, so
+/// `/historic/polkadot` becomes `historic.polkadot`. This defaults to `historic`.
+fn subject_from_path(path: &str) -> String {
+    match path.trim_matches('/') {
+        "" => "historic".to_string(),
+        path => path.replace('/', "."),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn subjects_come_from_url_paths() {
+        assert_eq!(subject_from_path(""), "historic");
+        assert_eq!(subject_from_path("/"), "historic");
+        assert_eq!(subject_from_path("/blocks"), "blocks");
+        assert_eq!(subject_from_path("/historic/polkadot/"), "historic.polkadot");
+    }
+}
@@ -0,0 +1,177 @@
+//! A small templating language for printing parts of a decoded [`Value`]. Templates are plain
+//! text in which `{path}` is replaced by whatever is found at that path, and `{{` and `}}` are
+//! literal braces. A path is a `.` separated list of field names or indexes, eg
+//! `{value.data.free}` or `{value.0}`. The name of an enum variant can also be given to step
+//! into it, eg `{value.Some.amount}`.
+
+use super::{value_to_bytes, write_compact_value_fmt};
+use crate::decoding::decode_options::DecodeOptions;
+use scale_value::{At, Primitive, Value, ValueDef};
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Part>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Path(Vec<String>),
+}
+
+impl std::str::FromStr for Template {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut path = String::new();
+                    let mut is_closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            is_closed = true;
+                            break;
+                        }
+                        path.push(c);
+                    }
+                    if !is_closed {
+                        anyhow::bail!("template has an unclosed '{{'");
+                    }
+                    if path.trim().is_empty() {
+                        anyhow::bail!("template has an empty '{{}}'");
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    let path = path.trim().split('.').map(|s| s.to_string()).collect();
+                    parts.push(Part::Path(path));
+                }
+                '}' => {
+                    anyhow::bail!("template has an unopened '}}'; use '}}}}' for a literal '}}'")
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template(parts))
+    }
+}
+
+impl Template {
+    /// Render this template, looking up paths in the given value.
+    pub fn render(&self, root: &Value<String>, opts: &DecodeOptions) -> anyhow::Result<String> {
+        let mut out = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Path(path) => {
+                    let value = lookup(root, path)
+                        .ok_or_else(|| anyhow::anyhow!("'{}' not found", path.join(".")))?;
+                    write_plain(&mut out, value, opts)?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Find the value at some path.
+fn lookup<'a>(mut value: &'a Value<String>, path: &[String]) -> Option<&'a Value<String>> {
+    for segment in path {
+        let next = match segment.parse::<usize>() {
+            Ok(idx) => value.at(idx),
+            Err(_) => value.at(segment),
+        };
+        value = match (next, &value.value) {
+            (Some(next), _) => next,
+            // Naming the current variant steps into it, so we stay where we are.
+            (None, ValueDef::Variant(variant)) if &variant.name == segment => value,
+            (None, _) => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Write a value without any decoration where possible, so that it's easy to use in scripts.
+fn write_plain(
+    out: &mut String,
+    value: &Value<String>,
+    opts: &DecodeOptions,
+) -> anyhow::Result<()> {
+    use std::fmt::Write;
+    match &value.value {
+        ValueDef::Primitive(Primitive::String(s)) => out.push_str(s),
+        ValueDef::Primitive(Primitive::Char(c)) => out.push(*c),
+        ValueDef::Primitive(Primitive::Bool(b)) => write!(out, "{b}")?,
+        ValueDef::Primitive(Primitive::U128(n)) => write!(out, "{n}")?,
+        ValueDef::Primitive(Primitive::I128(n)) => write!(out, "{n}")?,
+        ValueDef::Variant(variant) if variant.values.is_empty() => out.push_str(&variant.name),
+        _ => match value_to_bytes(value) {
+            Some(bytes) if !bytes.is_empty() => out.push_str(&subxt::utils::to_hex(bytes)),
+            _ => write_compact_value_fmt(out, value, opts)?,
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_templates() {
+        let value = Value::named_composite([
+            (
+                "data",
+                Value::named_composite([("free", Value::u128(100)), ("reserved", Value::u128(5))]),
+            ),
+            ("nonce", Value::u128(3)),
+            (
+                "status",
+                Value::unnamed_variant("Some", [Value::string("x")]),
+            ),
+            ("kind", Value::unnamed_variant("Free", [])),
+            (
+                "id",
+                Value::unnamed_composite([Value::u128(1), Value::u128(2)]),
+            ),
+        ])
+        .map_context(|_| String::new());
+        let root = Value {
+            value: ValueDef::Composite(scale_value::Composite::named([("value", value)])),
+            context: String::new(),
+        };
+
+        let opts = DecodeOptions::default();
+        let render = |t: &str| t.parse::<Template>().unwrap().render(&root, &opts).unwrap();
+        assert_eq!(render("{value.data.free}"), "100");
+        assert_eq!(
+            render("free={value.data.free} n={ value.nonce }"),
+            "free=100 n=3"
+        );
+        assert_eq!(render("{value.status.Some.0}"), "x");
+        assert_eq!(render("{value.status.0}"), "x");
+        assert_eq!(render("{value.kind}"), "Free");
+        assert_eq!(render("{value.id}"), "0x0102");
+        assert_eq!(render("{{literal}}"), "{literal}");
+
+        let template: Template = "{value.data.missing}".parse().unwrap();
+        assert!(template.render(&root, &opts).is_err());
+        assert!("{value".parse::<Template>().is_err());
+        assert!("value}".parse::<Template>().is_err());
+        assert!("{}".parse::<Template>().is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Accounting for the requests made to, and the bytes received from, RPC nodes during a run.
+//! Every connection made via [`super::rpc::RpcOpts::connect`] records its usage here, and the
+//! totals are printed at the end of the run.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+/// The requests made and bytes received for something.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub requests: u64,
+    pub bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: u64) {
+        self.requests += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// The usage recorded so far in this run.
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct UsageSummary {
+    /// The total across all endpoints.
+    pub total: Usage,
+    /// The usage of each endpoint, by URL.
+    pub endpoints: BTreeMap<String, Usage>,
+    /// The usage attributed to each storage entry (as `Pallet.Entry`) via [`accounted_to`].
+    pub storage_entries: BTreeMap<String, Usage>,
+}
+
+static USAGE: Mutex<UsageSummary> = Mutex::new(UsageSummary {
+    total: Usage {
+        requests: 0,
+        bytes: 0,
+    },
+    endpoints: BTreeMap::new(),
+    storage_entries: BTreeMap::new(),
+});
+
+tokio::task_local! {
+    /// The storage entry that requests made in the current task are for, if any.
+    static STORAGE_ENTRY: String;
+}
+
+/// Attribute any requests made while running the given future to some storage entry, as well
+/// as to the endpoint that they're made to.
+pub async fn accounted_to<F: Future>(storage_entry: String, f: F) -> F::Output {
+    STORAGE_ENTRY.scope(storage_entry, f).await
+}
+
+/// Record a request made to the given URL, whose response was the given number of bytes.
+pub fn record(url: &str, bytes: u64) {
+    let storage_entry = STORAGE_ENTRY.try_with(Clone::clone).ok();
+    let mut usage = USAGE.lock().unwrap();
+    usage.total.add(bytes);
+    usage
+        .endpoints
+        .entry(url.to_owned())
+        .or_default()
+        .add(bytes);
+    if let Some(storage_entry) = storage_entry {
+        usage
+            .storage_entries
+            .entry(storage_entry)
+            .or_default()
+            .add(bytes);
+    }
+}
+
+/// The usage recorded so far.
+pub fn summary() -> UsageSummary {
+    USAGE.lock().unwrap().clone()
+}
+
+/// Write a human readable summary of some usage, including the storage entries which received
+/// the most bytes.
+pub fn write_summary<W: std::io::Write>(mut w: W, summary: &UsageSummary) -> std::io::Result<()> {
+    const MAX_STORAGE_ENTRIES: usize = 10;
+
+    let describe = |usage: &Usage| format!("{} requests, {}", usage.requests, bytes(usage.bytes));
+    writeln!(w, "Network usage: {}", describe(&summary.total))?;
+    for (url, usage) in &summary.endpoints {
+        writeln!(w, "  {url}: {}", describe(usage))?;
+    }
+
+    if !summary.storage_entries.is_empty() {
+        let mut entries: Vec<_> = summary.storage_entries.iter().collect();
+        entries.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.bytes));
+        writeln!(w, "Largest storage entries:")?;
+        for (entry, usage) in entries.into_iter().take(MAX_STORAGE_ENTRIES) {
+            writeln!(w, "  {entry}: {}", describe(usage))?;
+        }
+    }
+    Ok(())
+}
+
+/// A human readable number of bytes.
+fn bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{n} B");
+    }
+    let mut size = n as f64;
+    let mut unit = "B";
+    for u in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = u;
+    }
+    format!("{size:.1} {unit}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(bytes(100), "100 B");
+        assert_eq!(bytes(1536), "1.5 KiB");
+        assert_eq!(bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[tokio::test]
+    async fn attributes_requests_to_storage_entries() {
+        accounted_to("Test.Entry".to_owned(), async {
+            record("wss://test.example.com", 10);
+            record("wss://test.example.com", 5);
+        })
+        .await;
+
+        let summary = summary();
+        assert_eq!(
+            summary.storage_entries["Test.Entry"],
+            Usage {
+                requests: 2,
+                bytes: 15
+            }
+        );
+        assert_eq!(
+            summary.endpoints["wss://test.example.com"],
+            Usage {
+                requests: 2,
+                bytes: 15
+            }
+        );
+    }
+}